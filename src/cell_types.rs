@@ -1,2 +1,13 @@
 pub mod conway;
 pub mod wireworld;
+pub mod greenberg_hastings;
+pub mod cyclic;
+pub mod epidemic;
+pub mod von_neumann;
+pub mod rule_table;
+pub mod loops;
+pub mod rule_tree;
+pub mod golly_rule;
+pub mod isotropic;
+pub mod multicolor;
+pub mod parity;
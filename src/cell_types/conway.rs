@@ -0,0 +1,50 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::{Boundary, Grid};
+
+pub const DEAD: u8 = 0;
+pub const ALIVE: u8 = 1;
+
+pub fn count_live_neighbors(grid: &Grid<u8>, x: usize, y: usize, boundary: Boundary) -> usize {
+    grid.get_moore_neighborhood_around(x, y, boundary).filter(|&cell| cell != DEAD).count()
+}
+
+pub fn advance_cell(grid: &Grid<u8>, x: usize, y: usize, boundary: Boundary) -> u8 {
+    let live_neighbors = count_live_neighbors(grid, x, y, boundary);
+
+    match (grid.get_cell(x, y) != DEAD, live_neighbors) {
+        (true, 2) | (true, 3) | (false, 3) => ALIVE,
+        _ => DEAD,
+    }
+}
+
+pub fn life_rule(boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+    Box::new(move |grid, x, y| advance_cell(grid, x, y, boundary))
+}
+
+/// Patterns shared by tests that check other rules and environments against Life.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use crate::environment::Environment;
+
+    /// A glider in the top left corner, heading southeast.
+    pub fn glider(x: usize, y: usize) -> u8 {
+        matches!((x, y), (1, 0) | (2, 1) | (0, 2) | (1, 2) | (2, 2)) as u8
+    }
+
+    /// A fixed, irregular soup with about two in five cells alive.
+    pub fn soup(x: usize, y: usize) -> u8 {
+        ((x * 7 + y * 11 + x * y) % 5 < 2) as u8
+    }
+
+    /// Runs Life from a glider on a wrapping `size` by `size` grid, checking that the grid `advance` returns after each
+    /// step matches it. `advance` should start from the same glider.
+    pub fn assert_glider_matches_life(size: usize, generations: usize, mut advance: impl FnMut() -> Grid<u8>) {
+        let mut life = Environment::new(size, size, glider, life_rule(Boundary::Wrapping));
+
+        for generation in 1..=generations {
+            life.advance();
+            assert_eq!(&advance(), life.get_grid(), "differs from Life at generation {}", generation);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::{neighborhood_offsets, Boundary, NeighborhoodType};
+
+/// Griffeath's cyclic cellular automaton. A cell in state k advances to state k + 1 (wrapping back to 0 after the last
+/// color) when at least `threshold` of its neighbors are already in state k + 1.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Cyclic {
+    pub n_colors: u8,
+    pub threshold: usize,
+    pub neighborhood_type: NeighborhoodType,
+    pub range: usize,
+    pub boundary: Boundary,
+}
+
+impl Cyclic {
+    /// Panics if `n_colors` is 0.
+    pub fn new(range: usize, threshold: usize, n_colors: u8, neighborhood_type: NeighborhoodType) -> Cyclic {
+        assert!(n_colors > 0, "A cyclic automaton needs at least one color");
+
+        Cyclic {
+            n_colors,
+            threshold,
+            neighborhood_type,
+            range,
+            boundary: Boundary::Wrapping,
+        }
+    }
+
+    /// Named rules from the cyclic CA catalogue, as range/threshold/colors/neighborhood.
+    pub fn presets() -> Vec<(&'static str, Cyclic)> {
+        use NeighborhoodType::{Moore, VonNeumann};
+
+        vec![
+            ("313", Cyclic::new(1, 3, 3, Moore)),
+            ("Perfect spirals", Cyclic::new(1, 3, 4, Moore)),
+            ("Cyclic spirals", Cyclic::new(3, 5, 8, Moore)),
+            ("Lava lamp", Cyclic::new(2, 10, 3, Moore)),
+            ("Fossil debris", Cyclic::new(2, 9, 4, Moore)),
+            ("Turbulent phase", Cyclic::new(2, 5, 8, Moore)),
+            ("Cubism", Cyclic::new(2, 5, 3, VonNeumann)),
+            ("Maps", Cyclic::new(2, 3, 5, VonNeumann)),
+        ]
+    }
+
+    pub fn preset(name: &str) -> Option<Cyclic> {
+        Self::presets().into_iter().find(|(preset_name, _)| preset_name.eq_ignore_ascii_case(name)).map(|(_, config)| config)
+    }
+
+    pub fn rule(&self) -> Box<AdvanceCellF<u8>> {
+        assert!(self.n_colors > 0, "A cyclic automaton needs at least one color");
+        let config = *self;
+        let offsets = neighborhood_offsets(self.neighborhood_type, self.range);
+
+        Box::new(move |grid, x, y| {
+            let cell = grid.get_cell(x, y);
+            let successor = ((cell as usize + 1) % config.n_colors as usize) as u8;
+            let successor_neighbors = grid.get_neighbors_around(x, y, &offsets, config.boundary).filter(|&neighbor| neighbor == successor).count();
+
+            if successor_neighbors >= config.threshold { successor } else { cell }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_cell_advances_at_threshold() {
+        let config = Cyclic { boundary: Boundary::Bounded, ..Cyclic::new(1, 2, 3, NeighborhoodType::VonNeumann) };
+        let mut env = Environment::new(3, 3, |_x, _y| 0, config.rule());
+        env.bulk_set_readable(vec![(1, 0, 1), (0, 1, 1), (2, 1, 1), (2, 2, 2)]);
+
+        env.advance();
+
+        assert_eq!(env.get_cell(1, 1), 1);
+        assert_eq!(env.get_cell(0, 0), 1);
+        // Only one neighbor in state 0, which is the successor of 2.
+        assert_eq!(env.get_cell(2, 2), 2);
+    }
+
+    #[test]
+    fn test_last_color_wraps_to_first() {
+        let config = Cyclic::new(1, 1, 3, NeighborhoodType::Moore);
+        let mut env = Environment::new(3, 3, |_x, _y| 0, config.rule());
+        env.bulk_set_readable(vec![(1, 1, 2)]);
+
+        env.advance();
+
+        assert_eq!(env.get_cell(1, 1), 0);
+    }
+
+    #[test]
+    fn test_presets_self_organize() {
+        let mut rng = StdRng::seed_from_u64(32);
+        let config = Cyclic::preset("313").unwrap();
+        let soup: Vec<u8> = (0..30 * 30).map(|_| rng.random_range(0..config.n_colors)).collect();
+        let mut env = Environment::new(30, 30, |x, y| soup[y * 30 + x], config.rule());
+
+        let mut changed = false;
+        for _ in 0..50 {
+            let before = env.get_grid().clone();
+            env.advance();
+            changed |= &before != env.get_grid();
+        }
+
+        assert!(changed);
+        assert_eq!(Cyclic::preset("perfect SPIRALS").unwrap().n_colors, 4);
+        assert_eq!(Cyclic::preset("nonexistent"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_zero_colors() {
+        Cyclic::new(1, 1, 0, NeighborhoodType::Moore);
+    }
+}
@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::environment::{AdvanceCellF, Environment};
+use crate::grid::{neighborhood_offsets, Boundary, Grid, NeighborhoodType};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum EpidemicCell {
+    Susceptible,
+    /// Infected, but not infectious yet. Counts down the generations left until the cell becomes infectious.
+    Exposed { remaining: u32 },
+    /// Counts down the generations left until the cell recovers.
+    Infected { remaining: u32 },
+    Recovered,
+    /// Immune from the start, and never part of the outbreak.
+    Vaccinated,
+}
+
+/// A probabilistic SEIR model. Every generation, each infected neighbor of a susceptible cell independently infects it
+/// with `infection_probability`. Newly infected cells spend `incubation_period` generations exposed, then
+/// `infectious_period` generations infectious, then recover for good. An incubation period of 0 gives the SIR model.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Epidemic {
+    pub infection_probability: f64,
+    pub incubation_period: u32,
+    pub infectious_period: u32,
+    pub neighborhood_type: NeighborhoodType,
+    pub boundary: Boundary,
+}
+
+impl Epidemic {
+    pub fn sir(infection_probability: f64, infectious_period: u32) -> Epidemic {
+        Self::seir(infection_probability, 0, infectious_period)
+    }
+
+    /// Panics if the infection probability isn't between 0 and 1.
+    pub fn seir(infection_probability: f64, incubation_period: u32, infectious_period: u32) -> Epidemic {
+        assert!((0.0..=1.0).contains(&infection_probability), "The infection probability must be between 0 and 1");
+
+        Epidemic {
+            infection_probability,
+            incubation_period,
+            infectious_period,
+            neighborhood_type: NeighborhoodType::Moore,
+            boundary: Boundary::Bounded,
+        }
+    }
+
+    fn newly_infected(&self) -> EpidemicCell {
+        if self.incubation_period > 0 {
+            EpidemicCell::Exposed { remaining: self.incubation_period }
+        } else {
+            EpidemicCell::Infected { remaining: self.infectious_period.max(1) }
+        }
+    }
+
+    pub fn rule(&self, seed: u64) -> Box<AdvanceCellF<EpidemicCell>> {
+        assert!((0.0..=1.0).contains(&self.infection_probability), "The infection probability must be between 0 and 1");
+        let config = *self;
+        let offsets = neighborhood_offsets(self.neighborhood_type, 1);
+        let rng = RefCell::new(StdRng::seed_from_u64(seed));
+
+        Box::new(move |grid, x, y| {
+            match grid.get_cell(x, y) {
+                EpidemicCell::Susceptible => {
+                    let infected_neighbors = grid.get_neighbors_around(x, y, &offsets, config.boundary)
+                        .filter(|neighbor| matches!(neighbor, EpidemicCell::Infected { .. }))
+                        .count();
+                    let mut rng = rng.borrow_mut();
+
+                    if (0..infected_neighbors).any(|_| rng.random_bool(config.infection_probability)) {
+                        config.newly_infected()
+                    } else {
+                        EpidemicCell::Susceptible
+                    }
+                },
+                EpidemicCell::Exposed { remaining: 1 } => EpidemicCell::Infected { remaining: config.infectious_period.max(1) },
+                EpidemicCell::Exposed { remaining } => EpidemicCell::Exposed { remaining: remaining - 1 },
+                EpidemicCell::Infected { remaining: 1 } => EpidemicCell::Recovered,
+                EpidemicCell::Infected { remaining } => EpidemicCell::Infected { remaining: remaining - 1 },
+                cell => cell,
+            }
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct CompartmentTotals {
+    pub susceptible: usize,
+    pub exposed: usize,
+    pub infected: usize,
+    pub recovered: usize,
+    pub vaccinated: usize,
+}
+
+impl CompartmentTotals {
+    pub fn count(grid: &Grid<EpidemicCell>) -> CompartmentTotals {
+        let mut totals = CompartmentTotals::default();
+
+        for y in 0..grid.get_height() {
+            for x in 0..grid.get_width() {
+                match grid.get_cell(x, y) {
+                    EpidemicCell::Susceptible => totals.susceptible += 1,
+                    EpidemicCell::Exposed { .. } => totals.exposed += 1,
+                    EpidemicCell::Infected { .. } => totals.infected += 1,
+                    EpidemicCell::Recovered => totals.recovered += 1,
+                    EpidemicCell::Vaccinated => totals.vaccinated += 1,
+                }
+            }
+        }
+
+        totals
+    }
+}
+
+/// An epidemic environment that records the compartment totals of every generation, starting with the initial one.
+pub struct EpidemicRun {
+    env: Environment<EpidemicCell>,
+    history: Vec<CompartmentTotals>,
+}
+
+impl EpidemicRun {
+    /// Starts an outbreak from the given index cases. Cells set in the vaccination mask start out vaccinated, and can't
+    /// be index cases.
+    pub fn new(config: Epidemic, vaccination_mask: &Grid<bool>, index_cases: &[(usize, usize)], seed: u64) -> EpidemicRun {
+        let width = vaccination_mask.get_width();
+        let height = vaccination_mask.get_height();
+
+        let mut env = Environment::new(width, height, |x, y| {
+            if vaccination_mask.get_cell(x, y) { EpidemicCell::Vaccinated } else { EpidemicCell::Susceptible }
+        }, config.rule(seed));
+
+        let index_cells = index_cases.iter()
+            .filter(|&&(x, y)| x >= width || y >= height || !vaccination_mask.get_cell(x, y))
+            .map(|&(x, y)| (x, y, EpidemicCell::Infected { remaining: config.infectious_period.max(1) }))
+            .collect();
+        env.bulk_set_readable(index_cells);
+
+        let history = vec![CompartmentTotals::count(env.get_grid())];
+
+        EpidemicRun {
+            env,
+            history,
+        }
+    }
+
+    pub fn get_environment(&self) -> &Environment<EpidemicCell> {
+        &self.env
+    }
+
+    pub fn get_history(&self) -> &[CompartmentTotals] {
+        &self.history
+    }
+
+    pub fn is_over(&self) -> bool {
+        let latest = self.history.last().unwrap();
+        latest.exposed == 0 && latest.infected == 0
+    }
+
+    pub fn advance(&mut self) {
+        self.env.advance();
+        self.history.push(CompartmentTotals::count(self.env.get_grid()));
+    }
+
+    pub fn history_csv(&self) -> String {
+        let mut csv = String::from("generation,susceptible,exposed,infected,recovered,vaccinated\n");
+
+        for (generation, totals) in self.history.iter().enumerate() {
+            csv.push_str(&format!("{},{},{},{},{},{}\n",
+                generation, totals.susceptible, totals.exposed, totals.infected, totals.recovered, totals.vaccinated));
+        }
+
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certain_sir_spread() {
+        let mask = Grid::new_filled(5, 5, false);
+        let mut run = EpidemicRun::new(Epidemic::sir(1.0, 2), &mask, &[(2, 2)], 0);
+
+        run.advance();
+
+        let grid = run.get_environment().get_grid();
+        assert_eq!(grid.get_cell(2, 2), EpidemicCell::Infected { remaining: 1 });
+        assert_eq!(grid.get_cell(1, 1), EpidemicCell::Infected { remaining: 2 });
+        assert_eq!(grid.get_cell(0, 0), EpidemicCell::Susceptible);
+
+        run.advance();
+
+        let grid = run.get_environment().get_grid();
+        assert_eq!(grid.get_cell(2, 2), EpidemicCell::Recovered);
+        assert_eq!(grid.get_cell(0, 0), EpidemicCell::Infected { remaining: 2 });
+    }
+
+    #[test]
+    fn test_seir_incubation() {
+        let mask = Grid::new_filled(3, 1, false);
+        let mut run = EpidemicRun::new(Epidemic::seir(1.0, 2, 1), &mask, &[(0, 0)], 0);
+
+        run.advance();
+        assert_eq!(run.get_environment().get_cell(1, 0), EpidemicCell::Exposed { remaining: 2 });
+
+        run.advance();
+        assert_eq!(run.get_environment().get_cell(1, 0), EpidemicCell::Exposed { remaining: 1 });
+
+        run.advance();
+        assert_eq!(run.get_environment().get_cell(1, 0), EpidemicCell::Infected { remaining: 1 });
+        assert_eq!(run.get_environment().get_cell(2, 0), EpidemicCell::Susceptible);
+    }
+
+    #[test]
+    fn test_vaccination_mask_blocks_spread() {
+        // A vaccinated wall down the middle column keeps the right side safe.
+        let mask = Grid::new(7, 7, |x, _y| x == 3);
+        let mut run = EpidemicRun::new(Epidemic::sir(1.0, 3), &mask, &[(0, 0), (3, 3)], 0);
+
+        while !run.is_over() {
+            run.advance();
+        }
+
+        let grid = run.get_environment().get_grid();
+        assert_eq!(grid.get_cell(3, 3), EpidemicCell::Vaccinated);
+        assert_eq!(grid.get_cell(6, 6), EpidemicCell::Susceptible);
+        assert_eq!(grid.get_cell(2, 6), EpidemicCell::Recovered);
+    }
+
+    #[test]
+    fn test_history_is_conserved_and_exported() {
+        let mask = Grid::new(20, 20, |x, y| (x + y) % 5 == 0);
+        let mut run = EpidemicRun::new(Epidemic::seir(0.3, 2, 4), &mask, &[(10, 11)], 33);
+
+        for _ in 0..30 {
+            run.advance();
+        }
+
+        assert_eq!(run.get_history().len(), 31);
+        for totals in run.get_history() {
+            assert_eq!(totals.susceptible + totals.exposed + totals.infected + totals.recovered + totals.vaccinated, 400);
+        }
+
+        let csv = run.history_csv();
+        assert_eq!(csv.lines().count(), 32);
+        assert!(csv.starts_with("generation,susceptible,exposed,infected,recovered,vaccinated\n0,"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_infection_probability_past_one() {
+        Epidemic::sir(1.5, 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rule_rejects_nan_infection_probability() {
+        let _ = Epidemic { infection_probability: f64::NAN, ..Epidemic::sir(0.5, 4) }.rule(0);
+    }
+}
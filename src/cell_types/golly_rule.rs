@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use crate::cell_types::rule_table::{RuleTable, StateSet, Symmetry, TableNeighborhood};
+use crate::cell_types::rule_tree::RuleTree;
+use crate::environment::AdvanceCellF;
+use crate::grid::Boundary;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuleBody {
+    Table(RuleTable),
+    Tree(RuleTree),
+}
+
+/// A rule read from one of Golly's `.rule` files. The transitions come from the `@TABLE` section if there is one, and
+/// the `@TREE` section otherwise. `@COLORS` is optional, and other sections like `@ICONS` are ignored.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GollyRule {
+    name: String,
+    body: RuleBody,
+    colors: Vec<Option<[u8; 3]>>,
+}
+
+/// Splits the file into its sections, keyed by the section name without the @. Comments and blank lines are dropped.
+fn split_sections(text: &str) -> HashMap<String, (String, Vec<&str>)> {
+    let mut sections: HashMap<String, (String, Vec<&str>)> = HashMap::new();
+    let mut current = None;
+
+    for line in text.lines().map(|line| line.split('#').next().unwrap().trim()).filter(|line| !line.is_empty()) {
+        if let Some(header) = line.strip_prefix('@') {
+            let (name, argument) = header.split_once(char::is_whitespace).unwrap_or((header, ""));
+            let name = name.to_ascii_uppercase();
+            sections.entry(name.clone()).or_insert_with(|| (argument.trim().to_string(), vec![]));
+            current = Some(name);
+        } else if let Some(name) = &current {
+            sections.get_mut(name).unwrap().1.push(line);
+        }
+    }
+
+    sections
+}
+
+fn parse_neighborhood(name: &str) -> Option<TableNeighborhood> {
+    match name.to_ascii_lowercase().as_str() {
+        "vonneumann" => Some(TableNeighborhood::VonNeumann),
+        "moore" => Some(TableNeighborhood::Moore),
+        "hexagonal" => Some(TableNeighborhood::Hexagonal),
+        "onedimensional" => Some(TableNeighborhood::OneDimensional),
+        _ => None,
+    }
+}
+
+fn parse_symmetry(name: &str) -> Option<Symmetry> {
+    match name.to_ascii_lowercase().as_str() {
+        "none" => Some(Symmetry::None),
+        "rotate4" => Some(Symmetry::Rotate4),
+        "rotate4reflect" => Some(Symmetry::Rotate4Reflect),
+        "rotate8" => Some(Symmetry::Rotate8),
+        "rotate8reflect" => Some(Symmetry::Rotate8Reflect),
+        "reflect_horizontal" => Some(Symmetry::ReflectHorizontal),
+        "rotate2" => Some(Symmetry::Rotate2),
+        "rotate3" => Some(Symmetry::Rotate3),
+        "rotate6" => Some(Symmetry::Rotate6),
+        "rotate6reflect" => Some(Symmetry::Rotate6Reflect),
+        "reflect" => Some(Symmetry::Reflect),
+        "permute" => Some(Symmetry::Permute),
+        _ => None,
+    }
+}
+
+/// Reads a `var` line's name and states. Elements of the set can be states or earlier variables.
+fn parse_variable(definition: &str, variables: &HashMap<String, Vec<u8>>) -> Option<(String, Vec<u8>)> {
+    let (name, set) = definition.split_once('=')?;
+    let set = set.trim().strip_prefix('{')?.strip_suffix('}')?;
+
+    let mut states = vec![];
+    for element in set.split(',').map(str::trim) {
+        match element.parse::<u8>() {
+            Ok(state) => states.push(state),
+            Err(_) => states.extend(variables.get(element)?),
+        }
+    }
+
+    Some((name.trim().to_string(), states))
+}
+
+/// Adds one transition line. A variable that appears more than once is bound, so it takes the same state everywhere
+/// in the line, and the transition is expanded over its states. Variables that appear once just match any of theirs.
+fn add_table_line(table: &mut RuleTable, tokens: &[&str], variables: &HashMap<String, Vec<u8>>) -> Option<()> {
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    for &token in tokens {
+        if token.parse::<u8>().is_err() {
+            variables.get(token)?;
+            *occurrences.entry(token).or_default() += 1;
+        }
+    }
+
+    let output = *tokens.last()?;
+    if output.parse::<u8>().is_err() && occurrences[output] < 2 {
+        return None;
+    }
+
+    let mut bound: Vec<&str> = occurrences.iter().filter(|&(_, &count)| count > 1).map(|(&name, _)| name).collect();
+    bound.sort();
+
+    // Count through every assignment of states to the bound variables, like an odometer.
+    let mut digits = vec![0; bound.len()];
+    loop {
+        let state_of = |token: &str| -> StateSet {
+            match token.parse::<u8>() {
+                Ok(state) => StateSet::single(state),
+                Err(_) => match bound.iter().position(|&name| name == token) {
+                    Some(index) => StateSet::single(variables[token][digits[index]]),
+                    None => StateSet::from_states(variables[token].iter().copied()),
+                },
+            }
+        };
+
+        let sets: Vec<StateSet> = tokens[..tokens.len() - 1].iter().map(|&token| state_of(token)).collect();
+        let next = state_of(output).max_state()?;
+        if !table.add_pattern(&sets, next) {
+            return None;
+        }
+
+        let Some(index) = (0..bound.len()).find(|&index| digits[index] + 1 < variables[bound[index]].len()) else { break };
+        digits[index] += 1;
+        digits[..index].fill(0);
+    }
+
+    Some(())
+}
+
+fn new_table(settings: &HashMap<String, String>) -> Option<RuleTable> {
+    let n_states = settings.get("n_states")?.parse().ok()?;
+    let neighborhood = parse_neighborhood(settings.get("neighborhood")?)?;
+    let symmetry = settings.get("symmetries").map_or(Some(Symmetry::None), |name| parse_symmetry(name))?;
+
+    RuleTable::new(n_states, neighborhood, symmetry)
+}
+
+fn parse_table(lines: &[&str]) -> Option<RuleTable> {
+    let mut settings: HashMap<String, String> = HashMap::new();
+    let mut variables = HashMap::new();
+    let mut table: Option<RuleTable> = None;
+
+    for &line in lines {
+        if let Some(definition) = line.strip_prefix("var ") {
+            let (name, states) = parse_variable(definition, &variables)?;
+            variables.insert(name, states);
+        } else if let Some((key, value)) = line.split_once(':') {
+            settings.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+        } else {
+            if table.is_none() {
+                table = Some(new_table(&settings)?);
+            }
+
+            // Transitions are comma separated, or written as one character per cell when every state is a single digit.
+            let tokens: Vec<&str> = if line.contains(',') {
+                line.split(',').map(str::trim).collect()
+            } else {
+                line.char_indices().filter(|(_, c)| !c.is_whitespace()).map(|(index, c)| &line[index..index + c.len_utf8()]).collect()
+            };
+
+            let table = table.as_mut().unwrap();
+            if tokens.len() != table.get_neighborhood().n_neighbors() + 2 {
+                return None;
+            }
+            add_table_line(table, &tokens, &variables)?;
+        }
+    }
+
+    match table {
+        Some(table) => Some(table),
+        None => new_table(&settings),
+    }
+}
+
+/// Reads `@COLORS` lines. Each is either a state and its red, green and blue, or two colors to spread as a gradient
+/// across every state but 0.
+fn parse_colors(lines: &[&str], n_states: u16) -> Option<Vec<Option<[u8; 3]>>> {
+    let mut colors = vec![None; n_states as usize];
+
+    for line in lines {
+        let numbers: Vec<u8> = line.split_whitespace().map(|number| number.parse().ok()).collect::<Option<_>>()?;
+        match numbers[..] {
+            [state, red, green, blue] => *colors.get_mut(state as usize)? = Some([red, green, blue]),
+            [r1, g1, b1, r2, g2, b2] => {
+                for (step, color) in colors.iter_mut().skip(1).enumerate() {
+                    let t = if n_states > 2 { step as f64 / (n_states - 2) as f64 } else { 0.0 };
+                    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * t).round() as u8;
+                    *color = Some([mix(r1, r2), mix(g1, g2), mix(b1, b2)]);
+                }
+            },
+            _ => return None,
+        }
+    }
+
+    Some(colors)
+}
+
+impl GollyRule {
+    /// Returns None if the file has no `@RULE` line, or no `@TABLE` or `@TREE` section that parses. Tables can use any of
+    /// Golly's neighborhoods, with the symmetries Golly allows for it, and up to 256 states.
+    pub fn parse(text: &str) -> Option<GollyRule> {
+        let sections = split_sections(text);
+        let (name, _) = sections.get("RULE")?;
+
+        let body = if let Some((_, lines)) = sections.get("TABLE") {
+            RuleBody::Table(parse_table(lines)?)
+        } else {
+            RuleBody::Tree(RuleTree::parse(&sections.get("TREE")?.1.join("\n"))?)
+        };
+
+        let n_states = match &body {
+            RuleBody::Table(table) => table.get_n_states(),
+            RuleBody::Tree(tree) => tree.get_n_states(),
+        };
+        let colors = match sections.get("COLORS") {
+            Some((_, lines)) => parse_colors(lines, n_states)?,
+            None => vec![None; n_states as usize],
+        };
+
+        Some(GollyRule {
+            name: name.clone(),
+            body,
+            colors,
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_body(&self) -> &RuleBody {
+        &self.body
+    }
+
+    pub fn get_n_states(&self) -> u16 {
+        self.colors.len() as u16
+    }
+
+    /// The color `@COLORS` gives the state, if it gives one.
+    pub fn get_color(&self, state: u8) -> Option<[u8; 3]> {
+        self.colors.get(state as usize).copied().flatten()
+    }
+
+    pub fn rule(&self, boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+        match &self.body {
+            RuleBody::Table(table) => table.rule(boundary),
+            RuleBody::Tree(tree) => tree.rule(boundary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::environment::Environment;
+    use crate::grid::hex_offsets_for_row;
+    use crate::wireworld;
+    use crate::wireworld::grid::CellType;
+
+    const WIREWORLD: &str = "@RULE WireWorld
+# Electrons travelling along copper wires.
+
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={a}
+var c={a}
+var d={a}
+var e={a}
+var f={a}
+var g={a}
+var h={a}
+var i={0,2,3}
+var j={i}
+var k={i}
+var l={i}
+var m={i}
+var n={i}
+var o={i}
+# A head becomes a tail, and a tail becomes copper.
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+# Copper with one or two heads next to it becomes a head.
+3,1,i,j,k,l,m,n,o,1
+3,1,1,j,k,l,m,n,o,1
+
+@COLORS
+0 48 48 48
+1 0 128 255
+2 255 255 255
+3 255 128 0
+";
+
+    fn random_circuit(seed: u64) -> Vec<(usize, usize, u8)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..30).flat_map(|y| (0..30).map(move |x| (x, y))).map(|(x, y)| (x, y, rng.random_range(0..4))).collect()
+    }
+
+    fn cell_type(state: u8) -> CellType {
+        match state {
+            0 => CellType::Empty,
+            1 => CellType::ElectronHead,
+            2 => CellType::ElectronTail,
+            _ => CellType::Conductor,
+        }
+    }
+
+    fn assert_matches_built_in_wireworld(rule: &GollyRule) {
+        let cells = random_circuit(39);
+        let mut built_in = wireworld::environment::Environment::new_empty(30, 30);
+        built_in.bulk_set_readable(cells.iter().map(|&(x, y, state)| (x, y, cell_type(state))).collect());
+        let mut from_rule = Environment::new(30, 30, |x, y| cells[y * 30 + x].2, rule.rule(Boundary::Bounded));
+
+        for _ in 0..20 {
+            built_in.advance();
+            from_rule.advance();
+            for (x, y, _) in &cells {
+                assert_eq!(cell_type(from_rule.get_cell(*x, *y)), built_in.get_cell(*x, *y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_wireworld_table_matches_built_in() {
+        let rule = GollyRule::parse(WIREWORLD).unwrap();
+
+        assert_eq!(rule.get_name(), "WireWorld");
+        assert_eq!(rule.get_n_states(), 4);
+        assert_eq!(rule.get_color(1), Some([0, 128, 255]));
+        assert_matches_built_in_wireworld(&rule);
+    }
+
+    #[test]
+    fn test_wireworld_tree_matches_built_in() {
+        let RuleBody::Table(table) = GollyRule::parse(WIREWORLD).unwrap().get_body().clone() else { panic!() };
+        let tree = RuleTree::from_fn(4, TableNeighborhood::Moore, |center, neighbors| table.lookup(center, neighbors).unwrap_or(center));
+        let rule = GollyRule::parse(&format!("@RULE WireWorld\n@TREE\n{}", tree.to_text())).unwrap();
+
+        assert!(matches!(rule.get_body(), RuleBody::Tree(_)));
+        assert_eq!(rule.get_color(1), None);
+        assert_matches_built_in_wireworld(&rule);
+    }
+
+    #[test]
+    fn test_bound_variables_and_compact_transitions() {
+        // A cell copies its north neighbor whenever its east neighbor matches it, in either orientation.
+        let text = "@RULE Copy\n@TABLE\nn_states:3\nneighborhood:vonNeumann\nsymmetries:none\nvar a={0,1,2}\nvar b={a}\nvar c={a}\nvar d={a}\nb,a,a,c,d,a\n";
+        let RuleBody::Table(table) = GollyRule::parse(text).unwrap().get_body().clone() else { panic!() };
+
+        assert_eq!(table.lookup(0, &[2, 2, 1, 0]), Some(2));
+        assert_eq!(table.lookup(0, &[2, 1, 1, 0]), None);
+        assert_eq!(table.lookup(0, &[0, 0, 1, 0]), Some(0));
+
+        let text = "@RULE Compact\n@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate4\n000011\n";
+        let RuleBody::Table(table) = GollyRule::parse(text).unwrap().get_body().clone() else { panic!() };
+        assert_eq!(table.lookup(0, &[0, 1, 0, 0]), Some(1));
+    }
+
+    #[test]
+    fn test_hexagonal_neighborhood() {
+        // Empty cells next to a live cell come alive, which reaches the six cells the grid counts as hex neighbors.
+        let text = "@RULE HexGrowth\n@TABLE\nn_states:2\nneighborhood:hexagonal\nsymmetries:rotate6\n0,1,0,0,0,0,0,1\n";
+        let rule = GollyRule::parse(text).unwrap();
+
+        for (cx, cy) in [(3, 2), (3, 3)] {
+            let mut env = Environment::new(7, 7, |x, y| ((x, y) == (cx, cy)) as u8, rule.rule(Boundary::Bounded));
+            env.advance();
+
+            let mut expected: Vec<(usize, usize)> = hex_offsets_for_row(cy).iter()
+                .map(|&(dx, dy)| (cx.checked_add_signed(dx).unwrap(), cy.checked_add_signed(dy).unwrap()))
+                .collect();
+            expected.push((cx, cy));
+            expected.sort();
+            let mut live: Vec<(usize, usize)> = (0..7).flat_map(|y| (0..7).map(move |x| (x, y))).filter(|&(x, y)| env.get_cell(x, y) == 1).collect();
+            live.sort();
+            assert_eq!(live, expected);
+        }
+    }
+
+    #[test]
+    fn test_one_dimensional_neighborhood() {
+        // Rule 90, where each cell becomes the XOR of its left and right neighbors.
+        let text = "@RULE Rule90\n@TABLE\nn_states:2\nneighborhood:oneDimensional\nsymmetries:reflect\nvar a={0,1}\nvar b={0,1}\na,1,0,1\nb,0,0,0\nb,1,1,0\n";
+        let rule = GollyRule::parse(text).unwrap();
+        let mut env = Environment::new(9, 2, |x, y| (x == 4 && y == 0) as u8, rule.rule(Boundary::Bounded));
+
+        env.run(2);
+
+        let rows: Vec<Vec<u8>> = (0..2).map(|y| (0..9).map(|x| env.get_cell(x, y)).collect()).collect();
+        assert_eq!(rows, vec![vec![0, 0, 1, 0, 0, 0, 1, 0, 0], vec![0; 9]]);
+    }
+
+    #[test]
+    fn test_256_states() {
+        let text = "@RULE Wide\n@TABLE\nn_states:256\nneighborhood:vonNeumann\nsymmetries:reflect_horizontal\n0,255,0,0,0,255\n255,0,0,0,0,254\n";
+        let rule = GollyRule::parse(text).unwrap();
+        let RuleBody::Table(table) = rule.get_body() else { panic!() };
+
+        assert_eq!(rule.get_n_states(), 256);
+        assert_eq!(table.lookup(0, &[255, 0, 0, 0]), Some(255));
+        assert_eq!(table.lookup(255, &[0, 0, 0, 0]), Some(254));
+        assert!(GollyRule::parse(&text.replace("256", "257")).is_none());
+    }
+
+    #[test]
+    fn test_color_gradient() {
+        let text = "@RULE Fade\n@TABLE\nn_states:4\nneighborhood:Moore\n@COLORS\n0 10 10 10\n255 0 0 0 0 255\n3 0 255 0\n";
+        let rule = GollyRule::parse(text).unwrap();
+
+        assert_eq!(rule.get_color(0), Some([10, 10, 10]));
+        assert_eq!(rule.get_color(1), Some([255, 0, 0]));
+        assert_eq!(rule.get_color(2), Some([128, 0, 128]));
+        assert_eq!(rule.get_color(3), Some([0, 255, 0]));
+    }
+
+    #[test]
+    fn test_rejects_unsupported_rules() {
+        let table = |header: &str| format!("@RULE Test\n@TABLE\n{}\n0,0,0,0,0,1\n", header);
+
+        assert!(GollyRule::parse(&table("n_states:2\nneighborhood:vonNeumann\nsymmetries:rotate4")).is_some());
+        assert!(GollyRule::parse(&table("n_states:2\nneighborhood:hexagonal\nsymmetries:rotate4")).is_none());
+        assert!(GollyRule::parse(&table("n_states:2\nneighborhood:vonNeumann\nsymmetries:rotate8")).is_none());
+        assert!(GollyRule::parse(&table("n_states:1\nneighborhood:vonNeumann\nsymmetries:none")).is_none());
+        assert!(GollyRule::parse("@TABLE\nn_states:2\nneighborhood:Moore\n").is_none());
+        // The output can only use a variable that's bound to one of the inputs.
+        assert!(GollyRule::parse("@RULE Test\n@TABLE\nn_states:2\nneighborhood:vonNeumann\nvar a={0,1}\n0,0,0,0,0,a\n").is_none());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::{neighborhood_offsets, Boundary, Grid, NeighborhoodType};
+
+pub const RESTING: u8 = 0;
+pub const EXCITED: u8 = 1;
+
+/// The Greenberg–Hastings model of an excitable medium. A resting cell becomes excited when at least `threshold` of
+/// its neighbors are excited. An excited cell then passes through `refractory_length` refractory states, during which
+/// it can't be excited, before it rests again. Wireworld's head -> tail -> conductor cycle is a close cousin.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct GreenbergHastings {
+    pub threshold: usize,
+    pub refractory_length: u8,
+    pub neighborhood_type: NeighborhoodType,
+    pub range: usize,
+    pub boundary: Boundary,
+}
+
+impl GreenbergHastings {
+    /// Panics if `refractory_length` is 255, since the states past the refractory ones wouldn't fit in a u8.
+    pub fn new(threshold: usize, refractory_length: u8, neighborhood_type: NeighborhoodType, range: usize, boundary: Boundary) -> GreenbergHastings {
+        assert!(refractory_length < u8::MAX, "At most 254 refractory states fit in a u8 cell");
+
+        GreenbergHastings {
+            threshold,
+            refractory_length,
+            neighborhood_type,
+            range,
+            boundary,
+        }
+    }
+
+    /// The original three-state model: one refractory state, and a single excited von Neumann neighbor is enough.
+    pub fn classic() -> GreenbergHastings {
+        GreenbergHastings::new(1, 1, NeighborhoodType::VonNeumann, 1, Boundary::Wrapping)
+    }
+
+    /// Wider neighborhoods and longer refractory periods give thick, smooth spiral waves.
+    pub fn thick_spirals() -> GreenbergHastings {
+        GreenbergHastings::new(3, 6, NeighborhoodType::Moore, 2, Boundary::Wrapping)
+    }
+
+    pub fn n_states(&self) -> usize {
+        self.refractory_length as usize + 2
+    }
+
+    pub fn rule(&self) -> Box<AdvanceCellF<u8>> {
+        assert!(self.refractory_length < u8::MAX, "At most 254 refractory states fit in a u8 cell");
+        let config = *self;
+        let offsets = neighborhood_offsets(self.neighborhood_type, self.range);
+
+        Box::new(move |grid, x, y| {
+            match grid.get_cell(x, y) {
+                RESTING => {
+                    let excited_neighbors = grid.get_neighbors_around(x, y, &offsets, config.boundary).filter(|&cell| cell == EXCITED).count();
+                    if excited_neighbors >= config.threshold { EXCITED } else { RESTING }
+                },
+                state if state > config.refractory_length => RESTING,
+                state => state + 1,
+            }
+        })
+    }
+
+    /// A straight wavefront with a free end, which curls up into a spiral around the center of the grid.
+    pub fn broken_wave_seed(&self, width: usize, height: usize) -> impl Fn(usize, usize) -> u8 + use<> {
+        let refractory_length = self.refractory_length as usize;
+
+        move |x, y| {
+            if y > height / 2 {
+                RESTING
+            } else if x == width / 2 {
+                EXCITED
+            } else if x < width / 2 && width / 2 - x <= refractory_length {
+                (width / 2 - x) as u8 + 1
+            } else {
+                RESTING
+            }
+        }
+    }
+}
+
+pub fn count_excited(grid: &Grid<u8>) -> usize {
+    (0..grid.get_height()).map(|y| (0..grid.get_width()).filter(|&x| grid.get_cell(x, y) == EXCITED).count()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_classic_single_excitation() {
+        let config = GreenbergHastings { boundary: Boundary::Bounded, ..GreenbergHastings::classic() };
+        let mut env = Environment::new(5, 5, |_x, _y| RESTING, config.rule());
+        env.bulk_set_readable(vec![(2, 2, EXCITED)]);
+
+        env.advance();
+
+        assert_eq!(env.get_cell(2, 2), 2);
+        assert_eq!(env.get_cell(2, 1), EXCITED);
+        assert_eq!(env.get_cell(1, 1), RESTING);
+        assert_eq!(count_excited(env.get_grid()), 4);
+
+        env.advance();
+
+        // The center is past its refractory period, but its neighbors are refractory, so it isn't re-excited.
+        assert_eq!(env.get_cell(2, 2), RESTING);
+        assert_eq!(env.get_cell(2, 1), 2);
+        assert_eq!(env.get_cell(1, 1), EXCITED);
+    }
+
+    #[test]
+    fn test_threshold_blocks_excitation() {
+        let config = GreenbergHastings { threshold: 2, boundary: Boundary::Bounded, ..GreenbergHastings::classic() };
+        let mut env = Environment::new(3, 3, |_x, _y| RESTING, config.rule());
+        env.bulk_set_readable(vec![(0, 1, EXCITED)]);
+
+        env.advance();
+
+        assert_eq!(count_excited(env.get_grid()), 0);
+    }
+
+    #[test]
+    fn test_refractory_cycle_length() {
+        let config = GreenbergHastings { refractory_length: 4, boundary: Boundary::Bounded, ..GreenbergHastings::classic() };
+        let mut env = Environment::new(1, 1, |_x, _y| EXCITED, config.rule());
+
+        let mut states = vec![];
+        for _ in 0..6 {
+            env.advance();
+            states.push(env.get_cell(0, 0));
+        }
+
+        assert_eq!(config.n_states(), 6);
+        assert_eq!(states, vec![2, 3, 4, 5, 0, 0]);
+    }
+
+    #[test]
+    fn test_broken_wave_keeps_spiraling() {
+        let config = GreenbergHastings::classic();
+        let mut env = Environment::new(40, 40, config.broken_wave_seed(40, 40), config.rule());
+
+        for _ in 0..200 {
+            env.advance();
+        }
+
+        assert!(count_excited(env.get_grid()) > 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_refractory_length_past_u8() {
+        GreenbergHastings::new(1, 255, NeighborhoodType::VonNeumann, 1, Boundary::Wrapping);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::cell_types::conway::{ALIVE, DEAD};
+use crate::environment::AdvanceCellF;
+use crate::grid::{Boundary, Grid};
+
+/// The neighbor offsets clockwise from north, which is the order the Hensel letter patterns are written in.
+const RING_OFFSETS: [(isize, isize); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+/// The bit each ring position has in a 512-entry table index, which reads the 3x3 block row by row with the northwest
+/// corner as the most significant bit.
+const RING_INDEX_BITS: [u16; 8] = [128, 64, 8, 1, 2, 4, 32, 256];
+const CENTER_INDEX_BIT: u16 = 16;
+
+/// One pattern for each Hensel letter with up to four neighbors, as ring bits clockwise from north. The letters for five
+/// to seven neighbors name the complements of the patterns for three to one.
+const HENSEL_LETTERS: [(u8, char, [u8; 8]); 31] = [
+    (1, 'e', [1, 0, 0, 0, 0, 0, 0, 0]),
+    (1, 'c', [0, 1, 0, 0, 0, 0, 0, 0]),
+    (2, 'a', [1, 1, 0, 0, 0, 0, 0, 0]),
+    (2, 'e', [1, 0, 1, 0, 0, 0, 0, 0]),
+    (2, 'k', [1, 0, 0, 1, 0, 0, 0, 0]),
+    (2, 'i', [1, 0, 0, 0, 1, 0, 0, 0]),
+    (2, 'c', [0, 1, 0, 1, 0, 0, 0, 0]),
+    (2, 'n', [0, 1, 0, 0, 0, 1, 0, 0]),
+    (3, 'a', [1, 1, 1, 0, 0, 0, 0, 0]),
+    (3, 'n', [1, 1, 0, 1, 0, 0, 0, 0]),
+    (3, 'r', [1, 1, 0, 0, 1, 0, 0, 0]),
+    (3, 'q', [1, 1, 0, 0, 0, 1, 0, 0]),
+    (3, 'j', [1, 1, 0, 0, 0, 0, 1, 0]),
+    (3, 'i', [1, 1, 0, 0, 0, 0, 0, 1]),
+    (3, 'e', [1, 0, 1, 0, 1, 0, 0, 0]),
+    (3, 'k', [1, 0, 1, 0, 0, 1, 0, 0]),
+    (3, 'y', [1, 0, 0, 1, 0, 1, 0, 0]),
+    (3, 'c', [0, 1, 0, 1, 0, 1, 0, 0]),
+    (4, 'a', [1, 1, 1, 1, 0, 0, 0, 0]),
+    (4, 'r', [1, 1, 1, 0, 1, 0, 0, 0]),
+    (4, 'q', [1, 1, 1, 0, 0, 1, 0, 0]),
+    (4, 'i', [1, 1, 0, 1, 1, 0, 0, 0]),
+    (4, 'y', [1, 1, 0, 1, 0, 1, 0, 0]),
+    (4, 'k', [1, 1, 0, 1, 0, 0, 1, 0]),
+    (4, 'n', [1, 1, 0, 1, 0, 0, 0, 1]),
+    (4, 'z', [1, 1, 0, 0, 1, 1, 0, 0]),
+    (4, 'j', [1, 1, 0, 0, 1, 0, 1, 0]),
+    (4, 't', [1, 0, 0, 1, 1, 1, 0, 0]),
+    (4, 'w', [1, 1, 0, 0, 0, 1, 1, 0]),
+    (4, 'e', [1, 0, 1, 0, 1, 0, 1, 0]),
+    (4, 'c', [0, 1, 0, 1, 0, 1, 0, 1]),
+];
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn ring_mask(bits: &[u8; 8]) -> u8 {
+    bits.iter().enumerate().fold(0, |mask, (position, &bit)| mask | (bit << position))
+}
+
+/// Every ring mask a pattern can be turned into by rotating and reflecting it.
+fn orbit(mask: u8) -> Vec<u8> {
+    let rotate = |mask: u8| mask.rotate_left(2);
+    let reflect = |mask: u8| (0..8).fold(0, |reflected, position| reflected | (((mask >> position) & 1) << ((8 - position) % 8)));
+
+    let mut images = vec![];
+    let mut image = mask;
+    for _ in 0..4 {
+        images.push(image);
+        images.push(reflect(image));
+        image = rotate(image);
+    }
+    images.sort_unstable();
+    images.dedup();
+
+    images
+}
+
+/// The letters that can follow a neighbor count, in the order the table lists them.
+fn letters_for(count: u8) -> Vec<char> {
+    let base = if count > 4 { 8 - count } else { count };
+    HENSEL_LETTERS.iter().filter(|&&(n, _, _)| n == base).map(|&(_, letter, _)| letter).collect()
+}
+
+/// The ring masks a count and letter stand for, or None if the letter doesn't go with the count.
+fn letter_masks(count: u8, letter: char) -> Option<Vec<u8>> {
+    let base = if count > 4 { 8 - count } else { count };
+    let &(_, _, bits) = HENSEL_LETTERS.iter().find(|&&(n, l, _)| n == base && l == letter)?;
+    let masks = orbit(ring_mask(&bits));
+
+    Some(if count > 4 { masks.into_iter().map(|mask| !mask).collect() } else { masks })
+}
+
+fn ring_masks_with_count(count: u8) -> impl Iterator<Item = u8> {
+    (0..=u8::MAX).filter(move |mask| mask.count_ones() == count as u32)
+}
+
+fn table_index(center: bool, ring: u8) -> usize {
+    let ring_bits: u16 = (0..8).filter(|position| ring & (1 << position) != 0).map(|position| RING_INDEX_BITS[position]).sum();
+    (ring_bits + if center { CENTER_INDEX_BIT } else { 0 }) as usize
+}
+
+/// Parses the conditions after a B or S, like `2-a3`, into the ring masks they match.
+fn parse_conditions(conditions: &str) -> Option<Vec<u8>> {
+    let chars: Vec<char> = conditions.chars().collect();
+    let mut masks = vec![];
+    let mut index = 0;
+
+    while index < chars.len() {
+        let count = chars[index].to_digit(10).filter(|&count| count <= 8)? as u8;
+        index += 1;
+
+        let negated = chars.get(index) == Some(&'-');
+        if negated {
+            index += 1;
+        }
+        let mut letters = vec![];
+        while let Some(&letter) = chars.get(index).filter(|c| c.is_ascii_lowercase()) {
+            letters.push(letter);
+            index += 1;
+        }
+        if negated && letters.is_empty() {
+            return None;
+        }
+
+        if letters.is_empty() {
+            masks.extend(ring_masks_with_count(count));
+            continue;
+        }
+
+        let valid_letters = letters_for(count);
+        if letters.iter().any(|letter| !valid_letters.contains(letter)) {
+            return None;
+        }
+        let chosen = if negated { valid_letters.into_iter().filter(|letter| !letters.contains(letter)).collect() } else { letters };
+        for letter in chosen {
+            masks.extend(letter_masks(count, letter)?);
+        }
+    }
+
+    Some(masks)
+}
+
+/// A two-state rule on the Moore neighborhood, stored as the next state for each of the 512 possible 3x3 blocks. This
+/// covers totalistic rules, isotropic non-totalistic rules in Hensel notation, and arbitrary MAP rules.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LookupRule {
+    table: Vec<bool>,
+}
+
+impl LookupRule {
+    /// Builds the table from a function of the block index, where bit 8 is the northwest cell and bit 0 the southeast,
+    /// reading row by row.
+    pub fn from_fn(next_state: impl Fn(usize) -> bool) -> LookupRule {
+        LookupRule {
+            table: (0..512).map(next_state).collect(),
+        }
+    }
+
+    /// Parses a rule like `B3/S23` or `B2-a/S12`. Each neighbor count can be followed by the Hensel letters for the
+    /// arrangements to include, or a - and the letters to leave out. Returns None for letters that don't go with their
+    /// count, or anything else malformed.
+    pub fn from_hensel(rule: &str) -> Option<LookupRule> {
+        let (birth, survival) = rule.split_once('/')?;
+        let birth = parse_conditions(birth.strip_prefix(['B', 'b'])?)?;
+        let survival = parse_conditions(survival.strip_prefix(['S', 's'])?)?;
+
+        let mut table = vec![false; 512];
+        for ring in birth {
+            table[table_index(false, ring)] = true;
+        }
+        for ring in survival {
+            table[table_index(true, ring)] = true;
+        }
+
+        Some(LookupRule { table })
+    }
+
+    /// Parses `MAP` followed by the 512-bit table in base64, with the bit for block 0 first. The padding is optional.
+    pub fn from_map(rule: &str) -> Option<LookupRule> {
+        let encoded = rule.strip_prefix("MAP")?.trim_end_matches('=');
+        if encoded.len() != 86 {
+            return None;
+        }
+
+        let mut table = Vec::with_capacity(516);
+        for c in encoded.bytes() {
+            let value = BASE64_ALPHABET.iter().position(|&symbol| symbol == c)?;
+            table.extend((0..6).rev().map(|bit| (value >> bit) & 1 == 1));
+        }
+        table.truncate(512);
+
+        Some(LookupRule { table })
+    }
+
+    /// Parses either a MAP string or a rule in Hensel notation.
+    pub fn parse(rule: &str) -> Option<LookupRule> {
+        if rule.starts_with("MAP") { Self::from_map(rule) } else { Self::from_hensel(rule) }
+    }
+
+    /// The rule as a MAP string, without padding.
+    pub fn to_map_string(&self) -> String {
+        let mut map = String::from("MAP");
+        for chunk in self.table.chunks(6) {
+            let value = (0..6).fold(0, |value, bit| (value << 1) | chunk.get(bit).copied().unwrap_or(false) as usize);
+            map.push(BASE64_ALPHABET[value] as char);
+        }
+
+        map
+    }
+
+    pub fn next_state(&self, block_index: usize) -> bool {
+        self.table[block_index]
+    }
+
+    pub fn block_index(grid: &Grid<u8>, x: usize, y: usize, boundary: Boundary) -> usize {
+        let ring = RING_OFFSETS.iter().enumerate().fold(0u8, |ring, (position, &offset)| {
+            let alive = grid.get_neighbor_coord(x, y, offset, boundary).is_some_and(|(nx, ny)| grid.get_cell(nx, ny) == ALIVE);
+            ring | ((alive as u8) << position)
+        });
+
+        table_index(grid.get_cell(x, y) == ALIVE, ring)
+    }
+
+    pub fn rule(&self, boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+        let table = self.table.clone();
+
+        Box::new(move |grid, x, y| {
+            if table[Self::block_index(grid, x, y, boundary)] { ALIVE } else { DEAD }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway::fixtures;
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_letters_partition_each_count() {
+        for count in 0..=8u8 {
+            let mut masks: Vec<u8> = letters_for(count).into_iter().flat_map(|letter| letter_masks(count, letter).unwrap()).collect();
+            if count == 0 || count == 8 {
+                assert!(masks.is_empty());
+                continue;
+            }
+
+            masks.sort_unstable();
+            let n_masks = masks.len();
+            masks.dedup();
+            assert_eq!(masks.len(), n_masks, "letters overlap for {}", count);
+            assert_eq!(masks, ring_masks_with_count(count).collect::<Vec<u8>>(), "letters don't cover {}", count);
+        }
+    }
+
+    #[test]
+    fn test_totalistic_life_matches_built_in() {
+        let rule = LookupRule::parse("B3/S23").unwrap();
+        let mut lookup = Environment::new(12, 12, fixtures::glider, rule.rule(Boundary::Wrapping));
+
+        fixtures::assert_glider_matches_life(12, 50, || {
+            lookup.advance();
+            lookup.get_grid().clone()
+        });
+    }
+
+    #[test]
+    fn test_hensel_letters_and_negation() {
+        assert_eq!(LookupRule::parse("B2-a/S12"), LookupRule::parse("B2ceikn/S12"));
+        assert_eq!(LookupRule::parse("B3aceijknqry/S23"), LookupRule::parse("B3/S23"));
+        assert_ne!(LookupRule::parse("B2-a/S12"), LookupRule::parse("B2/S12"));
+
+        // Two diagonally adjacent live cells: a 2n arrangement around the cell between their corners.
+        let rule = LookupRule::parse("B2n/S").unwrap();
+        assert!(rule.next_state(256 + 1));
+        assert!(!rule.next_state(256 + 64));
+        let negated = LookupRule::parse("b6-n/s").unwrap();
+        assert!(!negated.next_state(511 - 16 - 256 - 1));
+        assert!(negated.next_state(511 - 16 - 256 - 64));
+    }
+
+    #[test]
+    fn test_rejects_malformed_rules() {
+        assert_eq!(LookupRule::parse("B2z/S"), None);
+        assert_eq!(LookupRule::parse("B9/S"), None);
+        assert_eq!(LookupRule::parse("B3-/S23"), None);
+        assert_eq!(LookupRule::parse("B3S23"), None);
+        assert_eq!(LookupRule::parse("MAPABC"), None);
+    }
+
+    #[test]
+    fn test_map_strings() {
+        let life = LookupRule::parse("B3/S23").unwrap();
+        let map = life.to_map_string();
+
+        assert_eq!(map, "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA");
+        assert_eq!(LookupRule::parse(&map), Some(life.clone()));
+        assert_eq!(LookupRule::parse(&format!("{}==", map)), Some(life));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::cell_types::rule_table::{RuleTable, Symmetry};
+use crate::environment::Environment;
+use crate::grid::{Boundary, Grid, MOORE_OFFSETS};
+use crate::rle::{parse_rle, RlePattern};
+
+/// Counts the loops in a colony of self-replicating loops, as groups of non-empty cells connected through their Moore
+/// neighborhoods that include at least one sheath cell. A loop counts once even while a signal briefly breaks its
+/// sheath, and a loop still budding off its parent counts with it until the arm between them is cut.
+pub fn count_loops(grid: &Grid<u8>, sheath_state: u8) -> usize {
+    let mut visited = Grid::new_filled(grid.get_width(), grid.get_height(), false);
+    let mut n_loops = 0;
+
+    for y in 0..grid.get_height() {
+        for x in 0..grid.get_width() {
+            if visited.get_cell(x, y) || grid.get_cell(x, y) == 0 {
+                continue;
+            }
+
+            let mut has_sheath = false;
+            visited.set_cell(x, y, true);
+            let mut frontier = vec![(x, y)];
+            while let Some((cell_x, cell_y)) = frontier.pop() {
+                has_sheath |= grid.get_cell(cell_x, cell_y) == sheath_state;
+                for offset in MOORE_OFFSETS {
+                    let Some((nx, ny)) = grid.get_neighbor_coord(cell_x, cell_y, offset, Boundary::Bounded) else { continue };
+                    if !visited.get_cell(nx, ny) && grid.get_cell(nx, ny) != 0 {
+                        visited.set_cell(nx, ny, true);
+                        frontier.push((nx, ny));
+                    }
+                }
+            }
+            n_loops += has_sheath as usize;
+        }
+    }
+
+    n_loops
+}
+
+/// A bundled self-replicating loop: its transition table in Langton's format, to be read with quarter-turn symmetry,
+/// and its seed pattern as RLE.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LoopPreset {
+    pub table: &'static str,
+    pub n_states: u16,
+    pub seed: &'static str,
+    pub sheath_state: u8,
+}
+
+impl LoopPreset {
+    pub fn get_table(&self) -> RuleTable {
+        RuleTable::from_langton_format(self.table, self.n_states, Symmetry::Rotate4).expect("Bundled tables are well-formed")
+    }
+
+    pub fn get_seed(&self) -> RlePattern {
+        parse_rle(self.seed).expect("Bundled seeds are well-formed")
+    }
+}
+
+/// A self-replicating loop rule running from a starting pattern, which records the loop count of every generation,
+/// starting with the initial one.
+pub struct LoopColony {
+    env: Environment<u8>,
+    n_states: u16,
+    sheath_state: u8,
+    history: Vec<usize>,
+}
+
+impl LoopColony {
+    pub fn new(table: &RuleTable, initial: &Grid<u8>, sheath_state: u8) -> LoopColony {
+        let env = Environment::new(initial.get_width(), initial.get_height(), |x, y| initial.get_cell(x, y), table.rule(Boundary::Bounded));
+        let history = vec![count_loops(initial, sheath_state)];
+
+        LoopColony {
+            env,
+            n_states: table.get_n_states(),
+            sheath_state,
+            history,
+        }
+    }
+
+    /// The classic loops, each with its seed. Only Langton's loop is bundled so far. Byl's loop, the Chou-Reggia loop
+    /// and Evoloops are waiting on their published tables and seeds, and can be run from those files meanwhile.
+    pub fn presets() -> Vec<(&'static str, LoopPreset)> {
+        vec![
+            ("Langton", LoopPreset {
+                table: include_str!("loops/langton.table"),
+                n_states: 8,
+                seed: include_str!("loops/langton.rle"),
+                sheath_state: 2,
+            }),
+        ]
+    }
+
+    /// Starts a bundled loop from its seed, with `margin` empty cells around it for the colony to grow into.
+    pub fn preset(name: &str, margin: usize) -> Option<LoopColony> {
+        let (_, preset) = Self::presets().into_iter().find(|(preset_name, _)| preset_name.eq_ignore_ascii_case(name))?;
+        Some(Self::new(&preset.get_table(), &preset.get_seed().to_grid(margin), preset.sheath_state))
+    }
+
+    pub fn get_environment(&self) -> &Environment<u8> {
+        &self.env
+    }
+
+    pub fn get_n_states(&self) -> u16 {
+        self.n_states
+    }
+
+    pub fn get_history(&self) -> &[usize] {
+        &self.history
+    }
+
+    pub fn advance(&mut self) {
+        self.env.advance();
+        self.history.push(count_loops(self.env.get_grid(), self.sheath_state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(grid: &mut Grid<u8>, left: usize, top: usize, size: usize) {
+        for i in 0..size {
+            grid.set_cell(left + i, top, 2);
+            grid.set_cell(left + i, top + size - 1, 2);
+            grid.set_cell(left, top + i, 2);
+            grid.set_cell(left + size - 1, top + i, 2);
+        }
+    }
+
+    #[test]
+    fn test_count_loops() {
+        let mut grid = Grid::new_filled(20, 10, 0);
+        ring(&mut grid, 1, 1, 5);
+        ring(&mut grid, 8, 1, 5);
+        assert_eq!(count_loops(&grid, 2), 2);
+
+        // A diagonal touch joins them.
+        grid.set_cell(6, 6, 2);
+        grid.set_cell(7, 6, 2);
+        assert_eq!(count_loops(&grid, 2), 1);
+        assert_eq!(count_loops(&grid, 1), 0);
+    }
+
+    #[test]
+    fn test_colony_history() {
+        // Sheath cells decay whatever their neighbors are, so the count drops to 0 after one generation.
+        let table = RuleTable::from_langton_format("200000\n220000\n222000\n220200\n222200\n222220\n", 3, Symmetry::Rotate4).unwrap();
+        let mut grid = Grid::new_filled(12, 12, 0);
+        ring(&mut grid, 3, 3, 5);
+        let mut colony = LoopColony::new(&table, &grid, 2);
+
+        colony.advance();
+
+        assert_eq!(colony.get_history(), &[1, 0]);
+    }
+
+    fn replicates(name: &str, margin: usize, max_generations: usize) {
+        let mut colony = LoopColony::preset(name, margin).unwrap();
+        assert_eq!(colony.get_history(), &[1]);
+
+        while colony.get_history().len() <= max_generations && *colony.get_history().last().unwrap() <= 1 {
+            colony.advance();
+        }
+
+        assert!(*colony.get_history().last().unwrap() > 1, "{} didn't replicate in {} generations", name, max_generations);
+    }
+
+    #[test]
+    fn test_langton_loop_replicates() {
+        replicates("Langton", 20, 200);
+        assert!(LoopColony::preset("nonexistent", 20).is_none());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::{Boundary, Grid};
+
+pub const DEAD: u8 = 0;
+
+/// A Life-like rule where live cells carry one of `n_colors` colors, numbered from 1. Births and survivals only look at
+/// the number of live neighbors, but a newborn takes the most common color among its live neighbors. If several colors
+/// tie for the most, it takes the first of the least common colors instead, which for QuadLife is the one color none of
+/// its three parents have.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ColorLife {
+    pub n_colors: u8,
+    pub birth: [bool; 9],
+    pub survival: [bool; 9],
+    pub boundary: Boundary,
+}
+
+impl ColorLife {
+    /// Panics if there are no colors, or a count is above 8, the most neighbors a cell has.
+    pub fn new(n_colors: u8, birth: &[usize], survival: &[usize]) -> ColorLife {
+        assert!(n_colors > 0, "Live cells need at least one color");
+        assert!(birth.iter().chain(survival).all(|&count| count <= 8), "A cell has at most 8 neighbors");
+
+        let mut config = ColorLife {
+            n_colors,
+            birth: [false; 9],
+            survival: [false; 9],
+            boundary: Boundary::Wrapping,
+        };
+        for &count in birth {
+            config.birth[count] = true;
+        }
+        for &count in survival {
+            config.survival[count] = true;
+        }
+
+        config
+    }
+
+    /// Life with two colors.
+    pub fn immigration() -> ColorLife {
+        Self::new(2, &[3], &[2, 3])
+    }
+
+    /// Life with four colors.
+    pub fn quadlife() -> ColorLife {
+        Self::new(4, &[3], &[2, 3])
+    }
+
+    pub fn presets() -> Vec<(&'static str, ColorLife)> {
+        vec![
+            ("Immigration", Self::immigration()),
+            ("QuadLife", Self::quadlife()),
+        ]
+    }
+
+    pub fn preset(name: &str) -> Option<ColorLife> {
+        Self::presets().into_iter().find(|(preset_name, _)| preset_name.eq_ignore_ascii_case(name)).map(|(_, config)| config)
+    }
+
+    /// The color a cell is born with, given how many of its live neighbors have each color.
+    pub fn birth_color(&self, color_counts: &[usize]) -> u8 {
+        let most = *color_counts.iter().max().unwrap();
+        let mut majority = color_counts.iter().enumerate().filter(|&(_, &count)| count == most);
+        let (first, _) = majority.next().unwrap();
+        let color = if majority.next().is_none() {
+            first
+        } else {
+            let fewest = *color_counts.iter().min().unwrap();
+            color_counts.iter().position(|&count| count == fewest).unwrap()
+        };
+
+        color as u8 + 1
+    }
+
+    /// Cells in states past the last color count as dead.
+    pub fn rule(&self) -> Box<AdvanceCellF<u8>> {
+        let config = *self;
+        let is_live = move |cell: u8| cell != DEAD && cell <= config.n_colors;
+
+        Box::new(move |grid, x, y| {
+            let mut color_counts = vec![0; config.n_colors as usize];
+            for neighbor in grid.get_moore_neighborhood_around(x, y, config.boundary).filter(|&cell| is_live(cell)) {
+                color_counts[neighbor as usize - 1] += 1;
+            }
+            let live_neighbors: usize = color_counts.iter().sum();
+
+            match grid.get_cell(x, y) {
+                cell if !is_live(cell) && config.birth[live_neighbors] => config.birth_color(&color_counts),
+                cell if !is_live(cell) => DEAD,
+                color if config.survival[live_neighbors] => color,
+                _ => DEAD,
+            }
+        })
+    }
+}
+
+/// How many cells have each color, starting with color 1. Cells past the last color aren't counted.
+pub fn count_colors(grid: &Grid<u8>, n_colors: u8) -> Vec<usize> {
+    let mut counts = vec![0; n_colors as usize];
+    for y in 0..grid.get_height() {
+        for x in 0..grid.get_width() {
+            let cell = grid.get_cell(x, y);
+            if cell != DEAD && cell <= n_colors {
+                counts[cell as usize - 1] += 1;
+            }
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway;
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_birth_color_inheritance() {
+        let immigration = ColorLife::immigration();
+        assert_eq!(immigration.birth_color(&[1, 2]), 2);
+        assert_eq!(immigration.birth_color(&[3, 0]), 1);
+
+        let quadlife = ColorLife::quadlife();
+        assert_eq!(quadlife.birth_color(&[0, 2, 1, 0]), 2);
+        assert_eq!(quadlife.birth_color(&[1, 1, 0, 1]), 3);
+        assert_eq!(quadlife.birth_color(&[0, 1, 1, 1]), 1);
+    }
+
+    #[test]
+    fn test_newborn_takes_parent_majority() {
+        let mut env = Environment::new(5, 5, |_x, _y| DEAD, ColorLife::quadlife().rule());
+        // A blinker with one cell of each of three colors, so both newborns get the fourth color.
+        env.bulk_set_readable(vec![(1, 2, 1), (2, 2, 2), (3, 2, 3)]);
+
+        env.advance();
+
+        assert_eq!((env.get_cell(2, 1), env.get_cell(2, 2), env.get_cell(2, 3)), (4, 2, 4));
+        assert_eq!(count_colors(env.get_grid(), 4), vec![0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn test_matches_life_ignoring_color() {
+        let soup = |x: usize, y: usize| ((x * 5 + y * 3 + x * y) % 7 < 3) as u8 * (1 + (x + y) as u8 % 2);
+        let mut colored = Environment::new(16, 16, soup, ColorLife::immigration().rule());
+        let mut life = Environment::new(16, 16, |x, y| (soup(x, y) != DEAD) as u8, conway::life_rule(Boundary::Wrapping));
+
+        for _ in 0..20 {
+            colored.advance();
+            life.advance();
+            for y in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(colored.get_cell(x, y) != DEAD, life.get_cell(x, y) != DEAD);
+                }
+            }
+        }
+        assert_eq!(ColorLife::preset("quadlife").unwrap().n_colors, 4);
+    }
+
+    #[test]
+    fn test_states_past_last_color_count_as_dead() {
+        let mut env = Environment::new(5, 5, |_x, _y| DEAD, ColorLife::immigration().rule());
+        // The 7 would make a fourth neighbor of (2, 1), and survive itself, if it counted as live.
+        env.bulk_set_readable(vec![(1, 2, 1), (2, 2, 2), (3, 2, 1), (1, 1, 7)]);
+
+        env.advance();
+
+        assert_eq!((env.get_cell(2, 1), env.get_cell(2, 3), env.get_cell(1, 1)), (1, 1, DEAD));
+        assert_eq!(count_colors(env.get_grid(), 2), vec![2, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_counts_past_eight() {
+        ColorLife::new(2, &[3, 9], &[2, 3]);
+    }
+}
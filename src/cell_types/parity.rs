@@ -0,0 +1,9 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::Boundary;
+
+/// Fredkin's parity rule: a cell becomes the XOR of its von Neumann neighbors. Run second-order, it's reversible.
+pub fn parity_rule(boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+    Box::new(move |grid, x, y| {
+        grid.get_von_neumann_neighborhood_around(x, y, boundary).fold(0, |parity, cell| parity ^ (cell & 1))
+    })
+}
@@ -0,0 +1,417 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use crate::environment::AdvanceCellF;
+use crate::grid::Boundary;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TableNeighborhood {
+    VonNeumann,
+    Moore,
+    /// The six neighbors of a hexagonal grid in the grid's "odd-r" offset coordinates.
+    Hexagonal,
+    /// The left and right neighbors, so each row runs as its own one-dimensional automaton.
+    OneDimensional,
+}
+
+/// The hexagonal offsets from the grid, reordered clockwise from the northeast neighbor.
+const HEX_EVEN_ROW_CLOCKWISE: [(isize, isize); 6] = [(0, -1), (1, 0), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+const HEX_ODD_ROW_CLOCKWISE: [(isize, isize); 6] = [(1, -1), (1, 0), (1, 1), (0, 1), (-1, 0), (0, -1)];
+
+impl TableNeighborhood {
+    /// The neighbor offsets for a cell in row `y`, in the order transitions list their neighbors in. That's clockwise
+    /// from north for the square neighborhoods, clockwise from northeast for hexagonal, and left then right for one
+    /// dimension. Only the hexagonal offsets depend on the row.
+    pub fn offsets_for_row(&self, y: usize) -> &'static [(isize, isize)] {
+        match self {
+            TableNeighborhood::VonNeumann => &[(0, -1), (1, 0), (0, 1), (-1, 0)],
+            TableNeighborhood::Moore => &[(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)],
+            TableNeighborhood::Hexagonal if y.is_multiple_of(2) => &HEX_EVEN_ROW_CLOCKWISE,
+            TableNeighborhood::Hexagonal => &HEX_ODD_ROW_CLOCKWISE,
+            TableNeighborhood::OneDimensional => &[(-1, 0), (1, 0)],
+        }
+    }
+
+    pub fn n_neighbors(&self) -> usize {
+        self.offsets_for_row(0).len()
+    }
+
+    /// Whether Golly allows the symmetry with this neighborhood.
+    pub fn supports(&self, symmetry: Symmetry) -> bool {
+        match symmetry {
+            Symmetry::None | Symmetry::Permute => true,
+            Symmetry::Rotate4 | Symmetry::Rotate4Reflect | Symmetry::ReflectHorizontal => matches!(self, TableNeighborhood::VonNeumann | TableNeighborhood::Moore),
+            Symmetry::Rotate8 | Symmetry::Rotate8Reflect => *self == TableNeighborhood::Moore,
+            Symmetry::Rotate2 | Symmetry::Rotate3 | Symmetry::Rotate6 | Symmetry::Rotate6Reflect => *self == TableNeighborhood::Hexagonal,
+            Symmetry::Reflect => *self == TableNeighborhood::OneDimensional,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Symmetry {
+    None,
+    /// Rotations by quarter turns.
+    Rotate4,
+    /// Quarter turns, plus their mirror images.
+    Rotate4Reflect,
+    /// Rotations by eighth turns, which only makes sense for the Moore neighborhood.
+    Rotate8,
+    Rotate8Reflect,
+    /// Mirroring left to right, for the von Neumann and Moore neighborhoods.
+    ReflectHorizontal,
+    /// Rotations by half turns, third turns and sixth turns, for the hexagonal neighborhood.
+    Rotate2,
+    Rotate3,
+    Rotate6,
+    Rotate6Reflect,
+    /// Swapping left and right, for one dimension.
+    Reflect,
+    /// Only the number of neighbors in each state matters, not where they are.
+    Permute,
+}
+
+/// A set of states, which is how a transition says what it accepts in each position.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct StateSet([u64; 4]);
+
+impl StateSet {
+    pub fn single(state: u8) -> StateSet {
+        Self::from_states([state])
+    }
+
+    pub fn from_states(states: impl IntoIterator<Item = u8>) -> StateSet {
+        let mut set = StateSet::default();
+        for state in states {
+            set.0[state as usize / 64] |= 1 << (state % 64);
+        }
+
+        set
+    }
+
+    pub fn contains(&self, state: u8) -> bool {
+        self.0[state as usize / 64] & (1 << (state % 64)) != 0
+    }
+
+    /// The largest state in the set, or None if it's empty.
+    pub fn max_state(&self) -> Option<u8> {
+        (0..=u8::MAX).rev().find(|&state| self.contains(state))
+    }
+}
+
+/// Steps to the next lexicographic permutation, returning false once the last one has been reached. Equal elements are
+/// never swapped with each other, so every permutation produced is distinct.
+fn next_permutation<T: Ord>(items: &mut [T]) -> bool {
+    let Some(pivot) = (1..items.len()).rev().find(|&i| items[i - 1] < items[i]).map(|i| i - 1) else { return false };
+    let successor = (pivot + 1..items.len()).rev().find(|&i| items[i] > items[pivot]).unwrap();
+    items.swap(pivot, successor);
+    items[pivot + 1..].reverse();
+
+    true
+}
+
+/// A rule given as a table of transitions from a cell and its neighbors to the cell's next state. Each transition
+/// accepts a set of states in every position, and also stands for its images under the table's symmetry, which are
+/// expanded when it's added. The first transition that matches wins, and cells that match none keep their state.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuleTable {
+    /// Up to 256, so this doesn't fit in a state.
+    n_states: u16,
+    neighborhood: TableNeighborhood,
+    symmetry: Symmetry,
+    /// The expanded transitions, each as the accepted sets for the center and then the neighbors in order.
+    transitions: Vec<(Vec<StateSet>, u8)>,
+}
+
+impl RuleTable {
+    /// Returns None for more than 256 states, or a symmetry the neighborhood doesn't support, like eighth turns of the
+    /// von Neumann neighborhood.
+    pub fn new(n_states: u16, neighborhood: TableNeighborhood, symmetry: Symmetry) -> Option<RuleTable> {
+        if n_states > 256 || !neighborhood.supports(symmetry) {
+            return None;
+        }
+
+        Some(RuleTable {
+            n_states,
+            neighborhood,
+            symmetry,
+            transitions: vec![],
+        })
+    }
+
+    /// Reads Langton's format, where each line is six digits giving the center, top, right, bottom and left states, then
+    /// the new state. Lines starting with # and blank lines are skipped. Returns None if a line is malformed or
+    /// conflicts with an earlier one.
+    pub fn from_langton_format(text: &str, n_states: u16, symmetry: Symmetry) -> Option<RuleTable> {
+        let mut table = Self::new(n_states, TableNeighborhood::VonNeumann, symmetry)?;
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let digits: Vec<u8> = line.chars().map(|c| c.to_digit(10).map(|digit| digit as u8)).collect::<Option<_>>()?;
+            if digits.len() != 6 || !table.add_transition(digits[0], &digits[1..5], digits[5]) {
+                return None;
+            }
+        }
+
+        Some(table)
+    }
+
+    pub fn get_n_states(&self) -> u16 {
+        self.n_states
+    }
+
+    pub fn get_neighborhood(&self) -> TableNeighborhood {
+        self.neighborhood
+    }
+
+    /// The number of transitions after symmetry expansion.
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    /// The distinct neighbor orderings that the symmetry treats as the same, including the original.
+    fn images<T: Ord + Copy>(&self, neighbors: &[T]) -> Vec<Vec<T>> {
+        let n = neighbors.len();
+        let rotate = |steps: usize| -> Vec<T> { (0..n).map(|i| neighbors[(i + steps) % n]).collect() };
+        let reflect = |ring: &[T]| -> Vec<T> { (0..n).map(|i| ring[(n - i) % n]).collect() };
+
+        let mut images: Vec<Vec<T>> = match self.symmetry {
+            Symmetry::None => vec![neighbors.to_vec()],
+            Symmetry::ReflectHorizontal => vec![neighbors.to_vec(), reflect(neighbors)],
+            Symmetry::Reflect => vec![neighbors.to_vec(), neighbors.iter().rev().copied().collect()],
+            Symmetry::Rotate2 => (0..2).map(|turn| rotate(turn * n / 2)).collect(),
+            Symmetry::Rotate3 => (0..3).map(|turn| rotate(turn * n / 3)).collect(),
+            Symmetry::Rotate4 | Symmetry::Rotate4Reflect => (0..4).map(|turn| rotate(turn * n / 4)).collect(),
+            Symmetry::Rotate6 | Symmetry::Rotate6Reflect => (0..6).map(rotate).collect(),
+            Symmetry::Rotate8 | Symmetry::Rotate8Reflect => (0..8).map(rotate).collect(),
+            Symmetry::Permute => {
+                let mut permutation = neighbors.to_vec();
+                permutation.sort();
+                let mut permutations = vec![permutation.clone()];
+                while next_permutation(&mut permutation) {
+                    permutations.push(permutation.clone());
+                }
+                permutations
+            },
+        };
+
+        if matches!(self.symmetry, Symmetry::Rotate4Reflect | Symmetry::Rotate6Reflect | Symmetry::Rotate8Reflect) {
+            let reflections: Vec<Vec<T>> = images.iter().map(|ring| reflect(ring)).collect();
+            images.extend(reflections);
+        }
+        images.sort();
+        images.dedup();
+
+        images
+    }
+
+    fn is_valid(&self, sets: &[StateSet], next: u8) -> bool {
+        sets.len() == self.neighborhood.n_neighbors() + 1
+            && (next as u16) < self.n_states
+            && sets.iter().all(|set| set.max_state().is_some_and(|state| (state as u16) < self.n_states))
+    }
+
+    /// Adds a transition with all its symmetric images. Returns false and leaves the table unchanged if a state is out
+    /// of range, the wrong number of neighbors is given, or an image already leads to a different state.
+    pub fn add_transition(&mut self, center: u8, neighbors: &[u8], next: u8) -> bool {
+        let sets: Vec<StateSet> = [center].iter().chain(neighbors).map(|&state| StateSet::single(state)).collect();
+        if !self.is_valid(&sets, next) {
+            return false;
+        }
+
+        let images = self.images(neighbors);
+        if images.iter().any(|image| self.lookup(center, image).is_some_and(|existing| existing != next)) {
+            return false;
+        }
+
+        self.add_pattern(&sets, next)
+    }
+
+    /// Adds a transition that accepts a set of states in each position, given for the center and then the neighbors
+    /// in the neighborhood's order, with all its symmetric images. Images that overlap earlier transitions are only used where
+    /// the earlier ones don't match. Returns false and leaves the table unchanged if a set is empty or has a state out
+    /// of range, or the wrong number of sets is given.
+    pub fn add_pattern(&mut self, sets: &[StateSet], next: u8) -> bool {
+        if !self.is_valid(sets, next) {
+            return false;
+        }
+
+        for image in self.images(&sets[1..]) {
+            let mut transition = vec![sets[0]];
+            transition.extend(image);
+            self.transitions.push((transition, next));
+        }
+
+        true
+    }
+
+    /// The next state for a cell with the given neighbors, in the neighborhood's order, if any transition matches.
+    pub fn lookup(&self, center: u8, neighbors: &[u8]) -> Option<u8> {
+        self.transitions.iter()
+            .find(|(sets, _)| sets[0].contains(center) && sets[1..].iter().zip(neighbors).all(|(set, &state)| set.contains(state)))
+            .map(|&(_, next)| next)
+    }
+
+    /// Neighbors outside a bounded grid count as state 0. Lookups are cached, since most tables only ever see a small
+    /// fraction of the possible neighborhoods.
+    pub fn rule(&self, boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+        let table = self.clone();
+        let cache: RefCell<HashMap<Vec<u8>, u8>> = RefCell::new(HashMap::new());
+
+        Box::new(move |grid, x, y| {
+            let mut neighborhood = vec![grid.get_cell(x, y)];
+            neighborhood.extend(table.neighborhood.offsets_for_row(y).iter()
+                .map(|&offset| grid.get_neighbor_coord(x, y, offset, boundary).map_or(0, |(nx, ny)| grid.get_cell(nx, ny))));
+
+            if let Some(&next) = cache.borrow().get(&neighborhood) {
+                return next;
+            }
+
+            let next = table.lookup(neighborhood[0], &neighborhood[1..]).unwrap_or(neighborhood[0]);
+            cache.borrow_mut().insert(neighborhood, next);
+            next
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_rotations_are_expanded() {
+        let mut table = RuleTable::new(3, TableNeighborhood::VonNeumann, Symmetry::Rotate4).unwrap();
+        assert!(table.add_transition(0, &[1, 2, 0, 0], 2));
+
+        assert_eq!(table.len(), 4);
+        assert_eq!(table.lookup(0, &[0, 0, 1, 2]), Some(2));
+        assert_eq!(table.lookup(0, &[2, 0, 0, 1]), Some(2));
+        // The mirror image isn't a rotation.
+        assert_eq!(table.lookup(0, &[1, 0, 0, 2]), None);
+    }
+
+    #[test]
+    fn test_reflections_and_permutations() {
+        let mut reflecting = RuleTable::new(3, TableNeighborhood::Moore, Symmetry::Rotate8Reflect).unwrap();
+        assert!(reflecting.add_transition(0, &[1, 2, 0, 0, 0, 0, 0, 0], 1));
+        assert_eq!(reflecting.lookup(0, &[1, 0, 0, 0, 0, 0, 0, 2]), Some(1));
+        assert_eq!(reflecting.lookup(0, &[0, 0, 0, 2, 1, 0, 0, 0]), Some(1));
+
+        let mut permuting = RuleTable::new(3, TableNeighborhood::Moore, Symmetry::Permute).unwrap();
+        assert!(permuting.add_transition(0, &[1, 1, 2, 0, 0, 0, 0, 0], 2));
+        assert_eq!(permuting.lookup(0, &[0, 2, 0, 1, 0, 0, 0, 1]), Some(2));
+        assert_eq!(permuting.lookup(0, &[0, 2, 0, 1, 0, 0, 0, 0]), None);
+
+        assert_eq!(RuleTable::new(3, TableNeighborhood::VonNeumann, Symmetry::Rotate8), None);
+    }
+
+    #[test]
+    fn test_reflect_horizontal() {
+        let mut von_neumann = RuleTable::new(3, TableNeighborhood::VonNeumann, Symmetry::ReflectHorizontal).unwrap();
+        assert!(von_neumann.add_transition(0, &[1, 2, 0, 0], 1));
+        assert_eq!(von_neumann.lookup(0, &[1, 0, 0, 2]), Some(1));
+        // Turning it upside down isn't a left to right mirror.
+        assert_eq!(von_neumann.lookup(0, &[0, 2, 1, 0]), None);
+
+        let mut moore = RuleTable::new(3, TableNeighborhood::Moore, Symmetry::ReflectHorizontal).unwrap();
+        assert!(moore.add_transition(0, &[0, 1, 2, 0, 0, 0, 0, 0], 1));
+        assert_eq!(moore.len(), 2);
+        assert_eq!(moore.lookup(0, &[0, 0, 0, 0, 0, 0, 2, 1]), Some(1));
+    }
+
+    #[test]
+    fn test_hexagonal_half_turns() {
+        let mut table = RuleTable::new(3, TableNeighborhood::Hexagonal, Symmetry::Rotate2).unwrap();
+        assert!(table.add_transition(0, &[1, 2, 0, 0, 0, 0], 1));
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.lookup(0, &[0, 0, 0, 1, 2, 0]), Some(1));
+        assert_eq!(table.lookup(0, &[0, 0, 1, 2, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_hexagonal_third_turns() {
+        let mut table = RuleTable::new(3, TableNeighborhood::Hexagonal, Symmetry::Rotate3).unwrap();
+        assert!(table.add_transition(0, &[1, 2, 0, 0, 0, 0], 1));
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.lookup(0, &[0, 0, 1, 2, 0, 0]), Some(1));
+        assert_eq!(table.lookup(0, &[0, 0, 0, 0, 1, 2]), Some(1));
+        assert_eq!(table.lookup(0, &[0, 0, 0, 1, 2, 0]), None);
+    }
+
+    #[test]
+    fn test_hexagonal_sixth_turns() {
+        let mut table = RuleTable::new(3, TableNeighborhood::Hexagonal, Symmetry::Rotate6).unwrap();
+        assert!(table.add_transition(0, &[1, 2, 0, 0, 0, 0], 1));
+
+        assert_eq!(table.len(), 6);
+        assert_eq!(table.lookup(0, &[0, 1, 2, 0, 0, 0]), Some(1));
+        assert_eq!(table.lookup(0, &[1, 0, 0, 0, 0, 2]), None);
+    }
+
+    #[test]
+    fn test_hexagonal_sixth_turns_and_reflections() {
+        let mut table = RuleTable::new(3, TableNeighborhood::Hexagonal, Symmetry::Rotate6Reflect).unwrap();
+        assert!(table.add_transition(0, &[1, 2, 0, 0, 0, 0], 1));
+
+        assert_eq!(table.len(), 12);
+        assert_eq!(table.lookup(0, &[1, 0, 0, 0, 0, 2]), Some(1));
+        assert_eq!(table.lookup(0, &[0, 0, 2, 1, 0, 0]), Some(1));
+
+        assert_eq!(RuleTable::new(3, TableNeighborhood::Hexagonal, Symmetry::Rotate4), None);
+        assert_eq!(RuleTable::new(3, TableNeighborhood::Moore, Symmetry::Rotate6), None);
+    }
+
+    #[test]
+    fn test_one_dimensional_reflect() {
+        let mut table = RuleTable::new(2, TableNeighborhood::OneDimensional, Symmetry::Reflect).unwrap();
+        assert!(table.add_transition(0, &[1, 0], 1));
+
+        assert_eq!(table.lookup(0, &[0, 1]), Some(1));
+        assert_eq!(table.lookup(0, &[1, 1]), None);
+        assert_eq!(RuleTable::new(2, TableNeighborhood::OneDimensional, Symmetry::ReflectHorizontal), None);
+    }
+
+    #[test]
+    fn test_conflicts_are_rejected() {
+        let mut table = RuleTable::new(3, TableNeighborhood::VonNeumann, Symmetry::Rotate4).unwrap();
+        assert!(table.add_transition(0, &[1, 0, 0, 0], 1));
+        assert!(!table.add_transition(0, &[0, 0, 1, 0], 2));
+        assert!(!table.add_transition(0, &[3, 0, 0, 0], 1));
+        assert!(!table.add_transition(0, &[1, 0, 0], 1));
+        assert_eq!(table.len(), 4);
+    }
+
+    #[test]
+    fn test_patterns_first_match_wins() {
+        let mut table = RuleTable::new(3, TableNeighborhood::VonNeumann, Symmetry::Rotate4).unwrap();
+        let any = StateSet::from_states(0..3);
+        assert!(table.add_pattern(&[StateSet::single(1), StateSet::single(2), any, any, any], 0));
+        assert!(table.add_pattern(&[StateSet::single(1), any, any, any, any], 2));
+        assert!(!table.add_pattern(&[StateSet::default(), any, any, any, any], 2));
+
+        assert_eq!(table.lookup(1, &[0, 0, 2, 1]), Some(0));
+        assert_eq!(table.lookup(1, &[0, 0, 1, 1]), Some(2));
+        assert_eq!(table.lookup(0, &[0, 0, 1, 1]), None);
+    }
+
+    #[test]
+    fn test_langton_format_rule_runs() {
+        // Empty cells next to a live cell come alive, so a single cell grows into a diamond.
+        let table = RuleTable::from_langton_format("# Toy growth rule\n010001\n\n", 2, Symmetry::Rotate4).unwrap();
+        let mut env = Environment::new(5, 5, |x, y| if (x, y) == (2, 2) { 1 } else { 0 }, table.rule(Boundary::Bounded));
+
+        env.advance();
+
+        assert_eq!(env.get_cell(2, 1), 1);
+        assert_eq!(env.get_cell(3, 2), 1);
+        assert_eq!(env.get_cell(3, 3), 0);
+
+        assert_eq!(RuleTable::from_langton_format("01000", 2, Symmetry::Rotate4), None);
+        assert_eq!(RuleTable::from_langton_format("010001\n001001\n001002", 3, Symmetry::Rotate4), None);
+    }
+}
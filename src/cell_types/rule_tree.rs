@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use crate::cell_types::rule_table::TableNeighborhood;
+use crate::environment::AdvanceCellF;
+use crate::grid::Boundary;
+
+/// The order a tree reads the neighborhood in, as indices into the table neighbor order, followed by the center. Golly
+/// only writes trees for the square neighborhoods, so the others are read in table order.
+fn tree_order(neighborhood: TableNeighborhood) -> &'static [usize] {
+    match neighborhood {
+        // N, W, E, S.
+        TableNeighborhood::VonNeumann => &[0, 3, 1, 2],
+        // NW, NE, SW, SE, N, W, E, S.
+        TableNeighborhood::Moore => &[7, 1, 5, 3, 0, 6, 2, 4],
+        TableNeighborhood::Hexagonal => &[0, 1, 2, 3, 4, 5],
+        TableNeighborhood::OneDimensional => &[0, 1],
+    }
+}
+
+/// A rule given as a decision tree, in the same layout as Golly's `@TREE` sections. Each node branches on the state of
+/// one cell of the neighborhood, read in the tree order. The nodes just above the leaves branch on the center, and hold
+/// next states instead of child nodes. Identical subtrees are shared, which keeps even large rules small.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuleTree {
+    /// Up to 256, so this doesn't fit in a state.
+    n_states: u16,
+    neighborhood: TableNeighborhood,
+    /// Each node's children, or next states for nodes at level 1. Children always come before their parents, so the
+    /// last node is the root.
+    nodes: Vec<Vec<usize>>,
+    levels: Vec<usize>,
+}
+
+impl RuleTree {
+    /// Builds the tree for a rule given as a function of the center and its neighbors in the neighborhood's order. The
+    /// function is called once for every possible neighborhood.
+    pub fn from_fn(n_states: u16, neighborhood: TableNeighborhood, next_state: impl Fn(u8, &[u8]) -> u8) -> RuleTree {
+        let mut tree = RuleTree {
+            n_states,
+            neighborhood,
+            nodes: vec![],
+            levels: vec![],
+        };
+        let mut node_indices = HashMap::new();
+        let mut neighbors = vec![0; neighborhood.n_neighbors()];
+        tree.build_node(0, &mut neighbors, &next_state, &mut node_indices);
+
+        tree
+    }
+
+    fn build_node(&mut self, depth: usize, neighbors: &mut [u8], next_state: &impl Fn(u8, &[u8]) -> u8, node_indices: &mut HashMap<(usize, Vec<usize>), usize>) -> usize {
+        let order = tree_order(self.neighborhood);
+        let children: Vec<usize> = if depth == order.len() {
+            (0..self.n_states).map(|center| next_state(center as u8, neighbors) as usize).collect()
+        } else {
+            (0..self.n_states).map(|state| {
+                neighbors[order[depth]] = state as u8;
+                self.build_node(depth + 1, neighbors, next_state, node_indices)
+            }).collect()
+        };
+
+        let level = order.len() + 1 - depth;
+        *node_indices.entry((level, children.clone())).or_insert_with(|| {
+            self.nodes.push(children);
+            self.levels.push(level);
+            self.nodes.len() - 1
+        })
+    }
+
+    /// Parses the body of an `@TREE` section: the `num_states`, `num_neighbors` and `num_nodes` settings, then one line
+    /// per node giving its level and its children. Returns None if anything is missing or out of range.
+    pub fn parse(text: &str) -> Option<RuleTree> {
+        let mut settings = HashMap::new();
+        let mut node_lines = vec![];
+
+        for line in text.lines().map(|line| line.split('#').next().unwrap().trim()).filter(|line| !line.is_empty()) {
+            if let Some((key, value)) = line.split_once('=') {
+                settings.insert(key.trim().to_string(), value.trim().parse::<usize>().ok()?);
+            } else {
+                node_lines.push(line.split_whitespace().map(|number| number.parse::<usize>().ok()).collect::<Option<Vec<usize>>>()?);
+            }
+        }
+
+        let n_states = *settings.get("num_states")?;
+        if n_states > 256 {
+            return None;
+        }
+        let neighborhood = match settings.get("num_neighbors")? {
+            2 => TableNeighborhood::OneDimensional,
+            4 => TableNeighborhood::VonNeumann,
+            6 => TableNeighborhood::Hexagonal,
+            8 => TableNeighborhood::Moore,
+            _ => return None,
+        };
+        if node_lines.len() != *settings.get("num_nodes")? || node_lines.is_empty() {
+            return None;
+        }
+
+        let mut levels = vec![];
+        let mut nodes = vec![];
+        for line in node_lines {
+            let (&level, children) = line.split_first()?;
+            if children.len() != n_states {
+                return None;
+            }
+
+            let valid = if level == 1 {
+                children.iter().all(|&state| state < n_states)
+            } else {
+                children.iter().all(|&child| child < nodes.len() && levels[child] + 1 == level)
+            };
+            if !valid {
+                return None;
+            }
+
+            levels.push(level);
+            nodes.push(children.to_vec());
+        }
+
+        if *levels.last().unwrap() != tree_order(neighborhood).len() + 1 {
+            return None;
+        }
+
+        Some(RuleTree {
+            n_states: n_states as u16,
+            neighborhood,
+            nodes,
+            levels,
+        })
+    }
+
+    /// Writes the tree in the `@TREE` layout that `parse` reads.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("num_states={}\nnum_neighbors={}\nnum_nodes={}\n", self.n_states, self.neighborhood.n_neighbors(), self.nodes.len());
+
+        for (children, level) in self.nodes.iter().zip(&self.levels) {
+            let children: Vec<String> = children.iter().map(usize::to_string).collect();
+            text.push_str(&format!("{} {}\n", level, children.join(" ")));
+        }
+
+        text
+    }
+
+    pub fn get_n_states(&self) -> u16 {
+        self.n_states
+    }
+
+    pub fn get_neighborhood(&self) -> TableNeighborhood {
+        self.neighborhood
+    }
+
+    pub fn get_n_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The next state for a cell with the given neighbors, in the neighborhood's order.
+    pub fn lookup(&self, center: u8, neighbors: &[u8]) -> u8 {
+        let mut node = self.nodes.len() - 1;
+        for &index in tree_order(self.neighborhood) {
+            node = self.nodes[node][neighbors[index] as usize];
+        }
+
+        self.nodes[node][center as usize] as u8
+    }
+
+    /// Neighbors outside a bounded grid count as state 0.
+    pub fn rule(&self, boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+        let tree = self.clone();
+
+        Box::new(move |grid, x, y| {
+            let neighbors: Vec<u8> = tree.neighborhood.offsets_for_row(y).iter()
+                .map(|&offset| grid.get_neighbor_coord(x, y, offset, boundary).map_or(0, |(nx, ny)| grid.get_cell(nx, ny)))
+                .collect();
+
+            tree.lookup(grid.get_cell(x, y), &neighbors)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway::fixtures;
+    use crate::environment::Environment;
+
+    fn life(center: u8, neighbors: &[u8]) -> u8 {
+        let live_neighbors = neighbors.iter().filter(|&&state| state == 1).count();
+        (live_neighbors == 3 || (center == 1 && live_neighbors == 2)) as u8
+    }
+
+    #[test]
+    fn test_from_fn_shares_subtrees() {
+        let tree = RuleTree::from_fn(2, TableNeighborhood::Moore, life);
+
+        // Without sharing there'd be 511 nodes.
+        assert!(tree.get_n_nodes() < 40);
+        assert_eq!(tree.lookup(0, &[1, 1, 0, 0, 0, 0, 0, 1]), 1);
+        assert_eq!(tree.lookup(1, &[1, 1, 1, 1, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn test_text_round_trip_runs_life() {
+        let tree = RuleTree::parse(&RuleTree::from_fn(2, TableNeighborhood::Moore, life).to_text()).unwrap();
+        let mut from_tree = Environment::new(10, 10, fixtures::glider, tree.rule(Boundary::Wrapping));
+
+        fixtures::assert_glider_matches_life(10, 12, || {
+            from_tree.advance();
+            from_tree.get_grid().clone()
+        });
+    }
+
+    #[test]
+    fn test_parse_von_neumann_tree() {
+        // A two-state rule where each cell copies its north neighbor, so the root branches on north and then every
+        // path below it leads to the same state.
+        let nodes = ["1 0 0", "1 1 1", "2 0 0", "2 1 1", "3 2 2", "3 3 3", "4 4 4", "4 5 5", "5 6 7"];
+        let text = |nodes: &[&str], n_nodes: usize| format!("num_states=2\nnum_neighbors=4\nnum_nodes={}\n{}\n", n_nodes, nodes.join("\n"));
+
+        let tree = RuleTree::parse(&text(&nodes, 9)).unwrap();
+        assert_eq!(tree.lookup(0, &[1, 0, 0, 0]), 1);
+        assert_eq!(tree.lookup(1, &[0, 1, 1, 1]), 0);
+
+        assert_eq!(RuleTree::parse(&text(&nodes, 8)), None);
+        assert_eq!(RuleTree::parse(&text(&nodes[..8], 8)), None);
+        let mut skipping_level = nodes;
+        skipping_level[4] = "3 0 0";
+        assert_eq!(RuleTree::parse(&text(&skipping_level, 9)), None);
+    }
+
+    #[test]
+    fn test_parse_256_state_tree() {
+        // A one-dimensional rule where every state counts up by one, wrapping around, whatever its neighbors are.
+        let counting: Vec<String> = (0..256).map(|state| ((state + 1) % 256).to_string()).collect();
+        let text = format!("num_states=256\nnum_neighbors=2\nnum_nodes=3\n1 {}\n2 {}\n3 {}\n", counting.join(" "), vec!["0"; 256].join(" "), vec!["1"; 256].join(" "));
+        let tree = RuleTree::parse(&text).unwrap();
+
+        assert_eq!(tree.get_n_states(), 256);
+        assert_eq!(tree.get_neighborhood(), TableNeighborhood::OneDimensional);
+        assert_eq!(tree.lookup(254, &[255, 3]), 255);
+        assert_eq!(tree.lookup(255, &[0, 0]), 0);
+        assert_eq!(RuleTree::parse(&tree.to_text()), Some(tree));
+    }
+}
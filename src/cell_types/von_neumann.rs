@@ -0,0 +1,341 @@
+use crate::environment::{AdvanceCellF, Environment};
+use crate::grid::{Boundary, Grid};
+use crate::rle::parse_rle;
+
+// States are numbered the same way as Golly's JvN29 rule, so patterns load unchanged.
+pub const GROUND: u8 = 0;
+pub const SENSITIZED: u8 = 1;
+pub const S0: u8 = 2;
+pub const S1: u8 = 3;
+pub const S00: u8 = 4;
+pub const S01: u8 = 5;
+pub const S10: u8 = 6;
+pub const S11: u8 = 7;
+pub const S000: u8 = 8;
+pub const CONFLUENT: u8 = 9;
+pub const ORDINARY_TRANSMISSION: u8 = 13;
+pub const SPECIAL_TRANSMISSION: u8 = 21;
+pub const N_STATES: usize = 29;
+// Nobili32's confluent states carrying signals across, numbered as in Golly.
+pub const CROSSING_HORIZONTAL: u8 = 29;
+pub const CROSSING_VERTICAL: u8 = 30;
+pub const CROSSING_BOTH: u8 = 31;
+pub const NOBILI_N_STATES: usize = 32;
+
+/// Directions in the order Golly numbers them: east, north, west and south.
+const DIRECTION_OFFSETS: [(isize, isize); 4] = [(1, 0), (0, -1), (-1, 0), (0, 1)];
+pub const EAST: usize = 0;
+pub const NORTH: usize = 1;
+pub const WEST: usize = 2;
+pub const SOUTH: usize = 3;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Kind {
+    Ground,
+    Sensitized(u8),
+    /// The excitation being output now, and the one that will be output next.
+    Confluent { current: bool, next: bool },
+    /// A Nobili32 confluent state passing signals straight through, along each axis separately.
+    Crossing { horizontal: bool, vertical: bool },
+    Transmission { special: bool, direction: usize, excited: bool },
+}
+
+fn decode(state: u8) -> Kind {
+    match state {
+        GROUND => Kind::Ground,
+        SENSITIZED..=S000 => Kind::Sensitized(state),
+        9..=12 => Kind::Confluent { current: (state - CONFLUENT) & 1 == 1, next: (state - CONFLUENT) & 2 == 2 },
+        13..=28 => {
+            let index = state - ORDINARY_TRANSMISSION;
+            Kind::Transmission { special: index >= 8, direction: (index % 4) as usize, excited: index % 8 >= 4 }
+        },
+        CROSSING_HORIZONTAL => Kind::Crossing { horizontal: true, vertical: false },
+        CROSSING_VERTICAL => Kind::Crossing { horizontal: false, vertical: true },
+        CROSSING_BOTH => Kind::Crossing { horizontal: true, vertical: true },
+        _ => panic!("{} isn't a von Neumann state", state),
+    }
+}
+
+pub fn confluent(current: bool, next: bool) -> u8 {
+    CONFLUENT + current as u8 + 2 * next as u8
+}
+
+/// The Nobili32 state carrying the given crossing signals, or a quiescent confluent state if there are none.
+pub fn crossing(horizontal: bool, vertical: bool) -> u8 {
+    match (horizontal, vertical) {
+        (false, false) => confluent(false, false),
+        (true, false) => CROSSING_HORIZONTAL,
+        (false, true) => CROSSING_VERTICAL,
+        (true, true) => CROSSING_BOTH,
+    }
+}
+
+fn is_horizontal(direction: usize) -> bool {
+    direction == EAST || direction == WEST
+}
+
+pub fn transmission(special: bool, direction: usize, excited: bool) -> u8 {
+    ORDINARY_TRANSMISSION + 8 * special as u8 + 4 * excited as u8 + direction as u8
+}
+
+/// The construction sequences. Each sensitized state goes to the first state on a 0 and the second on a 1.
+fn sensitized_successor(state: u8, input: bool) -> u8 {
+    let (on_zero, on_one) = match state {
+        SENSITIZED => (S0, S1),
+        S0 => (S00, S01),
+        S1 => (S10, S11),
+        S00 => (S000, transmission(false, WEST, false)),
+        S01 => (transmission(false, SOUTH, false), transmission(true, EAST, false)),
+        S10 => (transmission(true, NORTH, false), transmission(true, WEST, false)),
+        S11 => (transmission(true, SOUTH, false), confluent(false, false)),
+        S000 => (transmission(false, EAST, false), transmission(false, NORTH, false)),
+        _ => unreachable!(),
+    };
+
+    if input { on_one } else { on_zero }
+}
+
+/// von Neumann's 29-state rule. Ordinary and special transmission states carry signals in the direction they point, and
+/// an excited transmission state of one kind destroys a transmission or confluent state of the other kind it points at.
+/// Confluent states AND their ordinary inputs with a delay of two, and fan out to every transmission state that isn't
+/// pointing at them. Signals fed into ground turn it into a sensitized state, which becomes a new transmission or
+/// confluent state depending on the next four or five bits.
+pub fn advance_cell(grid: &Grid<u8>, x: usize, y: usize) -> u8 {
+    next_state(grid, x, y, false)
+}
+
+/// Nobili's 32-state variant, which lets signals cross. A confluent state with ordinary inputs on both axes doesn't AND
+/// them, but passes each axis's signal straight through with a delay of one, holding it in states 29 to 31 meanwhile.
+/// Transmission states on either side of that axis, and not pointing at the confluent state, pick the signal up.
+/// Otherwise it's von Neumann's rule.
+pub fn nobili_advance_cell(grid: &Grid<u8>, x: usize, y: usize) -> u8 {
+    next_state(grid, x, y, true)
+}
+
+fn next_state(grid: &Grid<u8>, x: usize, y: usize, nobili: bool) -> u8 {
+    // The neighbor in each direction, if it's inside the grid.
+    let neighbors: Vec<Option<Kind>> = DIRECTION_OFFSETS.iter()
+        .map(|&offset| grid.get_neighbor_coord(x, y, offset, Boundary::Bounded).map(|(nx, ny)| decode(grid.get_cell(nx, ny))))
+        .collect();
+
+    // Transmission neighbors pointing back at this cell, as (side, special, excited).
+    let inputs: Vec<(usize, bool, bool)> = neighbors.iter().enumerate()
+        .filter_map(|(side, neighbor)| match *neighbor {
+            Some(Kind::Transmission { special, direction, excited }) if direction == (side + 2) % 4 => Some((side, special, excited)),
+            _ => None,
+        })
+        .collect();
+    let ordinary_excited = inputs.iter().any(|&(_, special, excited)| !special && excited);
+    let special_excited = inputs.iter().any(|&(_, special, excited)| special && excited);
+
+    match decode(grid.get_cell(x, y)) {
+        Kind::Ground => if ordinary_excited || special_excited { SENSITIZED } else { GROUND },
+        Kind::Sensitized(state) => sensitized_successor(state, ordinary_excited || special_excited),
+        kind @ (Kind::Confluent { .. } | Kind::Crossing { .. }) => {
+            if special_excited {
+                return GROUND;
+            }
+
+            let ordinary_inputs: Vec<(usize, bool)> = inputs.iter().filter(|&&(_, special, _)| !special).map(|&(side, _, excited)| (side, excited)).collect();
+            let on_axis = |horizontal: bool| ordinary_inputs.iter().filter(move |&&(side, _)| is_horizontal(side) == horizontal);
+            if nobili && on_axis(true).next().is_some() && on_axis(false).next().is_some() {
+                return crossing(on_axis(true).any(|&(_, excited)| excited), on_axis(false).any(|&(_, excited)| excited));
+            }
+
+            let next = matches!(kind, Kind::Confluent { next: true, .. });
+            confluent(next, !ordinary_inputs.is_empty() && ordinary_inputs.iter().all(|&(_, excited)| excited))
+        },
+        Kind::Transmission { special, direction, .. } => {
+            if (special && ordinary_excited) || (!special && special_excited) {
+                return GROUND;
+            }
+
+            let from_confluent = neighbors.iter().enumerate().any(|(side, neighbor)| side != direction && match neighbor {
+                Some(Kind::Confluent { current, .. }) => *current,
+                Some(Kind::Crossing { horizontal, vertical }) => if is_horizontal(side) { *horizontal } else { *vertical },
+                _ => false,
+            });
+            let same_kind_excited = if special { special_excited } else { ordinary_excited };
+            transmission(special, direction, same_kind_excited || from_confluent)
+        },
+    }
+}
+
+pub fn von_neumann_rule() -> Box<AdvanceCellF<u8>> {
+    Box::new(advance_cell)
+}
+
+pub fn nobili_rule() -> Box<AdvanceCellF<u8>> {
+    Box::new(nobili_advance_cell)
+}
+
+/// Loads a Golly RLE pattern for the JvN29 or Nobili32 rule, with `margin` ground cells around it to leave room for
+/// construction. Patterns without a rule are taken as JvN29. Returns None if the pattern doesn't parse, names a
+/// different rule, or has states its rule doesn't. No replicator is bundled, so the Nobili-Pesavento replicator has to
+/// be loaded from its published RLE.
+pub fn load_rle(text: &str, margin: usize) -> Option<Environment<u8>> {
+    let pattern = parse_rle(text)?;
+    let nobili = match pattern.rule.as_deref() {
+        None => false,
+        Some(rule) if rule.eq_ignore_ascii_case("JvN29") => false,
+        Some(rule) if rule.eq_ignore_ascii_case("Nobili32") => true,
+        Some(_) => return None,
+    };
+    let n_states = if nobili { NOBILI_N_STATES } else { N_STATES };
+    if pattern.cells.iter().any(|&(_, _, state)| state as usize >= n_states) {
+        return None;
+    }
+
+    let grid = pattern.to_grid(margin);
+    let rule = if nobili { nobili_rule() } else { von_neumann_rule() };
+    Some(Environment::new(grid.get_width(), grid.get_height(), |x, y| grid.get_cell(x, y), rule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An eastward ordinary wire of the given length, with the bits fed to the cell after its end. The first bit in the
+    /// sequence is the first to arrive.
+    fn wire_feeding(bits: &[bool], target: u8) -> Environment<u8> {
+        let length = bits.len() + 1;
+        Environment::new(length + 1, 1, |x, _y| {
+            if x == length {
+                target
+            } else {
+                transmission(false, EAST, x > 0 && bits[length - 1 - x])
+            }
+        }, von_neumann_rule())
+    }
+
+    #[test]
+    fn test_signal_travels_along_wire() {
+        let mut env = wire_feeding(&[true, false], GROUND);
+
+        env.advance();
+        assert_eq!(env.get_cell(2, 0), transmission(false, EAST, false));
+        assert_eq!(env.get_cell(3, 0), SENSITIZED);
+    }
+
+    #[test]
+    fn test_construction_sequences() {
+        let cases = [
+            ("10000", transmission(false, EAST, false)),
+            ("10001", transmission(false, NORTH, false)),
+            ("1001", transmission(false, WEST, false)),
+            ("1010", transmission(false, SOUTH, false)),
+            ("1011", transmission(true, EAST, false)),
+            ("1100", transmission(true, NORTH, false)),
+            ("1101", transmission(true, WEST, false)),
+            ("1110", transmission(true, SOUTH, false)),
+            ("1111", confluent(false, false)),
+        ];
+
+        for (sequence, expected) in cases {
+            let bits: Vec<bool> = sequence.chars().map(|c| c == '1').collect();
+            let mut env = wire_feeding(&bits, GROUND);
+            for _ in 0..bits.len() {
+                env.advance();
+            }
+
+            assert_eq!(env.get_cell(bits.len() + 1, 0), expected, "sequence {}", sequence);
+        }
+    }
+
+    #[test]
+    fn test_confluent_ands_inputs_with_delay() {
+        // Two wires meet at a confluent cell at (1, 1), which feeds a wire heading east.
+        let make = |west_excited, north_excited| Environment::new(3, 2, |x, y| match (x, y) {
+            (0, 1) => transmission(false, EAST, west_excited),
+            (1, 0) => transmission(false, SOUTH, north_excited),
+            (1, 1) => confluent(false, false),
+            (2, 1) => transmission(false, EAST, false),
+            _ => GROUND,
+        }, von_neumann_rule());
+
+        let mut one_input = make(true, false);
+        one_input.advance();
+        assert_eq!(one_input.get_cell(1, 1), confluent(false, false));
+
+        let mut both_inputs = make(true, true);
+        both_inputs.advance();
+        assert_eq!(both_inputs.get_cell(1, 1), confluent(false, true));
+        both_inputs.advance();
+        assert_eq!(both_inputs.get_cell(1, 1), confluent(true, false));
+        both_inputs.advance();
+        assert_eq!(both_inputs.get_cell(2, 1), transmission(false, EAST, true));
+        // The confluent cell doesn't feed back into the wires pointing at it.
+        assert_eq!(both_inputs.get_cell(0, 1), transmission(false, EAST, false));
+    }
+
+    #[test]
+    fn test_opposite_kinds_destroy_each_other() {
+        let mut env = wire_feeding(&[true], transmission(true, NORTH, false));
+        env.advance();
+        assert_eq!(env.get_cell(2, 0), GROUND);
+
+        let mut env = Environment::new(2, 1, |x, _y| if x == 0 { transmission(true, EAST, true) } else { confluent(true, true) }, von_neumann_rule());
+        env.advance();
+        assert_eq!(env.get_cell(1, 0), GROUND);
+    }
+
+    #[test]
+    fn test_load_rle() {
+        // An excited ordinary wire (Q) feeding a quiescent one (M), then ground.
+        let mut env = load_rle("x = 3, y = 1, rule = JvN29\nQ2M!", 1).unwrap();
+        assert_eq!(env.get_dimensions(), (5, 3));
+
+        env.advance();
+        assert_eq!(env.get_cell(2, 1), transmission(false, EAST, true));
+
+        assert!(load_rle("x = 1, y = 1, rule = B3/S23\no!", 0).is_none());
+        assert!(load_rle("x = 1, y = 1, rule = JvN29\npE!", 0).is_none());
+        assert_eq!(load_rle("x = 1, y = 1, rule = Nobili32\npE!", 0).unwrap().get_cell(0, 0), CROSSING_HORIZONTAL);
+        assert!(load_rle("x = 1, y = 1, rule = Nobili32\npH!", 0).is_none());
+    }
+
+    #[test]
+    fn test_nobili_signals_cross() {
+        // A wire heading east and one heading south cross at a confluent cell at (1, 1), with only the eastward wire
+        // carrying a signal.
+        let make = |rule| Environment::new(3, 3, |x, y| match (x, y) {
+            (0, 1) => transmission(false, EAST, true),
+            (2, 1) => transmission(false, EAST, false),
+            (1, 0) => transmission(false, SOUTH, false),
+            (1, 1) => confluent(false, false),
+            (1, 2) => transmission(false, SOUTH, false),
+            _ => GROUND,
+        }, rule);
+
+        let mut nobili = make(nobili_rule());
+        nobili.advance();
+        assert_eq!(nobili.get_cell(1, 1), CROSSING_HORIZONTAL);
+        nobili.advance();
+        assert_eq!((nobili.get_cell(2, 1), nobili.get_cell(1, 2)), (transmission(false, EAST, true), transmission(false, SOUTH, false)));
+
+        // von Neumann's confluent state ANDs the two wires instead, so nothing gets through.
+        let mut jvn = make(von_neumann_rule());
+        for _ in 0..3 {
+            jvn.advance();
+        }
+        assert_eq!((jvn.get_cell(2, 1), jvn.get_cell(1, 2)), (transmission(false, EAST, false), transmission(false, SOUTH, false)));
+    }
+
+    #[test]
+    fn test_pulser_builds_construction_arm() {
+        // A loop of ordinary wire through a confluent cell at (1, 0), carrying a pulse every five generations, which the
+        // confluent cell also sends east down an arm. Each 10000 arriving at the arm's end builds another eastward wire
+        // cell, so the arm grows one cell every five generations.
+        let pulser = "x = 3, y = 2, rule = {}\nQIM$NO!";
+        for rule in ["JvN29", "Nobili32"] {
+            let mut env = load_rle(&pulser.replace("{}", rule), 20).unwrap();
+            for _ in 0..60 {
+                env.advance();
+            }
+
+            let arm = (23..43).take_while(|&x| matches!(decode(env.get_cell(x, 20)), Kind::Transmission { special: false, direction: EAST, .. })).count();
+            assert_eq!(arm, 9, "{}", rule);
+            assert!(matches!(decode(env.get_cell(32, 20)), Kind::Sensitized(_)), "{}", rule);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::Boundary;
+
+/// WireWorld's states, numbered as in Golly.
+pub const EMPTY: u8 = 0;
+pub const ELECTRON_HEAD: u8 = 1;
+pub const ELECTRON_TAIL: u8 = 2;
+pub const CONDUCTOR: u8 = 3;
+
+/// WireWorld on numbered states, for environments that mix it with other rules. Electron heads are state 1 like live
+/// cells in Life, so next to a Life region, live cells act as electron heads and heads count as live cells.
+pub fn wireworld_rule(boundary: Boundary) -> Box<AdvanceCellF<u8>> {
+    Box::new(move |grid, x, y| {
+        match grid.get_cell(x, y) {
+            ELECTRON_HEAD => ELECTRON_TAIL,
+            ELECTRON_TAIL => CONDUCTOR,
+            CONDUCTOR => {
+                let heads = grid.get_moore_neighborhood_around(x, y, boundary).filter(|&cell| cell == ELECTRON_HEAD).count();
+                if heads == 1 || heads == 2 { ELECTRON_HEAD } else { CONDUCTOR }
+            },
+            _ => EMPTY,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::isotropic::LookupRule;
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_wire_carries_electron_into_life_region() {
+        // WireWorld in the left 6 columns and Life to the right, with a wire running up to the boundary.
+        let rules = vec![wireworld_rule(Boundary::Bounded), LookupRule::parse("B3/S23").unwrap().rule(Boundary::Bounded)];
+        let mut env = Environment::new_non_uniform(12, 5, |x, y| if y == 2 && x < 6 { CONDUCTOR } else { EMPTY }, rules, |x, _y| (x >= 6) as usize);
+        env.bulk_set_readable(vec![(0, 2, ELECTRON_TAIL), (1, 2, ELECTRON_HEAD)]);
+
+        for _ in 0..4 {
+            env.advance();
+        }
+        assert_eq!((env.get_cell(4, 2), env.get_cell(5, 2)), (ELECTRON_TAIL, ELECTRON_HEAD));
+
+        // A single head is a lone live cell to Life, so nothing is born across the boundary, and the electron runs off
+        // the end of the wire.
+        env.advance();
+        assert_eq!((env.get_cell(5, 2), env.get_cell(6, 2)), (ELECTRON_TAIL, EMPTY));
+    }
+}
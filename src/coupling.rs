@@ -0,0 +1,278 @@
+use std::ops::Range;
+use crate::environment::{AdvanceCellF, Environment};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Side {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// A stretch of cells along one side of a board, starting `start` cells from the west end of a north or south side, or
+/// the north end of an east or west side.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Edge {
+    pub board: usize,
+    pub side: Side,
+    pub start: usize,
+}
+
+/// One direction of a connection: `from_length` cells along the `from` edge show up just outside `to_length` cells of
+/// the `to` edge. When the lengths differ, each cell of the shorter stretch stands for a run of cells of the longer one.
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Port {
+    from: Edge,
+    to: Edge,
+    from_length: usize,
+    to_length: usize,
+}
+
+impl Port {
+    /// The offsets along the `from` edge that feed the halo cell at `offset` along the `to` edge. The offsets just past
+    /// each end of the join map to the cells just past the ends of the other side.
+    fn source_offsets(&self, offset: isize) -> Range<isize> {
+        let (from_length, to_length) = (self.from_length as isize, self.to_length as isize);
+        if offset < 0 {
+            -1..0
+        } else if offset >= to_length {
+            from_length..from_length + 1
+        } else {
+            let start = offset * from_length / to_length;
+            start..((offset + 1) * from_length / to_length).max(start + 1)
+        }
+    }
+}
+
+/// Several environments, or boards, stepped in lockstep and wired together at their edges, like WireWorld modules
+/// joined by ports. Each board is stored with a halo of cells one wide around it. Before every generation the halos are
+/// reset to the outside state, then filled in from the edge cells of connected boards, so the rules of each board see
+/// its neighbors' edges as if the boards were one grid. Boards can have different sizes and rules.
+///
+/// A coarse board can also be joined to a finer one at a scale, like 2:1. Each coarse edge cell is then replicated
+/// along the run of fine halo cells it faces, and each coarse halo cell samples the run of fine edge cells facing it,
+/// taking the first one that isn't in the outside state.
+pub struct CoupledEnvironments<T> {
+    boards: Vec<Environment<T>>,
+    outside: T,
+    ports: Vec<Port>,
+}
+
+fn side_length(side: Side, (width, height): (usize, usize)) -> usize {
+    match side {
+        Side::North | Side::South => width,
+        Side::East | Side::West => height,
+    }
+}
+
+/// The padded coordinates of the cell `depth` cells inside a side, at `position` along it, where a depth of 0 is the
+/// halo and 1 is the edge of the board.
+fn padded_coord(side: Side, (width, height): (usize, usize), position: isize, depth: usize) -> (usize, usize) {
+    let along = (position + 1) as usize;
+    match side {
+        Side::North => (along, depth),
+        Side::South => (along, height + 1 - depth),
+        Side::West => (depth, along),
+        Side::East => (width + 1 - depth, along),
+    }
+}
+
+impl<T: Copy + PartialEq> CoupledEnvironments<T> {
+    pub fn new(outside: T) -> CoupledEnvironments<T> {
+        CoupledEnvironments {
+            boards: vec![],
+            outside,
+            ports: vec![],
+        }
+    }
+
+    /// Adds a board and returns its index. The rule should treat its grid as bounded, since the halo takes the place of
+    /// anything past the edges.
+    pub fn add_board(&mut self, width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, advance_cell_f: Box<AdvanceCellF<T>>) -> usize {
+        let outside = self.outside;
+        let padded_producer = |x: usize, y: usize| {
+            if x == 0 || y == 0 || x > width || y > height { outside } else { initial_cell_producer(x - 1, y - 1) }
+        };
+        self.boards.push(Environment::new(width + 2, height + 2, padded_producer, advance_cell_f));
+
+        self.boards.len() - 1
+    }
+
+    /// Joins `length` cells along one edge to as many along another, in both directions. Cells diagonally across the
+    /// ends of the join are joined too, where both boards have them. Returns false, without connecting anything, if
+    /// either edge runs off its side.
+    pub fn connect(&mut self, a: Edge, b: Edge, length: usize) -> bool {
+        self.connect_scaled(a, b, length, 1)
+    }
+
+    /// Joins `length` cells along the edge of a coarse board to `length * scale` cells along the edge of a fine one, in
+    /// both directions. Returns false, without connecting anything, if either edge runs off its side or the scale is 0.
+    pub fn connect_scaled(&mut self, coarse: Edge, fine: Edge, length: usize, scale: usize) -> bool {
+        if scale == 0 || [(coarse, length), (fine, length * scale)].iter().any(|&(edge, length)| edge.start + length > side_length(edge.side, self.get_dimensions(edge.board))) {
+            return false;
+        }
+
+        self.ports.push(Port { from: coarse, to: fine, from_length: length, to_length: length * scale });
+        self.ports.push(Port { from: fine, to: coarse, from_length: length * scale, to_length: length });
+        true
+    }
+
+    pub fn get_n_boards(&self) -> usize {
+        self.boards.len()
+    }
+
+    pub fn get_dimensions(&self, board: usize) -> (usize, usize) {
+        let (width, height) = self.boards[board].get_dimensions();
+        (width - 2, height - 2)
+    }
+
+    pub fn get_cell(&self, board: usize, x: usize, y: usize) -> T {
+        self.boards[board].get_cell(x + 1, y + 1)
+    }
+
+    pub fn bulk_set_readable(&mut self, board: usize, cells: Vec<(usize, usize, T)>) {
+        let (width, height) = self.get_dimensions(board);
+
+        for (x, y, cell) in cells {
+            if x < width && y < height {
+                self.boards[board].bulk_set_readable(vec![(x + 1, y + 1, cell)]);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
+    }
+
+    fn fill_halos(&mut self) {
+        for board in self.boards.iter_mut() {
+            let (width, height) = board.get_dimensions();
+            let halo = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| x == 0 || y == 0 || x == width - 1 || y == height - 1)
+                .map(|(x, y)| (x, y, self.outside))
+                .collect();
+            board.bulk_set_readable(halo);
+        }
+
+        for port in self.ports.clone() {
+            let (from, to) = (port.from, port.to);
+            let from_dimensions = self.get_dimensions(from.board);
+            let to_dimensions = self.get_dimensions(to.board);
+            let from_length = side_length(from.side, from_dimensions) as isize;
+            let to_length = side_length(to.side, to_dimensions) as isize;
+
+            let cells = (-1..=port.to_length as isize).filter_map(|offset| {
+                let to_position = to.start as isize + offset;
+                if to_position < -1 || to_position > to_length {
+                    return None;
+                }
+
+                let sources: Vec<T> = port.source_offsets(offset)
+                    .map(|source_offset| from.start as isize + source_offset)
+                    .filter(|&from_position| from_position >= 0 && from_position < from_length)
+                    .map(|from_position| {
+                        let (from_x, from_y) = padded_coord(from.side, from_dimensions, from_position, 1);
+                        self.boards[from.board].get_cell(from_x, from_y)
+                    })
+                    .collect();
+                let cell = *sources.iter().find(|&&cell| cell != self.outside).or(sources.first())?;
+
+                let (to_x, to_y) = padded_coord(to.side, to_dimensions, to_position, 0);
+                Some((to_x, to_y, cell))
+            }).collect();
+            self.boards[to.board].bulk_set_readable(cells);
+        }
+    }
+
+    /// Advances every board by one generation, all from the same generation of their neighbors.
+    pub fn advance(&mut self) {
+        self.fill_halos();
+        for board in self.boards.iter_mut() {
+            board.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::{conway, wireworld};
+    use crate::cell_types::wireworld::{CONDUCTOR, ELECTRON_HEAD, ELECTRON_TAIL, EMPTY};
+    use crate::grid::Boundary;
+
+    #[test]
+    fn test_electron_crosses_into_next_board() {
+        let wire = |_x: usize, y: usize| if y == 1 { CONDUCTOR } else { EMPTY };
+        let mut boards = CoupledEnvironments::new(EMPTY);
+        let left = boards.add_board(5, 3, wire, wireworld::wireworld_rule(Boundary::Bounded));
+        let right = boards.add_board(5, 3, wire, wireworld::wireworld_rule(Boundary::Bounded));
+        assert!(boards.connect(Edge { board: left, side: Side::East, start: 0 }, Edge { board: right, side: Side::West, start: 0 }, 3));
+        assert!(!boards.connect(Edge { board: left, side: Side::South, start: 3 }, Edge { board: right, side: Side::North, start: 0 }, 3));
+        boards.bulk_set_readable(left, vec![(2, 1, ELECTRON_TAIL), (3, 1, ELECTRON_HEAD)]);
+
+        boards.advance();
+        assert_eq!(boards.get_cell(left, 4, 1), ELECTRON_HEAD);
+
+        boards.advance();
+        assert_eq!((boards.get_cell(left, 4, 1), boards.get_cell(right, 0, 1)), (ELECTRON_TAIL, ELECTRON_HEAD));
+    }
+
+    #[test]
+    fn test_coupled_boards_match_one_grid() {
+        // A glider crossing from one board into a wider one below it, against the same glider on one grid.
+        let glider = |x: usize, y: usize| matches!((x, y), (3, 3) | (4, 4) | (2, 5) | (3, 5) | (4, 5)) as u8;
+        let mut boards = CoupledEnvironments::new(0);
+        let top = boards.add_board(12, 7, glider, conway::life_rule(Boundary::Bounded));
+        let bottom = boards.add_board(14, 8, |_x, _y| 0, conway::life_rule(Boundary::Bounded));
+        boards.connect(Edge { board: top, side: Side::South, start: 0 }, Edge { board: bottom, side: Side::North, start: 0 }, 12);
+        let mut single = Environment::new(14, 15, |x, y| if x < 12 { glider(x, y) } else { 0 }, conway::life_rule(Boundary::Bounded));
+        // Past the top board's east edge there's nothing, so the single grid mustn't have anything there either.
+        let in_bounds = |x: usize, y: usize| y >= 7 || x < 12;
+
+        for _ in 0..16 {
+            boards.advance();
+            single.advance();
+            let cells: Vec<(usize, usize, u8)> = (0..15).flat_map(|y| (0..14).map(move |x| (x, y)))
+                .map(|(x, y)| (x, y, if in_bounds(x, y) { single.get_cell(x, y) } else { 0 }))
+                .collect();
+            single.bulk_set_readable(cells);
+
+            for y in 0..15 {
+                for x in 0..14 {
+                    let coupled = if y < 7 { if x < 12 { boards.get_cell(top, x, y) } else { 0 } } else { boards.get_cell(bottom, x, y - 7) };
+                    assert_eq!(coupled, single.get_cell(x, y), "at {}, {}", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_electron_crosses_scaled_join() {
+        // A coarse wire on row 1 faces rows 2 and 3 of a board at twice the scale, which carries its wire on row 2.
+        let mut boards = CoupledEnvironments::new(EMPTY);
+        let coarse = boards.add_board(5, 3, |_x, y| if y == 1 { CONDUCTOR } else { EMPTY }, wireworld::wireworld_rule(Boundary::Bounded));
+        let fine = boards.add_board(6, 6, |_x, y| if y == 2 { CONDUCTOR } else { EMPTY }, wireworld::wireworld_rule(Boundary::Bounded));
+        assert!(!boards.connect_scaled(Edge { board: coarse, side: Side::East, start: 0 }, Edge { board: fine, side: Side::West, start: 1 }, 3, 2));
+        assert!(boards.connect_scaled(Edge { board: coarse, side: Side::East, start: 0 }, Edge { board: fine, side: Side::West, start: 0 }, 3, 2));
+        boards.bulk_set_readable(coarse, vec![(3, 1, ELECTRON_TAIL), (4, 1, ELECTRON_HEAD)]);
+
+        // The coarse head is replicated to both fine halo cells it faces, so the fine wire sees two heads.
+        boards.advance();
+        assert_eq!(boards.get_cell(fine, 0, 2), ELECTRON_HEAD);
+
+        // The coarse halo cell samples that head out of its two fine edge cells, but the coarse edge cell is a tail by
+        // then, so the electron doesn't bounce back.
+        boards.advance();
+        assert_eq!((boards.get_cell(coarse, 4, 1), boards.get_cell(fine, 1, 2)), (CONDUCTOR, ELECTRON_HEAD));
+        boards.advance();
+        assert_eq!((boards.get_cell(coarse, 4, 1), boards.get_cell(fine, 2, 2)), (CONDUCTOR, ELECTRON_HEAD));
+
+        // An electron sent back from the fine board crosses the other way.
+        let mut boards_back = CoupledEnvironments::new(EMPTY);
+        let coarse = boards_back.add_board(5, 3, |_x, y| if y == 1 { CONDUCTOR } else { EMPTY }, wireworld::wireworld_rule(Boundary::Bounded));
+        let fine = boards_back.add_board(6, 6, |_x, y| if y == 2 { CONDUCTOR } else { EMPTY }, wireworld::wireworld_rule(Boundary::Bounded));
+        boards_back.connect_scaled(Edge { board: coarse, side: Side::East, start: 0 }, Edge { board: fine, side: Side::West, start: 0 }, 3, 2);
+        boards_back.bulk_set_readable(fine, vec![(1, 2, ELECTRON_TAIL), (0, 2, ELECTRON_HEAD)]);
+
+        boards_back.advance();
+        assert_eq!(boards_back.get_cell(coarse, 4, 1), ELECTRON_HEAD);
+    }
+}
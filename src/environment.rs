@@ -1,35 +1,615 @@
-/*use std::mem::swap;
-use crate::grid::{CellType, Grid};
+use std::mem::swap;
+use std::ops::{BitXor, Range};
+use crate::grid::Grid;
+use crate::observers::{CellHookF, ChangesHookF, GenerationHookF, ObserverId, Observers, PopulationHookF};
 
-type AdvanceCellF = dyn Fn(&Grid, usize, usize) -> CellType;
+pub type AdvanceCellF<T> = dyn Fn(&Grid<T>, usize, usize) -> T;
 
-struct Environment {
-    read_grid: Grid,
-    write_grid: Grid,
-    advance_cell_f: Box<AdvanceCellF>
+/// A mobile agent that lives on top of the grid, like Langton's ant. Agents can read and write the cell they're on.
+pub trait Agent<T> {
+    fn get_position(&self) -> (usize, usize);
+
+    fn step(&mut self, grid: &mut Grid<T>);
+}
+
+/// A generic double-buffered environment. Every generation, the next state of each cell is computed from the read
+/// grid by advance_cell_f and stored in the write grid, then the grids are swapped. Any agents are then stepped in
+/// the order they were added, each seeing the writes of the agents before it.
+///
+/// A generation can also be split into several phases, like the collision and streaming phases of a lattice gas.
+/// Each phase is a full pass over the grid, and sees the result of the phase before it.
+///
+/// Non-uniform environments follow different rules in different cells, picked by a layer of rule indices. Every cell
+/// reads its neighbors' states as they are, whichever rule the neighbors follow, so rules meeting at a boundary should
+/// agree on what the states they share mean.
+///
+/// Environments can also track the age of each cell, which is how many generations it has been in its current state,
+/// and the cells each generation changed. Changes are noted as cells are written, so finding them doesn't take another
+/// pass over the grid.
+///
+/// Observers can be registered to run before and after each generation, when cells in a region change to a state, or
+/// when a population crosses a threshold. Any observer that needs changes turns change tracking on.
+///
+/// Second-order environments, in the style of Fredkin, XOR the rule's output with each cell's state in the previous
+/// generation, which the write grid keeps between generations. Since XOR is its own inverse, they can also retreat
+/// one generation at a time, exactly undoing advance. Each cell only needs its own previous state, so the next
+/// generation can overwrite the previous one in place before the grids are swapped.
+pub struct Environment<T> {
+    read_grid: Grid<T>,
+    write_grid: Grid<T>,
+    /// The rules of each phase, indexed by the rule map.
+    phases: Vec<Vec<Box<AdvanceCellF<T>>>>,
+    rule_map: Option<Grid<usize>>,
+    agents: Vec<Box<dyn Agent<T>>>,
+    generation: isize,
+    /// The generation each cell last changed in, if ages are being tracked.
+    last_changes: Option<Grid<isize>>,
+    change_tracker: Option<ChangeTracker<T>>,
+    observers: Observers<T>,
+    /// How a second-order environment combines the rule's output with a cell's previous state.
+    second_order: Option<fn(T, T) -> T>,
 }
 
-impl Environment {
-    fn new(width: usize, height: usize, initial_cell_producer: fn(x: usize, y: usize) -> CellType, advance_cell_f: Box<AdvanceCellF>) -> Environment {
-        let read_grid = Grid::new(width, height, initial_cell_producer);
-        let write_grid = Grid::new(width, height, initial_cell_producer);
+/// A cell that changed during a generation, with its states before and after.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CellChange<T> {
+    pub x: usize,
+    pub y: usize,
+    pub old: T,
+    pub new: T,
+}
+
+/// The cells written during a generation. A cell is marked as touched the first time a phase or agent changes it, which
+/// is when its state from the start of the generation is still in the read grid.
+struct ChangeTracker<T> {
+    touched: Grid<bool>,
+    changes: Vec<CellChange<T>>,
+}
+
+impl<T: Copy + PartialEq> Environment<T> {
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, advance_cell_f: Box<AdvanceCellF<T>>) -> Environment<T> {
+        Self::new_phased(width, height, initial_cell_producer, vec![advance_cell_f])
+    }
+
+    pub fn new_phased(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, phases: Vec<Box<AdvanceCellF<T>>>) -> Environment<T> {
+        let read_grid = Grid::new(width, height, &initial_cell_producer);
+        let write_grid = Grid::new(width, height, &initial_cell_producer);
 
         Environment {
-            read_grid: read_grid,
-            write_grid: write_grid,
-            advance_cell_f: advance_cell_f,
+            read_grid,
+            write_grid,
+            phases: phases.into_iter().map(|phase| vec![phase]).collect(),
+            rule_map: None,
+            agents: vec![],
+            generation: 0,
+            last_changes: None,
+            change_tracker: None,
+            observers: Observers::new(),
+            second_order: None,
         }
     }
 
-    fn swap_grids(&mut self) -> () {
-        swap(&mut self.read_grid, &mut self.write_grid);
+    /// An environment where each cell follows the rule at its index in `rules`, as given by `rule_index_producer`.
+    pub fn new_non_uniform(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, rules: Vec<Box<AdvanceCellF<T>>>, rule_index_producer: impl Fn(usize, usize) -> usize) -> Environment<T> {
+        let rule_map = Grid::new(width, height, rule_index_producer);
+        assert!((0..height).all(|y| (0..width).all(|x| rule_map.get_cell(x, y) < rules.len())), "Rule index out of range");
+
+        Environment {
+            read_grid: Grid::new(width, height, &initial_cell_producer),
+            write_grid: Grid::new(width, height, &initial_cell_producer),
+            phases: vec![rules],
+            rule_map: Some(rule_map),
+            agents: vec![],
+            generation: 0,
+            last_changes: None,
+            change_tracker: None,
+            observers: Observers::new(),
+            second_order: None,
+        }
+    }
+
+    /// An environment where cells never change on their own, so that only agents modify the grid.
+    pub fn new_static(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T) -> Environment<T> {
+        Self::new(width, height, initial_cell_producer, Box::new(|grid, x, y| grid.get_cell(x, y)))
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> T {
+        self.read_grid.get_cell(x, y)
+    }
+
+    pub fn get_grid(&self) -> &Grid<T> {
+        &self.read_grid
+    }
+
+    /// A cell's state in the previous generation, which a second-order environment keeps in the write grid.
+    pub fn get_previous_cell(&self, x: usize, y: usize) -> T {
+        assert!(self.second_order.is_some(), "Only second-order environments keep the previous generation");
+        self.write_grid.get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.read_grid.get_width(), self.read_grid.get_height())
+    }
+
+    /// The generation number, which goes negative if a second-order environment retreats past where it started.
+    pub fn get_generation(&self) -> isize {
+        self.generation
+    }
+
+    /// Starts tracking cell ages, counting every cell as having just changed. Ages are worked out from the changes of
+    /// each generation, so this tracks changes too.
+    pub fn track_ages(&mut self) {
+        let (width, height) = self.get_dimensions();
+        self.last_changes = Some(Grid::new_filled(width, height, self.generation));
+        self.track_changes();
+    }
+
+    /// Starts noting the cells that change each generation.
+    pub fn track_changes(&mut self) {
+        if self.change_tracker.is_none() {
+            let (width, height) = self.get_dimensions();
+            self.change_tracker = Some(ChangeTracker { touched: Grid::new_filled(width, height, false), changes: vec![] });
+        }
+    }
+
+    /// The cells the last generation changed, in the order they were first written, or None if changes aren't being
+    /// tracked. Cells that changed and then changed back within the generation aren't included.
+    pub fn get_changes(&self) -> Option<&[CellChange<T>]> {
+        self.change_tracker.as_ref().map(|tracker| tracker.changes.as_slice())
+    }
+
+    /// The generation a cell last changed in, or None if ages aren't being tracked.
+    pub fn get_last_change(&self, x: usize, y: usize) -> Option<isize> {
+        self.last_changes.as_ref().map(|last_changes| last_changes.get_cell(x, y))
+    }
+
+    /// How many generations a cell has been in its current state, or None if ages aren't being tracked.
+    pub fn get_age(&self, x: usize, y: usize) -> Option<usize> {
+        self.get_last_change(x, y).map(|last_change| (self.generation - last_change) as usize)
+    }
+
+    pub fn get_last_changes(&self) -> Option<&Grid<isize>> {
+        self.last_changes.as_ref()
+    }
+
+    /// Runs `hook` with the grid and generation number before every generation.
+    pub fn on_before_generation(&mut self, hook: Box<GenerationHookF<T>>) -> ObserverId {
+        self.observers.add_before(hook)
+    }
+
+    /// Runs `hook` after every generation, with the grid, the new generation number and the cells that changed.
+    pub fn on_after_generation(&mut self, hook: Box<ChangesHookF<T>>) -> ObserverId {
+        self.track_changes();
+        self.observers.add_after(hook)
+    }
+
+    /// Runs `hook` whenever a cell in the region changes to `state`, by a generation or by being set.
+    pub fn on_cell_change(&mut self, columns: Range<usize>, rows: Range<usize>, state: T, hook: Box<CellHookF<T>>) -> ObserverId {
+        self.track_changes();
+        self.observers.add_cell_watcher(columns, rows, state, hook)
+    }
+
+    /// Runs `hook` whenever the number of cells where `counts` is true crosses `threshold`, with the direction, the new
+    /// population and the generation. The population is counted once here, then kept up to date from the changes.
+    pub fn on_population_crossing(&mut self, counts: Box<dyn Fn(T) -> bool>, threshold: usize, hook: Box<PopulationHookF>) -> ObserverId {
+        self.track_changes();
+        self.observers.add_population_watcher(&self.read_grid, counts, threshold, hook)
+    }
+
+    /// Returns false if there's no observer with that ID.
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
+
+    /// The index of the rule a cell follows, which is always 0 in a uniform environment.
+    pub fn get_rule_index(&self, x: usize, y: usize) -> usize {
+        self.rule_map.as_ref().map_or(0, |rule_map| rule_map.get_cell(x, y))
+    }
+
+    /// Switches a cell of a non-uniform environment to another of its rules.
+    pub fn set_rule_index(&mut self, x: usize, y: usize, rule_index: usize) {
+        assert!(self.phases.iter().all(|rules| rule_index < rules.len()), "Rule index out of range");
+        self.rule_map.as_mut().expect("Not a non-uniform environment").set_cell(x, y, rule_index);
+    }
+
+    /// Replaces every phase of a uniform environment with a single rule, for runs that change rules over time.
+    pub fn set_rule(&mut self, advance_cell_f: Box<AdvanceCellF<T>>) {
+        assert!(self.rule_map.is_none(), "Non-uniform environments can't switch to a single rule");
+        self.phases = vec![vec![advance_cell_f]];
+    }
+
+    pub fn add_agent(&mut self, agent: Box<dyn Agent<T>>) {
+        self.agents.push(agent);
+    }
+
+    pub fn get_agents(&self) -> &[Box<dyn Agent<T>>] {
+        &self.agents
+    }
+
+    pub fn bulk_set_readable(&mut self, cells: Vec<(usize, usize, T)>) {
+        let (width, height) = self.get_dimensions();
+
+        let mut changes = vec![];
+        for (x, y, cell) in cells {
+            if x < width && y < height {
+                let old = self.read_grid.get_cell(x, y);
+                if old != cell {
+                    changes.push(CellChange { x, y, old, new: cell });
+                }
+                if let Some(last_changes) = self.last_changes.as_mut() && old != cell {
+                    last_changes.set_cell(x, y, self.generation);
+                }
+                self.read_grid.set_cell(x, y, cell);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
+
+        self.observers.cells_changed(&changes, self.generation);
+    }
+
+    /// Sets cells of the previous generation of a second-order environment, which the next generation is XORed with.
+    pub fn bulk_set_previous(&mut self, cells: Vec<(usize, usize, T)>) {
+        assert!(self.second_order.is_some(), "Only second-order environments keep the previous generation");
+        let (width, height) = self.get_dimensions();
+
+        for (x, y, cell) in cells {
+            if x < width && y < height {
+                self.write_grid.set_cell(x, y, cell);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
     }
 
     pub fn advance(&mut self) {
+        self.observers.before_generation(&self.read_grid, self.generation);
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.changes.clear();
+        }
+
+        for rules in self.phases.iter() {
+            for y in 0..self.read_grid.get_height() {
+                for x in 0..self.read_grid.get_width() {
+                    let mut next_cell = rules[self.get_rule_index(x, y)](&self.read_grid, x, y);
+                    if let Some(combine) = self.second_order {
+                        next_cell = combine(next_cell, self.write_grid.get_cell(x, y));
+                    }
+                    if let Some(tracker) = self.change_tracker.as_mut() {
+                        tracker.note_write(x, y, self.read_grid.get_cell(x, y), next_cell);
+                    }
+                    self.write_grid.set_cell(x, y, next_cell);
+                }
+            }
+
+            swap(&mut self.read_grid, &mut self.write_grid);
+        }
+
+        for agent in self.agents.iter_mut() {
+            let (x, y) = agent.get_position();
+            let before = self.read_grid.get_cell(x, y);
+            agent.step(&mut self.read_grid);
+            if let Some(tracker) = self.change_tracker.as_mut() {
+                tracker.note_write(x, y, before, self.read_grid.get_cell(x, y));
+            }
+        }
+
+        self.generation += 1;
+        self.finish_generation();
+    }
+
+    /// Steps a second-order environment back one generation, exactly undoing advance. Since next = f(current) ^ previous,
+    /// previous = f(current) ^ next, so this is advance with the roles of the grids flipped. Observers and change
+    /// tracking follow along as they do for advance. Ages can't be worked out backwards, so a cell that last changed
+    /// after the generation stepped back to counts as having just entered its state. Like Margolus step_back, this can
+    /// go past the first generation into negative ones. Panics if there are agents, since their steps can't be undone.
+    pub fn retreat(&mut self) {
+        let combine = self.second_order.expect("Only second-order environments can retreat");
+        assert!(self.agents.is_empty(), "Agents can't be stepped back");
+
+        self.observers.before_generation(&self.read_grid, self.generation);
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.changes.clear();
+        }
+
+        let rules = &self.phases[0];
         for y in 0..self.read_grid.get_height() {
             for x in 0..self.read_grid.get_width() {
-                let new_cell_type = *self.advance_cell_f(&self.read_grid, x, y);
+                let current = self.read_grid.get_cell(x, y);
+                let previous_cell = combine(rules[self.get_rule_index(x, y)](&self.write_grid, x, y), current);
+                if let Some(tracker) = self.change_tracker.as_mut() {
+                    tracker.note_write(x, y, current, self.write_grid.get_cell(x, y));
+                }
+                self.read_grid.set_cell(x, y, previous_cell);
             }
         }
+        swap(&mut self.read_grid, &mut self.write_grid);
+
+        self.generation -= 1;
+        if let Some(last_changes) = self.last_changes.as_mut() {
+            let (width, height) = (last_changes.get_width(), last_changes.get_height());
+            *last_changes = Grid::new(width, height, |x, y| last_changes.get_cell(x, y).min(self.generation));
+        }
+        self.finish_generation();
+    }
+
+    fn finish_generation(&mut self) {
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.finish(&self.read_grid);
+            if let Some(last_changes) = self.last_changes.as_mut() {
+                for change in tracker.changes.iter() {
+                    last_changes.set_cell(change.x, change.y, self.generation);
+                }
+            }
+            self.observers.after_generation(&self.read_grid, self.generation, &tracker.changes);
+        }
+    }
+
+    /// Advances by several generations, running any observers along the way.
+    pub fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.advance();
+        }
+    }
+
+    /// Advances until a generation leaves every cell as it was, like toppling a sandpile until it's stable, giving up
+    /// after `max_generations`. Returns how many generations it took, counting the last, unchanged one, or None if the
+    /// grid never settled. Starts tracking changes if they weren't already.
+    pub fn relax(&mut self, max_generations: usize) -> Option<usize> {
+        (1..=max_generations).find(|_| self.advance_with_changes().is_empty())
+    }
+
+    /// Advances like `advance`, and returns the cells that changed. Starts tracking changes if they weren't already.
+    pub fn advance_with_changes(&mut self) -> &[CellChange<T>] {
+        self.track_changes();
+        self.advance();
+        self.get_changes().unwrap()
+    }
+}
+
+impl<T: Copy + PartialEq + BitXor<Output = T>> Environment<T> {
+    /// A second-order environment, where the next state of a cell is advance_cell_f applied to the current generation,
+    /// XORed with the cell's state in the previous generation. The previous generation starts out the same as the
+    /// current one, until set with bulk_set_previous.
+    pub fn new_second_order(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, advance_cell_f: Box<AdvanceCellF<T>>) -> Environment<T> {
+        let mut env = Self::new(width, height, initial_cell_producer, advance_cell_f);
+        env.second_order = Some(|next, previous| next ^ previous);
+        env
+    }
+}
+
+impl<T: Copy + PartialEq> ChangeTracker<T> {
+    fn note_write(&mut self, x: usize, y: usize, old: T, next_cell: T) {
+        if old != next_cell && !self.touched.get_cell(x, y) {
+            self.touched.set_cell(x, y, true);
+            self.changes.push(CellChange { x, y, old, new: old });
+        }
+    }
+
+    /// Fills in the final states of the touched cells, drops any that ended up back where they started, and clears the
+    /// touched marks for the next generation.
+    fn finish(&mut self, grid: &Grid<T>) {
+        for change in self.changes.iter_mut() {
+            change.new = grid.get_cell(change.x, change.y);
+            self.touched.set_cell(change.x, change.y, false);
+        }
+        self.changes.retain(|change| change.old != change.new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::{conway, parity};
+    use crate::cell_types::conway::fixtures;
+    use crate::grid::Boundary;
+    use crate::sandpile::Sandpile;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_life_blinker_oscillates() {
+        let mut env = Environment::new(5, 5, |_x, _y| 0, conway::life_rule(Boundary::Bounded));
+        env.bulk_set_readable(vec![(1, 2, 1), (2, 2, 1), (3, 2, 1)]);
+
+        env.advance();
+
+        let column: Vec<u8> = (0..5).map(|y| env.get_cell(2, y)).collect();
+        let row: Vec<u8> = (0..5).map(|x| env.get_cell(x, 2)).collect();
+        assert_eq!(column, vec![0, 1, 1, 1, 0]);
+        assert_eq!(row, vec![0, 0, 1, 0, 0]);
+
+        env.advance();
+
+        let row: Vec<u8> = (0..5).map(|x| env.get_cell(x, 2)).collect();
+        assert_eq!(row, vec![0, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_cell_ages() {
+        let mut env = Environment::new(7, 5, |_x, _y| 0, conway::life_rule(Boundary::Bounded));
+        assert_eq!(env.get_age(0, 0), None);
+        env.track_ages();
+        // A blinker, and a block that never changes after it's placed.
+        env.bulk_set_readable(vec![(0, 2, 1), (1, 2, 1), (2, 2, 1), (5, 3, 1), (6, 3, 1), (5, 4, 1), (6, 4, 1)]);
+
+        for _ in 0..3 {
+            env.advance();
+        }
+
+        assert_eq!(env.get_generation(), 3);
+        // The blinker's center never changes, but its arms flip every generation.
+        assert_eq!((env.get_age(1, 2), env.get_last_change(1, 2)), (Some(3), Some(0)));
+        assert_eq!((env.get_age(1, 1), env.get_last_change(1, 1)), (Some(0), Some(3)));
+        assert_eq!(env.get_age(6, 4), Some(3));
+
+        env.bulk_set_readable(vec![(6, 4, 0), (0, 0, 0)]);
+        assert_eq!((env.get_age(6, 4), env.get_age(0, 0)), (Some(0), Some(3)));
+    }
+
+    struct Painter {
+        x: usize,
+    }
+
+    impl Agent<u8> for Painter {
+        fn get_position(&self) -> (usize, usize) {
+            (self.x, 0)
+        }
+
+        fn step(&mut self, grid: &mut Grid<u8>) {
+            grid.set_cell(self.x, 0, 7);
+            self.x += 1;
+        }
+    }
+
+    #[test]
+    fn test_changes_match_full_diff() {
+        let mut env = Environment::new(16, 16, fixtures::soup, conway::life_rule(Boundary::Wrapping));
+        env.add_agent(Box::new(Painter { x: 0 }));
+        assert_eq!(env.get_changes(), None);
+
+        for _ in 0..10 {
+            let before = env.get_grid().clone();
+            let changes = env.advance_with_changes().to_vec();
+            let mut expected = vec![];
+            for y in 0..16 {
+                for x in 0..16 {
+                    if before.get_cell(x, y) != env.get_cell(x, y) {
+                        expected.push(CellChange { x, y, old: before.get_cell(x, y), new: env.get_cell(x, y) });
+                    }
+                }
+            }
+
+            let mut sorted = changes.clone();
+            sorted.sort_by_key(|change| (change.y, change.x));
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn test_changes_undone_by_a_later_phase_are_dropped() {
+        // The first phase turns every cell on, the second turns all but (1, 0) back off.
+        let mut env = Environment::new_phased(3, 1, |_x, _y| 0u8, vec![
+            Box::new(|_grid, _x, _y| 1),
+            Box::new(|grid, x, y| if x == 1 { grid.get_cell(x, y) } else { 0 }),
+        ]);
+
+        assert_eq!(env.advance_with_changes(), &[CellChange { x: 1, y: 0, old: 0, new: 1 }]);
+        assert_eq!(env.advance_with_changes(), &[]);
+    }
+
+    #[test]
+    fn test_non_uniform_rules_by_region() {
+        // Life on the left half, and a rule that freezes every cell on the right half.
+        let rules: Vec<Box<AdvanceCellF<u8>>> = vec![conway::life_rule(Boundary::Bounded), Box::new(|grid, x, y| grid.get_cell(x, y))];
+        let mut env = Environment::new_non_uniform(8, 5, |_x, _y| 0, rules, |x, _y| (x >= 4) as usize);
+        // A blinker straddling the boundary, and a lone cell that would die under Life.
+        env.bulk_set_readable(vec![(2, 2, 1), (3, 2, 1), (4, 2, 1), (6, 0, 1)]);
+
+        env.advance();
+
+        let column: Vec<u8> = (0..5).map(|y| env.get_cell(3, y)).collect();
+        assert_eq!(column, vec![0, 1, 1, 1, 0]);
+        // The frozen half keeps its cells, and still counts as neighbors for the Life half.
+        assert_eq!((env.get_cell(2, 2), env.get_cell(4, 2), env.get_cell(6, 0)), (0, 1, 1));
+
+        env.set_rule_index(6, 0, 0);
+        env.advance();
+
+        assert_eq!((env.get_rule_index(6, 0), env.get_cell(6, 0)), (0, 0));
+    }
+
+    #[test]
+    fn test_relax_until_stable() {
+        // The sandpile toppling rule, run one wave per generation, settles where Sandpile::relax does.
+        let toppling_rule = |grid: &Grid<u32>, x: usize, y: usize| {
+            let falling_in = grid.get_von_neumann_neighborhood_around(x, y, Boundary::Bounded).filter(|&grains| grains >= 4).count() as u32;
+            let grains = grid.get_cell(x, y);
+            if grains >= 4 { grains - 4 + falling_in } else { grains + falling_in }
+        };
+        let mut env = Environment::new(7, 7, |x, y| if (x, y) == (3, 3) { 40 } else { 0 }, Box::new(toppling_rule));
+        let mut pile = Sandpile::new(7, 7, |x, y| if (x, y) == (3, 3) { 40 } else { 0 });
+        pile.relax();
+
+        let generations = env.relax(1000).unwrap();
+
+        assert_eq!(env.get_generation(), generations as isize);
+        assert_eq!(env.get_grid(), &Grid::new(7, 7, |x, y| pile.get_cell(x, y)));
+        assert_eq!(env.relax(10), Some(1));
+
+        let mut blinker = Environment::new(5, 5, |_x, _y| 0, conway::life_rule(Boundary::Bounded));
+        blinker.bulk_set_readable(vec![(1, 2, 1), (2, 2, 1), (3, 2, 1)]);
+        assert_eq!(blinker.relax(10), None);
+        assert_eq!(blinker.get_generation(), 10);
     }
-}*/
\ No newline at end of file
+
+    #[test]
+    fn test_second_order_parity_single_seed() {
+        let mut env = Environment::new_second_order(5, 5, |_x, _y| 0, parity::parity_rule(Boundary::Bounded));
+        env.bulk_set_readable(vec![(2, 2, 1)]);
+
+        env.advance();
+
+        assert_eq!(env.get_cell(2, 2), 0);
+        assert_eq!(env.get_cell(2, 1), 1);
+        assert_eq!(env.get_cell(1, 2), 1);
+        assert_eq!(env.get_cell(3, 2), 1);
+        assert_eq!(env.get_cell(2, 3), 1);
+        assert_eq!(env.get_previous_cell(2, 2), 1);
+    }
+
+    #[test]
+    fn test_retreat_undoes_advance() {
+        let mut rng = StdRng::seed_from_u64(27);
+
+        for rule in [parity::parity_rule(Boundary::Wrapping), conway::life_rule(Boundary::Bounded)] {
+            let mut env = Environment::new_second_order(12, 9, |_x, _y| 0, rule);
+            env.bulk_set_readable((0..12 * 9).map(|i| (i % 12, i / 12, rng.random_range(0..2))).collect());
+            env.bulk_set_previous((0..12 * 9).map(|i| (i % 12, i / 12, rng.random_range(0..2))).collect());
+            let initial_grid = env.get_grid().clone();
+            env.track_ages();
+
+            env.run(40);
+            for _ in 0..40 {
+                env.retreat();
+            }
+
+            assert_eq!(env.get_generation(), 0);
+            assert_eq!(env.get_grid(), &initial_grid);
+            assert!((0..9).all(|y| (0..12).all(|x| env.get_age(x, y) == Some(0))));
+        }
+    }
+
+    #[test]
+    fn test_retreat_reports_changes() {
+        let mut env = Environment::new_second_order(5, 5, |_x, _y| 0, parity::parity_rule(Boundary::Bounded));
+        env.bulk_set_readable(vec![(2, 2, 1)]);
+        env.run(2);
+
+        // Stepping back changes the same cells, the other way around.
+        let forward: Vec<(usize, usize, u8, u8)> = env.advance_with_changes().iter().map(|change| (change.x, change.y, change.new, change.old)).collect();
+        env.retreat();
+        let backward: Vec<(usize, usize, u8, u8)> = env.get_changes().unwrap().iter().map(|change| (change.x, change.y, change.old, change.new)).collect();
+
+        assert!(!forward.is_empty());
+        assert_eq!(forward, backward);
+        assert_eq!(env.get_generation(), 2);
+    }
+
+    #[test]
+    fn test_retreat_past_first_generation() {
+        let mut env = Environment::new_second_order(6, 6, |_x, _y| 0, conway::life_rule(Boundary::Wrapping));
+        env.bulk_set_readable(vec![(1, 1, 1), (2, 1, 1), (3, 2, 1)]);
+        let initial_grid = env.get_grid().clone();
+
+        env.advance();
+        for _ in 0..4 {
+            env.retreat();
+        }
+        assert_eq!(env.get_generation(), -3);
+
+        env.run(3);
+        assert_eq!(env.get_generation(), 0);
+        assert_eq!(env.get_grid(), &initial_grid);
+    }
+}
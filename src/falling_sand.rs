@@ -0,0 +1,255 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::grid::Grid;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Material {
+    Empty,
+    Sand,
+    Water,
+    Stone,
+    Fire,
+}
+
+pub const MATERIALS: [Material; 5] = [Material::Empty, Material::Sand, Material::Water, Material::Stone, Material::Fire];
+
+/// The chance that a fire particle burns out on any given step.
+const FIRE_BURNOUT_CHANCE: f64 = 0.1;
+
+impl Material {
+    // Heavier materials sink through lighter ones by swapping places with them.
+    fn density(self) -> u8 {
+        match self {
+            Material::Empty | Material::Fire => 0,
+            Material::Water => 1,
+            Material::Sand | Material::Stone => 2,
+        }
+    }
+}
+
+/// A falling-sand particle simulation. Unlike the double-buffered environments, particles move by swapping places with
+/// a neighbor in a single grid, so updates are applied in a fixed scan order: rows from the bottom up, alternating
+/// between left-to-right and right-to-left every generation to avoid a sideways bias. When two particles want the same
+/// cell, the one scanned first takes it. Each particle moves at most once per generation.
+///
+/// The edges of the grid act as walls.
+pub struct FallingSand {
+    grid: Grid<Material>,
+    moved: Grid<bool>,
+    rng: StdRng,
+    generation: usize,
+}
+
+impl FallingSand {
+    pub fn new(width: usize, height: usize, seed: u64) -> FallingSand {
+        FallingSand {
+            grid: Grid::new_filled(width, height, Material::Empty),
+            moved: Grid::new_filled(width, height, false),
+            rng: StdRng::seed_from_u64(seed),
+            generation: 0,
+        }
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> Material {
+        self.grid.get_cell(x, y)
+    }
+
+    pub fn set_cell(&mut self, x: usize, y: usize, material: Material) {
+        self.grid.set_cell(x, y, material);
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.grid.get_width(), self.grid.get_height())
+    }
+
+    pub fn count(&self, material: Material) -> usize {
+        let (width, height) = self.get_dimensions();
+        (0..height).map(|y| (0..width).filter(|&x| self.get_cell(x, y) == material).count()).sum()
+    }
+
+    pub fn bulk_set_readable(&mut self, cells: Vec<(usize, usize, Material)>) {
+        let (width, height) = self.get_dimensions();
+
+        for (x, y, material) in cells {
+            if x < width && y < height {
+                self.grid.set_cell(x, y, material);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
+    }
+
+    fn offset_coord(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+        let (width, height) = self.get_dimensions();
+        let new_x = x.checked_add_signed(dx)?;
+        let new_y = y.checked_add_signed(dy)?;
+
+        if new_x < width && new_y < height { Some((new_x, new_y)) } else { None }
+    }
+
+    /// Tries each offset in order, moving the particle into the first cell that it can displace.
+    fn try_moves(&mut self, x: usize, y: usize, offsets: &[(isize, isize)]) -> bool {
+        let material = self.get_cell(x, y);
+
+        for &(dx, dy) in offsets {
+            let Some((target_x, target_y)) = self.offset_coord(x, y, dx, dy) else { continue };
+            let target = self.get_cell(target_x, target_y);
+
+            let can_displace = target == Material::Empty || (dy > 0 && target != Material::Stone && target.density() < material.density());
+            if can_displace && !self.moved.get_cell(target_x, target_y) {
+                self.grid.set_cell(target_x, target_y, material);
+                self.grid.set_cell(x, y, target);
+                self.moved.set_cell(target_x, target_y, true);
+                self.moved.set_cell(x, y, target != Material::Empty);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn update_particle(&mut self, x: usize, y: usize, side: isize) {
+        match self.get_cell(x, y) {
+            Material::Empty | Material::Stone => {},
+            Material::Sand => {
+                self.try_moves(x, y, &[(0, 1), (side, 1), (-side, 1)]);
+            },
+            Material::Water => {
+                self.try_moves(x, y, &[(0, 1), (side, 1), (-side, 1), (side, 0), (-side, 0)]);
+            },
+            Material::Fire => {
+                let touching_water = [(0, -1), (-1, 0), (1, 0), (0, 1)].iter().any(|&(dx, dy)| {
+                    self.offset_coord(x, y, dx, dy).is_some_and(|(nx, ny)| self.get_cell(nx, ny) == Material::Water)
+                });
+
+                if touching_water || self.rng.random_bool(FIRE_BURNOUT_CHANCE) {
+                    self.grid.set_cell(x, y, Material::Empty);
+                } else {
+                    self.try_moves(x, y, &[(0, -1), (side, -1), (-side, -1)]);
+                }
+            },
+        }
+    }
+
+    pub fn advance(&mut self) {
+        let (width, height) = self.get_dimensions();
+        self.moved = Grid::new_filled(width, height, false);
+
+        let left_to_right = self.generation.is_multiple_of(2);
+        let side = if left_to_right { 1 } else { -1 };
+
+        for y in (0..height).rev() {
+            for i in 0..width {
+                let x = if left_to_right { i } else { width - 1 - i };
+                if !self.moved.get_cell(x, y) {
+                    self.update_particle(x, y, side);
+                }
+            }
+        }
+
+        self.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sand_falls_to_floor() {
+        let mut world = FallingSand::new(3, 5, 0);
+        world.bulk_set_readable(vec![(1, 0, Material::Sand)]);
+
+        for _ in 0..10 {
+            world.advance();
+        }
+
+        assert_eq!(world.get_cell(1, 4), Material::Sand);
+        assert_eq!(world.count(Material::Sand), 1);
+    }
+
+    #[test]
+    fn test_sand_falls_one_cell_per_generation() {
+        let mut world = FallingSand::new(3, 5, 0);
+        world.bulk_set_readable(vec![(1, 0, Material::Sand)]);
+
+        world.advance();
+
+        assert_eq!(world.get_cell(1, 0), Material::Empty);
+        assert_eq!(world.get_cell(1, 1), Material::Sand);
+    }
+
+    #[test]
+    fn test_sand_slides_off_stacks() {
+        let mut world = FallingSand::new(3, 2, 0);
+        world.bulk_set_readable(vec![(1, 0, Material::Sand), (1, 1, Material::Stone)]);
+
+        world.advance();
+
+        assert_eq!(world.get_cell(1, 0), Material::Empty);
+        assert_eq!(world.get_cell(2, 1), Material::Sand);
+    }
+
+    #[test]
+    fn test_scan_order_resolves_conflict() {
+        // Both grains want (1, 1). The left grain is scanned first on even generations, so it wins.
+        let mut world = FallingSand::new(3, 2, 0);
+        world.bulk_set_readable(vec![
+            (0, 0, Material::Sand), (2, 0, Material::Sand),
+            (0, 1, Material::Stone), (2, 1, Material::Stone),
+        ]);
+
+        world.advance();
+
+        assert_eq!(world.get_cell(1, 1), Material::Sand);
+        assert_eq!(world.get_cell(0, 0), Material::Empty);
+        assert_eq!(world.get_cell(2, 0), Material::Sand);
+    }
+
+    #[test]
+    fn test_water_levels_out() {
+        let mut world = FallingSand::new(4, 3, 0);
+        world.bulk_set_readable(vec![(0, 0, Material::Water), (0, 1, Material::Water), (0, 2, Material::Water)]);
+
+        for _ in 0..20 {
+            world.advance();
+        }
+
+        assert_eq!(world.count(Material::Water), 3);
+        assert_eq!((0..4).filter(|&x| world.get_cell(x, 2) == Material::Water).count(), 3);
+    }
+
+    #[test]
+    fn test_sand_sinks_through_water() {
+        let mut world = FallingSand::new(1, 2, 0);
+        world.bulk_set_readable(vec![(0, 0, Material::Sand), (0, 1, Material::Water)]);
+
+        world.advance();
+
+        assert_eq!(world.get_cell(0, 0), Material::Water);
+        assert_eq!(world.get_cell(0, 1), Material::Sand);
+    }
+
+    #[test]
+    fn test_fire_rises_and_burns_out() {
+        let mut world = FallingSand::new(3, 10, 30);
+        world.bulk_set_readable(vec![(1, 9, Material::Fire), (0, 9, Material::Fire)]);
+
+        for _ in 0..200 {
+            world.advance();
+        }
+
+        assert_eq!(world.count(Material::Fire), 0);
+    }
+
+    #[test]
+    fn test_water_extinguishes_fire() {
+        let mut world = FallingSand::new(2, 1, 0);
+        world.bulk_set_readable(vec![(0, 0, Material::Fire), (1, 0, Material::Water)]);
+
+        world.advance();
+
+        assert_eq!(world.count(Material::Fire), 0);
+        assert_eq!(world.count(Material::Water), 1);
+    }
+}
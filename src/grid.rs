@@ -1,17 +1,73 @@
-/*pub type CellType = i8;
+pub const MOORE_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0), (1, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
 
-pub struct Grid {
-    cells: Vec<Vec<CellType>>,
+pub const VON_NEUMANN_OFFSETS: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum NeighborhoodType {
+    Moore,
+    VonNeumann,
+}
+
+/// The offsets of every cell within range of the center, excluding the center itself. Moore neighborhoods are squares,
+/// and von Neumann neighborhoods are diamonds.
+pub fn neighborhood_offsets(neighborhood_type: NeighborhoodType, range: usize) -> Vec<(isize, isize)> {
+    let range = range as isize;
+    let mut offsets = vec![];
+
+    for dy in -range..=range {
+        for dx in -range..=range {
+            let in_range = match neighborhood_type {
+                NeighborhoodType::Moore => true,
+                NeighborhoodType::VonNeumann => dx.abs() + dy.abs() <= range,
+            };
+
+            if in_range && (dx, dy) != (0, 0) {
+                offsets.push((dx, dy));
+            }
+        }
+    }
+
+    offsets
+}
+
+/// Hexagonal neighbors in "odd-r" offset coordinates, where odd rows are shifted half a cell to the right. Directions
+/// are ordered counterclockwise, starting from east: E, NE, NW, W, SW, SE.
+pub const HEX_EVEN_ROW_OFFSETS: [(isize, isize); 6] = [(1, 0), (0, -1), (-1, -1), (-1, 0), (-1, 1), (0, 1)];
+pub const HEX_ODD_ROW_OFFSETS: [(isize, isize); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (0, 1), (1, 1)];
+
+pub fn hex_offsets_for_row(y: usize) -> &'static [(isize, isize); 6] {
+    if y.is_multiple_of(2) { &HEX_EVEN_ROW_OFFSETS } else { &HEX_ODD_ROW_OFFSETS }
+}
+
+/// How neighbors past the edge of the grid are treated.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Boundary {
+    /// Cells past the edge don't exist, so edge cells have fewer neighbors.
+    Bounded,
+    /// The grid is a torus, and the edges are neighbors of each other.
+    Wrapping,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Grid<T> {
+    cells: Vec<Vec<T>>,
 }
 
-impl Grid {
-    pub fn new(width: usize, height: usize, initial_cell_producer: fn(x: usize, y: usize) -> CellType) -> Grid {
+/// Cells can be any type, including structs that carry per-cell data. Types that are cheap to copy get some extra
+/// conveniences, like reading cells and neighborhoods by value.
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T) -> Grid<T> {
         let mut vec_grid: Vec<Vec<T>> = Vec::with_capacity(height);
         for y in 0..height {
             let mut row: Vec<T> = Vec::with_capacity(width);
             for x in 0..width {
                 row.push(initial_cell_producer(x, y));
             }
+            vec_grid.push(row);
         }
 
         Grid {
@@ -27,34 +83,150 @@ impl Grid {
         self.cells.len()
     }
 
-    pub fn get_cell(&self, x: usize, y: usize) -> CellType {
-        self.cells[y][x]
+    pub fn get_cell_ref(&self, x: usize, y: usize) -> &T {
+        &self.cells[y][x]
     }
 
-    pub fn set_cell(&mut self, x: usize, y: usize, value: CellType) {
-        self.cells[y][x] = value;
+    pub fn get_cell_mut(&mut self, x: usize, y: usize) -> &mut T {
+        &mut self.cells[y][x]
     }
 
-    // TODO: Figure out how to make lazy. Either unstable generators, or make a 2D range iterator somehow
-    pub fn get_moore_neighborhood_around(&self, center_x: usize, center_y: usize) -> Vec<CellType> {
-        let mut neighbors: Vec<CellType> = vec![];
+    pub fn set_cell(&mut self, x: usize, y: usize, value: T) {
+        self.cells[y][x] = value;
+    }
 
-        let width = self.get_width();
-        let height = self.get_height();
+    /// Swaps the contents of two cells, which is how movement-based models move payloads around without copying.
+    pub fn swap_cells(&mut self, first: (usize, usize), second: (usize, usize)) {
+        if first.1 == second.1 {
+            self.cells[first.1].swap(first.0, second.0);
+        } else {
+            let (upper, lower) = if first.1 < second.1 { (first, second) } else { (second, first) };
+            let (upper_rows, lower_rows) = self.cells.split_at_mut(lower.1);
+            std::mem::swap(&mut upper_rows[upper.1][upper.0], &mut lower_rows[0][lower.0]);
+        }
+    }
 
-        let min_x = center_x.saturating_sub(1).clamp(0, width);
-        let max_x = center_x.saturating_add(1).clamp(0, width);
-        let min_y = center_y.saturating_sub(1).clamp(0, height);
-        let max_y = center_y.saturating_add(1).clamp(0, height);
+    pub fn get_neighbor_coord(&self, x: usize, y: usize, offset: (isize, isize), boundary: Boundary) -> Option<(usize, usize)> {
+        let width = self.get_width() as isize;
+        let height = self.get_height() as isize;
+        let neighbor_x = x as isize + offset.0;
+        let neighbor_y = y as isize + offset.1;
 
-        for y in min_y..=max_y {
-            for x in min_x..=max_x {
-                neighbors.push(self.get_cell(x, y));
-            }
+        match boundary {
+            Boundary::Bounded => {
+                if (0..width).contains(&neighbor_x) && (0..height).contains(&neighbor_y) {
+                    Some((neighbor_x as usize, neighbor_y as usize))
+                } else {
+                    None
+                }
+            },
+            Boundary::Wrapping => Some((neighbor_x.rem_euclid(width) as usize, neighbor_y.rem_euclid(height) as usize)),
         }
+    }
+}
 
-        neighbors
+impl<T: Clone> Grid<T> {
+    pub fn new_filled(width: usize, height: usize, value: T) -> Grid<T> {
+        Self::new(width, height, |_x, _y| value.clone())
     }
 }
 
-*/
\ No newline at end of file
+impl<T: Copy> Grid<T> {
+    pub fn get_cell(&self, x: usize, y: usize) -> T {
+        self.cells[y][x]
+    }
+
+    /// Reads a cell as if the grid were a torus, so any offset from any cell is valid.
+    pub fn get_wrapped_cell(&self, x: isize, y: isize) -> T {
+        let wrapped_x = x.rem_euclid(self.get_width() as isize) as usize;
+        let wrapped_y = y.rem_euclid(self.get_height() as isize) as usize;
+
+        self.get_cell(wrapped_x, wrapped_y)
+    }
+
+    pub fn get_neighbors_around<'a>(&'a self, x: usize, y: usize, offsets: &'a [(isize, isize)], boundary: Boundary) -> impl Iterator<Item = T> + 'a {
+        offsets.iter().filter_map(move |&offset| {
+            self.get_neighbor_coord(x, y, offset, boundary).map(|(neighbor_x, neighbor_y)| self.get_cell(neighbor_x, neighbor_y))
+        })
+    }
+
+    pub fn get_moore_neighborhood_around(&self, x: usize, y: usize, boundary: Boundary) -> impl Iterator<Item = T> + '_ {
+        self.get_neighbors_around(x, y, &MOORE_OFFSETS, boundary)
+    }
+
+    pub fn get_von_neumann_neighborhood_around(&self, x: usize, y: usize, boundary: Boundary) -> impl Iterator<Item = T> + '_ {
+        self.get_neighbors_around(x, y, &VON_NEUMANN_OFFSETS, boundary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_wrapped_cell() {
+        let grid = Grid::new(3, 2, |x, y| x + y * 3);
+
+        assert_eq!(grid.get_wrapped_cell(0, 0), 0);
+        assert_eq!(grid.get_wrapped_cell(-1, 0), 2);
+        assert_eq!(grid.get_wrapped_cell(3, 1), 3);
+        assert_eq!(grid.get_wrapped_cell(-1, -1), 5);
+    }
+
+    #[test]
+    fn test_swap_cells_holds_payloads() {
+        let mut grid = Grid::new(2, 2, |x, y| vec![x, y]);
+
+        grid.swap_cells((0, 0), (1, 1));
+        grid.swap_cells((0, 1), (1, 1));
+
+        assert_eq!(grid.get_cell_ref(0, 0), &vec![1, 1]);
+        assert_eq!(grid.get_cell_ref(1, 1), &vec![0, 1]);
+        assert_eq!(grid.get_cell_ref(0, 1), &vec![0, 0]);
+    }
+
+    #[test]
+    fn test_get_moore_neighborhood_around_bounded_corner() {
+        let grid = Grid::new(3, 3, |x, y| x + y * 3);
+        let neighbors: Vec<usize> = grid.get_moore_neighborhood_around(0, 0, Boundary::Bounded).collect();
+
+        assert_eq!(neighbors, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_get_moore_neighborhood_around_wrapping_corner() {
+        let grid = Grid::new(3, 3, |x, y| x + y * 3);
+        let neighbors: Vec<usize> = grid.get_moore_neighborhood_around(0, 0, Boundary::Wrapping).collect();
+
+        assert_eq!(neighbors, vec![8, 6, 7, 2, 1, 5, 3, 4]);
+    }
+
+    #[test]
+    fn test_get_von_neumann_neighborhood_around_bounded_edge() {
+        let grid = Grid::new(3, 3, |x, y| x + y * 3);
+        let neighbors: Vec<usize> = grid.get_von_neumann_neighborhood_around(1, 0, Boundary::Bounded).collect();
+
+        assert_eq!(neighbors, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn test_neighborhood_offsets() {
+        assert_eq!(neighborhood_offsets(NeighborhoodType::Moore, 1), MOORE_OFFSETS.to_vec());
+        assert_eq!(neighborhood_offsets(NeighborhoodType::VonNeumann, 1), VON_NEUMANN_OFFSETS.to_vec());
+        assert_eq!(neighborhood_offsets(NeighborhoodType::Moore, 3).len(), 48);
+        assert_eq!(neighborhood_offsets(NeighborhoodType::VonNeumann, 2).len(), 12);
+    }
+
+    #[test]
+    fn test_hex_offsets_are_symmetric() {
+        // Stepping in a direction, then in the opposite direction, should lead back to the start on both row parities.
+        let grid = Grid::new_filled(6, 6, 0);
+        for (x, y) in [(2, 2), (2, 3)] {
+            for direction in 0..6 {
+                let there = grid.get_neighbor_coord(x, y, hex_offsets_for_row(y)[direction], Boundary::Wrapping).unwrap();
+                let back = grid.get_neighbor_coord(there.0, there.1, hex_offsets_for_row(there.1)[(direction + 3) % 6], Boundary::Wrapping).unwrap();
+                assert_eq!(back, (x, y));
+            }
+        }
+    }
+}
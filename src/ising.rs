@@ -0,0 +1,207 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::grid::{Boundary, Grid};
+
+pub const UP: i8 = 1;
+pub const DOWN: i8 = -1;
+
+/// Onsager's exact critical temperature for the square lattice with J = 1, 2 / ln(1 + √2).
+pub const CRITICAL_TEMPERATURE: f64 = 2.269185314213022;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Dynamics {
+    /// Flips a spin whenever that lowers the energy, and otherwise with probability exp(-ΔE / T).
+    Metropolis,
+    /// Sets a spin up with its equilibrium probability given its neighbors, regardless of its current value.
+    HeatBath,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct IsingMeasurement {
+    /// The mean spin, between -1 and 1.
+    pub magnetization: f64,
+    /// The energy per spin.
+    pub energy: f64,
+}
+
+/// The ferromagnetic Ising model on a square lattice, with energy -J Σ s_i s_j - h Σ s_i over nearest-neighbor pairs.
+/// Each sweep updates the two checkerboard sublattices in turn. Spins on the same sublattice don't interact, so updating
+/// one sublattice in place is the same as updating it simultaneously.
+pub struct Ising {
+    spins: Grid<i8>,
+    pub temperature: f64,
+    pub external_field: f64,
+    pub coupling: f64,
+    pub dynamics: Dynamics,
+    boundary: Boundary,
+    rng: StdRng,
+    history: Vec<IsingMeasurement>,
+}
+
+impl Ising {
+    /// The lattice wraps, so its dimensions must be even, or the checkerboard sublattices would touch across the seam.
+    pub fn new(width: usize, height: usize, temperature: f64, dynamics: Dynamics, initial_cell_producer: impl Fn(usize, usize) -> i8, seed: u64) -> Ising {
+        assert!(width.is_multiple_of(2) && height.is_multiple_of(2), "Ising lattice dimensions must be even");
+
+        let mut ising = Ising {
+            spins: Grid::new(width, height, |x, y| if initial_cell_producer(x, y) < 0 { DOWN } else { UP }),
+            temperature,
+            external_field: 0.0,
+            coupling: 1.0,
+            dynamics,
+            boundary: Boundary::Wrapping,
+            rng: StdRng::seed_from_u64(seed),
+            history: vec![],
+        };
+        ising.history.push(ising.measure());
+
+        ising
+    }
+
+    /// Starts from infinite temperature, with every spin independently up or down.
+    pub fn new_random(width: usize, height: usize, temperature: f64, dynamics: Dynamics, seed: u64) -> Ising {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let spins: Vec<i8> = (0..width * height).map(|_| if rng.random_bool(0.5) { UP } else { DOWN }).collect();
+
+        Self::new(width, height, temperature, dynamics, |x, y| spins[y * width + x], seed)
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> i8 {
+        self.spins.get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.spins.get_width(), self.spins.get_height())
+    }
+
+    /// The measurements at the start, and after every sweep since.
+    pub fn get_history(&self) -> &[IsingMeasurement] {
+        &self.history
+    }
+
+    fn neighbor_sum(&self, x: usize, y: usize) -> f64 {
+        self.spins.get_von_neumann_neighborhood_around(x, y, self.boundary)
+            .map(|spin| spin as f64)
+            .sum()
+    }
+
+    /// The field a spin feels from its neighbors and the external field. The spin's energy is minus this times the spin.
+    fn local_field(&self, x: usize, y: usize) -> f64 {
+        self.coupling * self.neighbor_sum(x, y) + self.external_field
+    }
+
+    pub fn measure(&self) -> IsingMeasurement {
+        let (width, height) = self.get_dimensions();
+        let mut total_spin = 0.0;
+        let mut total_energy = 0.0;
+
+        for y in 0..height {
+            for x in 0..width {
+                let spin = self.get_cell(x, y) as f64;
+                total_spin += spin;
+                // Halve the pair term, since every bond is counted from both ends.
+                total_energy -= spin * (self.coupling * self.neighbor_sum(x, y) / 2.0 + self.external_field);
+            }
+        }
+
+        let n_spins = (width * height) as f64;
+        IsingMeasurement {
+            magnetization: total_spin / n_spins,
+            energy: total_energy / n_spins,
+        }
+    }
+
+    fn update_spin(&mut self, x: usize, y: usize) {
+        let field = self.local_field(x, y);
+        let spin = self.get_cell(x, y);
+
+        let next_spin = match self.dynamics {
+            Dynamics::Metropolis => {
+                let energy_change = 2.0 * spin as f64 * field;
+                let flip = energy_change <= 0.0
+                    || (self.temperature > 0.0 && self.rng.random_bool((-energy_change / self.temperature).exp()));
+                if flip { -spin } else { spin }
+            },
+            Dynamics::HeatBath => {
+                let up_probability = if self.temperature > 0.0 {
+                    1.0 / (1.0 + (-2.0 * field / self.temperature).exp())
+                } else if field == 0.0 {
+                    0.5
+                } else if field > 0.0 { 1.0 } else { 0.0 };
+                if self.rng.random_bool(up_probability) { UP } else { DOWN }
+            },
+        };
+
+        self.spins.set_cell(x, y, next_spin);
+    }
+
+    /// One sweep, which visits every spin once.
+    pub fn advance(&mut self) {
+        let (width, height) = self.get_dimensions();
+
+        for parity in 0..2 {
+            for y in 0..height {
+                for x in ((y + parity) % 2..width).step_by(2) {
+                    self.update_spin(x, y);
+                }
+            }
+        }
+
+        self.history.push(self.measure());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ground_state_measurements() {
+        let mut ising = Ising::new(8, 8, 1.0, Dynamics::Metropolis, |_x, _y| UP, 0);
+        assert_eq!(ising.measure(), IsingMeasurement { magnetization: 1.0, energy: -2.0 });
+
+        ising.external_field = 0.5;
+        assert_eq!(ising.measure().energy, -2.5);
+
+        let checkerboard = Ising::new(8, 8, 1.0, Dynamics::Metropolis, |x, y| if (x + y).is_multiple_of(2) { UP } else { DOWN }, 0);
+        assert_eq!(checkerboard.measure(), IsingMeasurement { magnetization: 0.0, energy: 2.0 });
+    }
+
+    #[test]
+    fn test_zero_temperature_heat_bath_aligns_with_field() {
+        let mut ising = Ising::new_random(16, 16, 0.0, Dynamics::HeatBath, 36);
+        ising.coupling = 0.0;
+        ising.external_field = -1.0;
+
+        ising.advance();
+
+        assert_eq!(ising.measure().magnetization, -1.0);
+    }
+
+    #[test]
+    fn test_ordered_phase_below_critical_temperature() {
+        for dynamics in [Dynamics::Metropolis, Dynamics::HeatBath] {
+            let mut ising = Ising::new(32, 32, 1.5, dynamics, |_x, _y| UP, 36);
+            for _ in 0..100 {
+                ising.advance();
+            }
+
+            assert!(ising.measure().magnetization > 0.9);
+        }
+    }
+
+    #[test]
+    fn test_disordered_phase_above_critical_temperature() {
+        for dynamics in [Dynamics::Metropolis, Dynamics::HeatBath] {
+            let mut ising = Ising::new(32, 32, 5.0, dynamics, |_x, _y| UP, 36);
+            for _ in 0..200 {
+                ising.advance();
+            }
+
+            let recent = &ising.get_history()[100..];
+            let mean_magnetization = recent.iter().map(|measurement| measurement.magnetization).sum::<f64>() / recent.len() as f64;
+            assert!(mean_magnetization.abs() < 0.2);
+            assert_eq!(ising.get_history().len(), 201);
+        }
+    }
+}
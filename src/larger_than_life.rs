@@ -0,0 +1,334 @@
+use std::ops::RangeInclusive;
+use crate::grid::{Boundary, Grid};
+
+pub const DEAD: u8 = 0;
+pub const ALIVE: u8 = 1;
+
+/// A block of offsets that all carry the same weight, from `(left, top)` to `(right, bottom)` inclusive.
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct WeightedRect {
+    left: isize,
+    top: isize,
+    right: isize,
+    bottom: isize,
+    weight: i64,
+}
+
+/// A weighted neighborhood, stored as rectangles of equal weight so that each one can be summed in constant time from
+/// a summed-area table. Boxes are a single rectangle and diamonds one per row, so the cost per cell grows with the
+/// outline of the neighborhood rather than its area.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Kernel {
+    rects: Vec<WeightedRect>,
+    radius: usize,
+}
+
+impl Kernel {
+    /// Offsets that aren't listed have weight 0. Listing an offset twice adds the weights.
+    pub fn from_weights(weights: &[((isize, isize), i64)]) -> Kernel {
+        let radius = weights.iter().map(|&((dx, dy), _)| dx.unsigned_abs().max(dy.unsigned_abs())).max().unwrap_or(0);
+        let r = radius as isize;
+        let side = 2 * radius + 1;
+        let mut matrix = vec![vec![0; side]; side];
+        for &((dx, dy), weight) in weights {
+            matrix[(dy + r) as usize][(dx + r) as usize] += weight;
+        }
+
+        // Split each row into runs of equal weight, then merge runs that continue the one directly above them.
+        let mut rects: Vec<WeightedRect> = vec![];
+        let mut open: Vec<usize> = vec![];
+        for (row, weights) in matrix.iter().enumerate() {
+            let dy = row as isize - r;
+            let mut still_open = vec![];
+            let mut start = 0;
+            while start < side {
+                let weight = weights[start];
+                let end = (start..side).take_while(|&column| weights[column] == weight).last().unwrap();
+                if weight != 0 {
+                    let (left, right) = (start as isize - r, end as isize - r);
+                    let above = open.iter().copied().find(|&index| {
+                        let rect = rects[index];
+                        rect.left == left && rect.right == right && rect.weight == weight
+                    });
+                    match above {
+                        Some(index) => {
+                            rects[index].bottom = dy;
+                            still_open.push(index);
+                        },
+                        None => {
+                            rects.push(WeightedRect { left, top: dy, right, bottom: dy, weight });
+                            still_open.push(rects.len() - 1);
+                        },
+                    }
+                }
+                start = end + 1;
+            }
+            open = still_open;
+        }
+
+        Kernel { rects, radius }
+    }
+
+    /// The (2r + 1) x (2r + 1) box, with or without the center.
+    pub fn moore(range: usize, include_center: bool) -> Kernel {
+        let r = range as isize;
+        let offsets: Vec<((isize, isize), i64)> = (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| ((dx, dy), 1)))
+            .filter(|&((dx, dy), _)| include_center || (dx, dy) != (0, 0))
+            .collect();
+
+        Self::from_weights(&offsets)
+    }
+
+    /// The diamond of cells within Manhattan distance r, with or without the center.
+    pub fn von_neumann(range: usize, include_center: bool) -> Kernel {
+        let r = range as isize;
+        let offsets: Vec<((isize, isize), i64)> = (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| ((dx, dy), 1)))
+            .filter(|&((dx, dy), _)| dx.abs() + dy.abs() <= r && (include_center || (dx, dy) != (0, 0)))
+            .collect();
+
+        Self::from_weights(&offsets)
+    }
+
+    pub fn get_radius(&self) -> usize {
+        self.radius
+    }
+
+    /// The number of rectangles summed for each cell.
+    pub fn get_n_rects(&self) -> usize {
+        self.rects.len()
+    }
+
+    /// The weighted sum around every cell, counting cells where `counts` is true as 1 and the rest as 0. Outside a
+    /// bounded grid counts as 0.
+    pub fn sums<T: Copy>(&self, grid: &Grid<T>, boundary: Boundary, counts: impl Fn(T) -> bool) -> Grid<i64> {
+        let width = grid.get_width();
+        let height = grid.get_height();
+        let r = self.radius as isize;
+
+        // The table is padded by the radius on every side, and has an extra row and column of zeros at the start, so
+        // table[y][x] is the total of the padded cells above and left of (x, y).
+        let padded_width = width + 2 * self.radius;
+        let padded_height = height + 2 * self.radius;
+        let mut table = vec![vec![0i64; padded_width + 1]; padded_height + 1];
+        for py in 0..padded_height {
+            let mut row_total = 0;
+            for px in 0..padded_width {
+                let x = px as isize - r;
+                let y = py as isize - r;
+                let inside = match boundary {
+                    Boundary::Wrapping => true,
+                    Boundary::Bounded => x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height,
+                };
+                if inside && counts(grid.get_wrapped_cell(x, y)) {
+                    row_total += 1;
+                }
+                table[py + 1][px + 1] = table[py][px + 1] + row_total;
+            }
+        }
+
+        Grid::new(width, height, |x, y| {
+            self.rects.iter().map(|rect| {
+                let left = (x as isize + r + rect.left) as usize;
+                let right = (x as isize + r + rect.right) as usize + 1;
+                let top = (y as isize + r + rect.top) as usize;
+                let bottom = (y as isize + r + rect.bottom) as usize + 1;
+                rect.weight * (table[bottom][right] - table[top][right] - table[bottom][left] + table[top][left])
+            }).sum()
+        })
+    }
+}
+
+/// A range-r totalistic rule in the style of Evans's Larger than Life. A dead cell is born when its neighborhood sum is
+/// in a birth range, and a live cell survives when its sum is in a survival range. With more than two states, cells
+/// that don't survive go through the dying states one generation at a time, and only live cells are counted.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LtlRule {
+    pub kernel: Kernel,
+    pub n_states: u8,
+    pub birth: Vec<RangeInclusive<i64>>,
+    pub survival: Vec<RangeInclusive<i64>>,
+}
+
+fn parse_range(range: &str) -> Option<RangeInclusive<i64>> {
+    let (min, max) = range.split_once("..")?;
+    Some(min.parse().ok()?..=max.parse().ok()?)
+}
+
+impl LtlRule {
+    /// Parses Golly's format, like `R5,C0,M1,S34..58,B34..45,NM`. C0 and C2 both mean two states, M1 counts the
+    /// center cell, and the neighborhood is NM for a box or NN for a diamond, defaulting to the box.
+    pub fn parse(rule: &str) -> Option<LtlRule> {
+        let mut range = None;
+        let mut n_states = None;
+        let mut include_center = None;
+        let mut survival = None;
+        let mut birth = None;
+        let mut diamond = false;
+
+        for field in rule.split(',').map(str::trim) {
+            let (key, value) = field.split_at_checked(1)?;
+            match key {
+                "R" => range = Some(value.parse::<usize>().ok().filter(|&r| r >= 1)?),
+                "C" => n_states = Some(value.parse::<u8>().ok()?.max(2)),
+                "M" => include_center = Some(match value { "0" => false, "1" => true, _ => return None }),
+                "S" => survival = Some(parse_range(value)?),
+                "B" => birth = Some(parse_range(value)?),
+                "N" => diamond = match value { "M" => false, "N" => true, _ => return None },
+                _ => return None,
+            }
+        }
+
+        let (range, include_center) = (range?, include_center?);
+        let kernel = if diamond { Kernel::von_neumann(range, include_center) } else { Kernel::moore(range, include_center) };
+
+        Some(LtlRule {
+            kernel,
+            n_states: n_states?,
+            birth: vec![birth?],
+            survival: vec![survival?],
+        })
+    }
+
+    /// Bosco's rule, which has a famous range-5 spaceship called Bosco.
+    pub fn bosco() -> LtlRule {
+        Self::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap()
+    }
+
+    pub fn next_state(&self, state: u8, sum: i64) -> u8 {
+        match state {
+            DEAD => if self.birth.iter().any(|range| range.contains(&sum)) { ALIVE } else { DEAD },
+            ALIVE => {
+                if self.survival.iter().any(|range| range.contains(&sum)) {
+                    ALIVE
+                } else if self.n_states > 2 {
+                    2
+                } else {
+                    DEAD
+                }
+            },
+            dying => if dying + 1 < self.n_states { dying + 1 } else { DEAD },
+        }
+    }
+}
+
+/// A grid running a Larger than Life or weighted-neighborhood rule. Each generation computes every neighborhood sum from
+/// one summed-area table, instead of going through the per-cell rules other environments use.
+pub struct LargerThanLife {
+    grid: Grid<u8>,
+    rule: LtlRule,
+    boundary: Boundary,
+    generation: usize,
+}
+
+impl LargerThanLife {
+    pub fn new(width: usize, height: usize, rule: LtlRule, boundary: Boundary, initial_cell_producer: impl Fn(usize, usize) -> u8) -> LargerThanLife {
+        LargerThanLife {
+            grid: Grid::new(width, height, initial_cell_producer),
+            rule,
+            boundary,
+            generation: 0,
+        }
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> u8 {
+        self.grid.get_cell(x, y)
+    }
+
+    pub fn get_grid(&self) -> &Grid<u8> {
+        &self.grid
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.grid.get_width(), self.grid.get_height())
+    }
+
+    pub fn get_rule(&self) -> &LtlRule {
+        &self.rule
+    }
+
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn count_alive(&self) -> usize {
+        let (width, height) = self.get_dimensions();
+        (0..height).map(|y| (0..width).filter(|&x| self.get_cell(x, y) == ALIVE).count()).sum()
+    }
+
+    pub fn advance(&mut self) {
+        let sums = self.rule.kernel.sums(&self.grid, self.boundary, |state| state == ALIVE);
+        let (width, height) = self.get_dimensions();
+        self.grid = Grid::new(width, height, |x, y| self.rule.next_state(self.grid.get_cell(x, y), sums.get_cell(x, y)));
+        self.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway::fixtures;
+
+    fn brute_force_sum(grid: &Grid<u8>, weights: &[((isize, isize), i64)], x: usize, y: usize, boundary: Boundary) -> i64 {
+        weights.iter().filter_map(|&(offset, weight)| {
+            grid.get_neighbor_coord(x, y, offset, boundary).map(|(nx, ny)| weight * (grid.get_cell(nx, ny) == ALIVE) as i64)
+        }).sum()
+    }
+
+    #[test]
+    fn test_kernel_sums_match_brute_force() {
+        let grid = Grid::new(13, 11, |x, y| ((x * 7 + y * 13 + x * y) % 5 < 2) as u8);
+        // A ring of weight 2 around a center of weight -1, with a lopsided arm.
+        let weights = vec![((0, 0), -1), ((-1, -1), 2), ((0, -1), 2), ((1, -1), 2), ((-1, 0), 2), ((1, 0), 2), ((-1, 1), 2), ((0, 1), 2), ((1, 1), 2), ((3, 0), 5), ((3, 1), 5)];
+        let diamond: Vec<((isize, isize), i64)> = (-3..=3isize).flat_map(|dy| (-3..=3isize).map(move |dx| ((dx, dy), 1))).filter(|&((dx, dy), _)| dx.abs() + dy.abs() <= 3).collect();
+
+        for boundary in [Boundary::Bounded, Boundary::Wrapping] {
+            for (kernel, weights) in [(Kernel::from_weights(&weights), &weights), (Kernel::von_neumann(3, true), &diamond)] {
+                let sums = kernel.sums(&grid, boundary, |state| state == ALIVE);
+                for y in 0..11 {
+                    for x in 0..13 {
+                        assert_eq!(sums.get_cell(x, y), brute_force_sum(&grid, weights, x, y, boundary));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_kernels_merge_into_rects() {
+        assert_eq!(Kernel::moore(20, true).get_n_rects(), 1);
+        assert_eq!(Kernel::moore(5, false).get_n_rects(), 4);
+        assert_eq!(Kernel::von_neumann(5, true).get_n_rects(), 11);
+    }
+
+    #[test]
+    fn test_range_one_matches_life() {
+        let rule = LtlRule::parse("R1,C0,M0,S2..3,B3..3,NM").unwrap();
+        let mut ltl = LargerThanLife::new(10, 10, rule, Boundary::Wrapping, fixtures::glider);
+
+        fixtures::assert_glider_matches_life(10, 40, || {
+            ltl.advance();
+            ltl.get_grid().clone()
+        });
+    }
+
+    #[test]
+    fn test_parse_and_dying_states() {
+        let bosco = LtlRule::bosco();
+        assert_eq!(bosco.kernel.get_radius(), 5);
+        assert_eq!((bosco.birth[0].clone(), bosco.survival[0].clone()), (34..=45, 34..=58));
+        assert!(LtlRule::parse("R5,C0,M1,S34..58,B34..45,NX").is_none());
+        assert!(LtlRule::parse("R5,C0,S34..58,B34..45").is_none());
+        assert!(LtlRule::parse("R0,C0,M1,S1..2,B1..2").is_none());
+
+        let rule = LtlRule::parse("R1,C4,M0,S8..8,B9..9,NN").unwrap();
+        let mut ltl = LargerThanLife::new(3, 3, rule, Boundary::Bounded, |x, y| ((x, y) == (1, 1)) as u8);
+        let mut states = vec![];
+        for _ in 0..3 {
+            ltl.advance();
+            states.push(ltl.get_cell(1, 1));
+        }
+        assert_eq!(states, vec![2, 3, DEAD]);
+    }
+}
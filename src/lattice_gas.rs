@@ -0,0 +1,254 @@
+use std::cell::RefCell;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::environment::{AdvanceCellF, Environment};
+use crate::grid::{hex_offsets_for_row, Boundary, Grid, VON_NEUMANN_OFFSETS};
+
+/// Marks a cell as a solid obstacle. Particles that enter an obstacle are reflected straight back.
+pub const OBSTACLE: u8 = 0b1000_0000;
+
+/// Each cell holds a bitmask of the particles in it, with one bit per direction of travel. No two particles in the
+/// same cell can move in the same direction.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Lattice {
+    /// HPP: four directions on the square grid, ordered E, N, W, S.
+    Square,
+    /// FHP: six directions on the hexagonal grid, ordered E, NE, NW, W, SW, SE.
+    Hexagonal,
+}
+
+// E, N, W, S, in the same order as the particle bits of the square lattice.
+const SQUARE_OFFSETS: [(isize, isize); 4] = [VON_NEUMANN_OFFSETS[2], VON_NEUMANN_OFFSETS[0], VON_NEUMANN_OFFSETS[1], VON_NEUMANN_OFFSETS[3]];
+
+impl Lattice {
+    pub fn n_directions(self) -> usize {
+        match self {
+            Lattice::Square => 4,
+            Lattice::Hexagonal => 6,
+        }
+    }
+
+    fn all_particles(self) -> u8 {
+        (1 << self.n_directions()) - 1
+    }
+
+    fn neighbor_offset(self, y: usize, direction: usize) -> (isize, isize) {
+        match self {
+            Lattice::Square => SQUARE_OFFSETS[direction],
+            Lattice::Hexagonal => hex_offsets_for_row(y)[direction],
+        }
+    }
+
+    /// The unit vector of a direction, with y pointing down to match the grid.
+    pub fn direction_vector(self, direction: usize) -> (f32, f32) {
+        let angle = std::f32::consts::TAU * direction as f32 / self.n_directions() as f32;
+        (angle.cos(), -angle.sin())
+    }
+
+    fn rotate(self, particles: u8, steps: usize) -> u8 {
+        let n = self.n_directions();
+        let steps = steps % n;
+        if steps == 0 {
+            return particles;
+        }
+
+        ((particles << steps) | (particles >> (n - steps))) & self.all_particles()
+    }
+
+    /// Resolves collisions within a single cell. Momentum and particle count are always conserved.
+    /// For FHP, head-on pairs scatter either clockwise or counterclockwise, based on chirality.
+    fn collide(self, cell: u8, chirality: bool) -> u8 {
+        let particles = cell & self.all_particles();
+
+        if cell & OBSTACLE != 0 {
+            return OBSTACLE | self.rotate(particles, self.n_directions() / 2);
+        }
+
+        match self {
+            Lattice::Square => match particles {
+                0b0101 => 0b1010,
+                0b1010 => 0b0101,
+                _ => particles,
+            },
+            Lattice::Hexagonal => {
+                let is_head_on_pair = (0..3).any(|direction| particles == (1 << direction) | (1 << (direction + 3)));
+                let is_symmetric_triple = particles == 0b010101 || particles == 0b101010;
+
+                if is_head_on_pair {
+                    self.rotate(particles, if chirality { 1 } else { 5 })
+                } else if is_symmetric_triple {
+                    self.rotate(particles, 1)
+                } else {
+                    particles
+                }
+            },
+        }
+    }
+}
+
+fn collision_phase(lattice: Lattice, seed: u64) -> Box<AdvanceCellF<u8>> {
+    let rng = RefCell::new(StdRng::seed_from_u64(seed));
+
+    Box::new(move |grid, x, y| {
+        let chirality = lattice == Lattice::Hexagonal && rng.borrow_mut().random_bool(0.5);
+        lattice.collide(grid.get_cell(x, y), chirality)
+    })
+}
+
+// Streaming is written as a gather, so it fits the per-cell update: the particle moving in a direction comes from
+// the neighbor in the opposite direction.
+fn streaming_phase(lattice: Lattice) -> Box<AdvanceCellF<u8>> {
+    Box::new(move |grid, x, y| {
+        let n = lattice.n_directions();
+
+        (0..n).fold(grid.get_cell(x, y) & OBSTACLE, |cell, direction| {
+            let offset = lattice.neighbor_offset(y, (direction + n / 2) % n);
+            let (source_x, source_y) = grid.get_neighbor_coord(x, y, offset, Boundary::Wrapping).unwrap();
+
+            cell | (grid.get_cell(source_x, source_y) & (1 << direction))
+        })
+    })
+}
+
+/// A lattice gas on a wrapping grid. Every generation has a collision phase, followed by a streaming phase.
+/// The hexagonal lattice needs an even height, so that row parity is preserved when wrapping.
+pub fn new_lattice_gas(width: usize, height: usize, lattice: Lattice, seed: u64, initial_cell_producer: impl Fn(usize, usize) -> u8) -> Environment<u8> {
+    assert!(lattice == Lattice::Square || height.is_multiple_of(2), "Hexagonal lattices must have an even height. Got {}", height);
+
+    Environment::new_phased(width, height, initial_cell_producer, vec![
+        collision_phase(lattice, seed),
+        streaming_phase(lattice),
+    ])
+}
+
+pub fn count_particles(grid: &Grid<u8>, lattice: Lattice) -> usize {
+    (0..grid.get_height())
+        .flat_map(|y| (0..grid.get_width()).map(move |x| (x, y)))
+        .map(|(x, y)| (grid.get_cell(x, y) & lattice.all_particles()).count_ones() as usize)
+        .sum()
+}
+
+pub fn cell_momentum(cell: u8, lattice: Lattice) -> (f32, f32) {
+    (0..lattice.n_directions())
+        .filter(|&direction| cell & (1 << direction) != 0)
+        .map(|direction| lattice.direction_vector(direction))
+        .fold((0.0, 0.0), |(sum_x, sum_y), (dx, dy)| (sum_x + dx, sum_y + dy))
+}
+
+/// Averages the momentum of the particles over square blocks of cells, which smooths out the noise of individual
+/// particles into a velocity field. Obstacle cells are left out of the average. Indexed by [block_y][block_x].
+pub fn average_velocity_field(grid: &Grid<u8>, lattice: Lattice, block_size: usize) -> Vec<Vec<(f32, f32)>> {
+    let blocks_wide = grid.get_width().div_ceil(block_size);
+    let blocks_high = grid.get_height().div_ceil(block_size);
+
+    (0..blocks_high).map(|block_y| {
+        (0..blocks_wide).map(|block_x| {
+            let mut sum = (0.0, 0.0);
+            let mut n_cells = 0;
+
+            for y in block_y * block_size..((block_y + 1) * block_size).min(grid.get_height()) {
+                for x in block_x * block_size..((block_x + 1) * block_size).min(grid.get_width()) {
+                    let cell = grid.get_cell(x, y);
+                    if cell & OBSTACLE == 0 {
+                        let (momentum_x, momentum_y) = cell_momentum(cell, lattice);
+                        sum = (sum.0 + momentum_x, sum.1 + momentum_y);
+                        n_cells += 1;
+                    }
+                }
+            }
+
+            if n_cells == 0 { (0.0, 0.0) } else { (sum.0 / n_cells as f32, sum.1 / n_cells as f32) }
+        }).collect()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAST: u8 = 1 << 0;
+    const NORTH: u8 = 1 << 1;
+    const WEST: u8 = 1 << 2;
+    const SOUTH: u8 = 1 << 3;
+
+    fn total_momentum(grid: &Grid<u8>, lattice: Lattice) -> (f32, f32) {
+        (0..grid.get_height())
+            .flat_map(|y| (0..grid.get_width()).map(move |x| (x, y)))
+            .map(|(x, y)| cell_momentum(grid.get_cell(x, y), lattice))
+            .fold((0.0, 0.0), |sum, momentum| (sum.0 + momentum.0, sum.1 + momentum.1))
+    }
+
+    #[test]
+    fn test_hpp_particle_streams() {
+        let mut env = new_lattice_gas(5, 5, Lattice::Square, 0, |_x, _y| 0);
+        env.bulk_set_readable(vec![(2, 2, EAST), (1, 1, SOUTH)]);
+
+        env.advance();
+
+        assert_eq!(env.get_cell(3, 2), EAST);
+        assert_eq!(env.get_cell(1, 2), SOUTH);
+        assert_eq!(count_particles(env.get_grid(), Lattice::Square), 2);
+    }
+
+    #[test]
+    fn test_hpp_head_on_collision() {
+        let mut env = new_lattice_gas(5, 5, Lattice::Square, 0, |_x, _y| 0);
+        env.bulk_set_readable(vec![(2, 2, EAST | WEST)]);
+
+        env.advance();
+
+        assert_eq!(env.get_cell(2, 1), NORTH);
+        assert_eq!(env.get_cell(2, 3), SOUTH);
+        assert_eq!(env.get_cell(2, 2), 0);
+    }
+
+    #[test]
+    fn test_obstacle_bounces_back() {
+        let mut env = new_lattice_gas(5, 1, Lattice::Square, 0, |_x, _y| 0);
+        env.bulk_set_readable(vec![(1, 0, EAST), (2, 0, OBSTACLE)]);
+
+        env.advance();
+        assert_eq!(env.get_cell(2, 0), OBSTACLE | EAST);
+
+        env.advance();
+        assert_eq!(env.get_cell(1, 0), WEST);
+        assert_eq!(env.get_cell(2, 0), OBSTACLE);
+    }
+
+    #[test]
+    fn test_fhp_head_on_pair_rotates() {
+        assert_eq!(Lattice::Hexagonal.collide(0b001001, true), 0b010010);
+        assert_eq!(Lattice::Hexagonal.collide(0b001001, false), 0b100100);
+
+        assert_eq!(Lattice::Hexagonal.collide(0b010101, false), 0b101010);
+        assert_eq!(Lattice::Hexagonal.collide(0b000011, true), 0b000011);
+    }
+
+    #[test]
+    fn test_fhp_conserves_particles_and_momentum() {
+        let mut rng = StdRng::seed_from_u64(31);
+        let soup: Vec<u8> = (0..16 * 12).map(|_| rng.random_range(0..64)).collect();
+        let mut env = new_lattice_gas(16, 12, Lattice::Hexagonal, 31, |x, y| soup[y * 16 + x]);
+        let initial_particles = count_particles(env.get_grid(), Lattice::Hexagonal);
+        let initial_momentum = total_momentum(env.get_grid(), Lattice::Hexagonal);
+
+        for _ in 0..30 {
+            env.advance();
+        }
+
+        let momentum = total_momentum(env.get_grid(), Lattice::Hexagonal);
+        assert_eq!(count_particles(env.get_grid(), Lattice::Hexagonal), initial_particles);
+        assert!((momentum.0 - initial_momentum.0).abs() < 1e-3);
+        assert!((momentum.1 - initial_momentum.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_average_velocity_field() {
+        let grid = Grid::new(4, 2, |x, _y| if x < 2 { EAST } else { OBSTACLE | WEST });
+        let field = average_velocity_field(&grid, Lattice::Square, 2);
+
+        assert_eq!(field.len(), 1);
+        assert!((field[0][0].0 - 1.0).abs() < 1e-6);
+        assert_eq!(field[0][1], (0.0, 0.0));
+    }
+}
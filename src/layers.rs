@@ -0,0 +1,139 @@
+use std::mem::swap;
+use crate::grid::Grid;
+
+/// Computes the next state of one layer's cell from every layer of the current generation.
+pub type LayerRuleF<T> = dyn Fn(&[Grid<T>], usize, usize) -> T;
+
+/// A double-buffered environment of stacked layers, all the same size, like a static terrain under a dynamic layer. A
+/// layer can have a rule, which reads every layer and writes only its own. All rules see the same generation, so the
+/// order they were set in doesn't matter. Layers without a rule never change on their own.
+pub struct LayeredEnvironment<T> {
+    width: usize,
+    height: usize,
+    names: Vec<String>,
+    read_layers: Vec<Grid<T>>,
+    write_layers: Vec<Grid<T>>,
+    rules: Vec<Option<Box<LayerRuleF<T>>>>,
+}
+
+impl<T: Copy> LayeredEnvironment<T> {
+    pub fn new(width: usize, height: usize) -> LayeredEnvironment<T> {
+        LayeredEnvironment {
+            width,
+            height,
+            names: vec![],
+            read_layers: vec![],
+            write_layers: vec![],
+            rules: vec![],
+        }
+    }
+
+    /// Adds a layer on top of the others, without a rule, and returns its index.
+    pub fn add_layer(&mut self, name: &str, initial_cell_producer: impl Fn(usize, usize) -> T) -> usize {
+        self.names.push(name.to_string());
+        self.read_layers.push(Grid::new(self.width, self.height, &initial_cell_producer));
+        self.write_layers.push(Grid::new(self.width, self.height, &initial_cell_producer));
+        self.rules.push(None);
+
+        self.read_layers.len() - 1
+    }
+
+    pub fn set_rule(&mut self, layer: usize, rule: Box<LayerRuleF<T>>) {
+        self.rules[layer] = Some(rule);
+    }
+
+    pub fn get_layer_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|layer_name| layer_name == name)
+    }
+
+    pub fn get_layer_name(&self, layer: usize) -> &str {
+        &self.names[layer]
+    }
+
+    pub fn get_n_layers(&self) -> usize {
+        self.read_layers.len()
+    }
+
+    pub fn get_layer(&self, layer: usize) -> &Grid<T> {
+        &self.read_layers[layer]
+    }
+
+    pub fn get_cell(&self, layer: usize, x: usize, y: usize) -> T {
+        self.read_layers[layer].get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn bulk_set_readable(&mut self, layer: usize, cells: Vec<(usize, usize, T)>) {
+        for (x, y, cell) in cells {
+            if x < self.width && y < self.height {
+                self.read_layers[layer].set_cell(x, y, cell);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, self.width, self.height);
+            }
+        }
+    }
+
+    pub fn advance(&mut self) {
+        for (layer, rule) in self.rules.iter().enumerate() {
+            let Some(rule) = rule else { continue };
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let next_cell = rule(&self.read_layers, x, y);
+                    self.write_layers[layer].set_cell(x, y, next_cell);
+                }
+            }
+        }
+
+        for (layer, rule) in self.rules.iter().enumerate() {
+            if rule.is_some() {
+                swap(&mut self.read_layers[layer], &mut self.write_layers[layer]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway;
+    use crate::grid::Boundary;
+
+    #[test]
+    fn test_rule_reads_static_terrain() {
+        let mut env = LayeredEnvironment::new(6, 5);
+        // Life can't be born on rock, which covers the right half.
+        let terrain = env.add_layer("terrain", |x, _y| (x >= 3) as u8);
+        let life = env.add_layer("life", |_x, _y| 0);
+        env.set_rule(life, Box::new(move |layers, x, y| {
+            let next = conway::advance_cell(&layers[life], x, y, Boundary::Bounded);
+            if layers[terrain].get_cell(x, y) == 1 && layers[life].get_cell(x, y) == 0 { 0 } else { next }
+        }));
+        // A blinker whose vertical phase would stick out onto the rock.
+        env.bulk_set_readable(life, vec![(1, 2, 1), (2, 2, 1), (3, 2, 1)]);
+
+        env.advance();
+
+        let row: Vec<u8> = (0..6).map(|x| env.get_cell(life, x, 2)).collect();
+        assert_eq!(row, vec![0, 0, 1, 0, 0, 0]);
+        assert_eq!((env.get_cell(life, 2, 1), env.get_cell(life, 2, 3)), (1, 1));
+        assert_eq!(env.get_layer(terrain), &Grid::new(6, 5, |x, _y| (x >= 3) as u8));
+        assert_eq!(env.get_layer_index("life"), Some(life));
+    }
+
+    #[test]
+    fn test_rules_see_the_same_generation() {
+        // Two layers that copy each other swap every generation, which only works if neither sees the other's writes.
+        let mut env = LayeredEnvironment::new(2, 1);
+        let a = env.add_layer("a", |x, _y| x as u8);
+        let b = env.add_layer("b", |x, _y| 10 + x as u8);
+        env.set_rule(a, Box::new(move |layers, x, y| layers[b].get_cell(x, y)));
+        env.set_rule(b, Box::new(move |layers, x, y| layers[a].get_cell(x, y)));
+
+        env.advance();
+
+        assert_eq!((env.get_cell(a, 1, 0), env.get_cell(b, 1, 0)), (11, 1));
+    }
+}
@@ -0,0 +1,21 @@
+pub mod grid;
+pub mod environment;
+pub mod rle;
+pub mod cell_types;
+pub mod wireworld;
+pub mod margolus;
+pub mod turmites;
+pub mod sandpile;
+pub mod falling_sand;
+pub mod lattice_gas;
+pub mod traffic;
+pub mod wator;
+pub mod schelling;
+pub mod ising;
+pub mod larger_than_life;
+pub mod layers;
+pub mod memory;
+pub mod schedule;
+pub mod coupling;
+pub mod observers;
+pub mod ui;
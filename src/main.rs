@@ -1,14 +1,285 @@
+use cellular_automata::coupling::{CoupledEnvironments, Edge, Side};
+use cellular_automata::environment::Environment;
+use cellular_automata::falling_sand::{FallingSand, Material};
+use cellular_automata::grid::{Boundary, Grid};
+use cellular_automata::ising::{Dynamics, Ising, CRITICAL_TEMPERATURE};
+use cellular_automata::larger_than_life::{LargerThanLife, LtlRule};
+use cellular_automata::lattice_gas::{new_lattice_gas, Lattice, OBSTACLE};
+use cellular_automata::sandpile::Sandpile;
+use cellular_automata::schedule::{InitialCells, RuleSchedule, RunDescription};
+use cellular_automata::schelling::Schelling;
+use cellular_automata::turmites::{Direction, Turmite, TurmiteRule};
+use cellular_automata::cell_types::conway;
+use cellular_automata::cell_types::cyclic::Cyclic;
+use cellular_automata::cell_types::epidemic::{Epidemic, EpidemicRun};
+use cellular_automata::cell_types::greenberg_hastings::GreenbergHastings;
+use cellular_automata::cell_types::golly_rule::GollyRule;
+use cellular_automata::cell_types::isotropic::LookupRule;
+use cellular_automata::cell_types::loops::LoopColony;
+use cellular_automata::cell_types::multicolor::ColorLife;
+use cellular_automata::cell_types::wireworld::{wireworld_rule, CONDUCTOR, ELECTRON_HEAD, ELECTRON_TAIL, EMPTY};
+use cellular_automata::cell_types::rule_table::{RuleTable, Symmetry};
+use cellular_automata::cell_types::von_neumann::{self, confluent, transmission, EAST, NORTH, SOUTH, WEST};
+use cellular_automata::traffic::{BihamMiddletonLevine, NagelSchreckenberg};
+use cellular_automata::wator::{Species, Wator, WatorParameters};
+use cellular_automata::layers::LayeredEnvironment;
+use cellular_automata::memory::MemoryWeighting;
+use cellular_automata::ui::egui::{generate_palette, golly_palette, FallingSandCanvas, LatticeGasCanvas, CoupledCanvas, LayerStyle, LayeredCanvas, MemoryComparisonCanvas, PaletteEnvironment, TrafficSpaceTimeCanvas};
+use eframe::egui::Color32;
 use rand::Rng;
-use crate::wireworld::grid::CellType;
+use cellular_automata::wireworld;
+use cellular_automata::wireworld::grid::CellType;
 use std::time::Instant;
 
-mod environment;
-mod grid;
-mod cell_types;
-mod wireworld;
+fn start_turmites() -> eframe::Result {
+    let size = 80;
+    let rllr = TurmiteRule::from_ant_string("RLLR").unwrap();
 
-fn main() {
+    let mut env = Environment::new_static(size, size, |_x, _y| 0);
+    env.add_agent(Box::new(Turmite::new(size / 3, size / 2, Direction::North, rllr.clone(), Boundary::Wrapping)));
+    env.add_agent(Box::new(Turmite::new(2 * size / 3, size / 2, Direction::South, rllr.clone(), Boundary::Wrapping)));
+
+    cellular_automata::ui::egui::start_gui("Turmites", PaletteEnvironment { env, palette: generate_palette(rllr.get_n_colors(), true) })
+}
+
+fn start_sandpile() -> eframe::Result {
+    cellular_automata::ui::egui::start_gui("Sandpile identity", Sandpile::identity(100, 100))
+}
+
+fn start_falling_sand() -> eframe::Result {
+    let canvas = FallingSandCanvas { world: FallingSand::new(120, 120, 0), brush: Material::Sand };
+    cellular_automata::ui::egui::start_gui("Falling sand", canvas)
+}
+
+fn start_lattice_gas() -> eframe::Result {
+    // A rightward flow around a square obstacle.
+    let env = new_lattice_gas(160, 160, Lattice::Hexagonal, 0, |x, y| {
+        if (70..90).contains(&x) && (70..90).contains(&y) { OBSTACLE } else if (x + y) % 2 == 0 { 0b000111 } else { 0b100011 }
+    });
+    cellular_automata::ui::egui::start_gui("FHP lattice gas", LatticeGasCanvas::new(env, Lattice::Hexagonal, 8))
+}
+
+fn start_greenberg_hastings() -> eframe::Result {
+    let config = GreenbergHastings::thick_spirals();
+    let env = Environment::new(150, 150, config.broken_wave_seed(150, 150), config.rule());
+    let palette = generate_palette(config.n_states(), true);
+    cellular_automata::ui::egui::start_gui("Greenberg-Hastings", PaletteEnvironment { env, palette })
+}
+
+fn start_cyclic() -> eframe::Result {
+    let preset_name = std::env::args().nth(2).unwrap_or(String::from("313"));
+    let config = Cyclic::preset(&preset_name).expect("Unknown cyclic preset");
+    let mut rng = rand::rng();
+    let soup: Vec<u8> = (0..150 * 150).map(|_| rng.random_range(0..config.n_colors)).collect();
+    let env = Environment::new(150, 150, |x, y| soup[y * 150 + x], config.rule());
+    let palette = generate_palette(config.n_colors as usize, false);
+    cellular_automata::ui::egui::start_gui("Cyclic CA", PaletteEnvironment { env, palette })
+}
+
+fn start_color_life() -> eframe::Result {
+    let preset_name = std::env::args().nth(2).unwrap_or(String::from("QuadLife"));
+    let config = ColorLife::preset(&preset_name).expect("Unknown multi-color Life preset");
+    let mut rng = rand::rng();
+    // Each color starts in its own vertical band, so they compete for territory where the bands meet.
+    let soup: Vec<bool> = (0..150 * 150).map(|_| rng.random_bool(0.35)).collect();
+    let band_width = 150 / config.n_colors as usize + 1;
+    let env = Environment::new(150, 150, |x, y| if soup[y * 150 + x] { (x / band_width) as u8 + 1 } else { 0 }, config.rule());
+    let palette = generate_palette(config.n_colors as usize + 1, true);
+    cellular_automata::ui::egui::start_gui(&preset_name, PaletteEnvironment { env, palette })
+}
+
+fn start_epidemic() -> eframe::Result {
+    let mut rng = rand::rng();
+    let vaccinated: Vec<bool> = (0..150 * 150).map(|_| rng.random_bool(0.3)).collect();
+    let mask = Grid::new(150, 150, |x, y| vaccinated[y * 150 + x] && (x, y) != (75, 75));
+    let run = EpidemicRun::new(Epidemic::seir(0.15, 3, 6), &mask, &[(75, 75)], 0);
+    cellular_automata::ui::egui::start_gui("SEIR epidemic", run)
+}
+
+fn start_nagel_schreckenberg() -> eframe::Result {
+    let road = NagelSchreckenberg::new_random(200, 0.2, 5, 0.25, 0);
+    cellular_automata::ui::egui::start_gui("Nagel-Schreckenberg", TrafficSpaceTimeCanvas::new(road, 5, 200))
+}
+
+fn start_biham_middleton_levine() -> eframe::Result {
+    cellular_automata::ui::egui::start_gui("Biham-Middleton-Levine", BihamMiddletonLevine::new_random(128, 128, 0.32, 0))
+}
+
+fn start_wator() -> eframe::Result {
+    let parameters = WatorParameters { fish_breed_time: 3, shark_breed_time: 10, shark_starve_time: 3 };
+    let mut rng = rand::rng();
+    let ocean: Vec<Option<Species>> = (0..150 * 150).map(|_| match rng.random_range(0..100) {
+        0..5 => Some(Species::Shark),
+        5..35 => Some(Species::Fish),
+        _ => None,
+    }).collect();
+    cellular_automata::ui::egui::start_gui("Wa-Tor", Wator::new(150, 150, parameters, |x, y| ocean[y * 150 + x], 0))
+}
+
+fn start_schelling() -> eframe::Result {
+    cellular_automata::ui::egui::start_gui("Schelling segregation", Schelling::new_random(120, 120, 2, 0.1, 0.5, 0))
+}
+
+fn start_ising() -> eframe::Result {
+    cellular_automata::ui::egui::start_gui("Ising model", Ising::new_random(200, 200, CRITICAL_TEMPERATURE, Dynamics::Metropolis, 0))
+}
+
+fn start_von_neumann() -> eframe::Result {
+    let env = match std::env::args().nth(2) {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).expect("Couldn't read the pattern file");
+            von_neumann::load_rle(&text, 50).expect("Not a JvN29 or Nobili32 pattern")
+        },
+        None => {
+            // A pulser loop whose confluent cell also feeds a construction arm, which keeps extending itself east.
+            let mut env = Environment::new(150, 20, |_x, _y| von_neumann::GROUND, von_neumann::von_neumann_rule());
+            env.bulk_set_readable(vec![
+                (2, 8, transmission(false, EAST, true)),
+                (3, 8, transmission(false, EAST, false)),
+                (4, 8, confluent(false, false)),
+                (4, 9, transmission(false, SOUTH, false)),
+                (4, 10, transmission(false, WEST, false)),
+                (3, 10, transmission(false, WEST, false)),
+                (2, 10, transmission(false, NORTH, false)),
+                (2, 9, transmission(false, NORTH, false)),
+                (5, 8, transmission(false, EAST, false)),
+            ]);
+            env
+        },
+    };
+
+    let palette = generate_palette(von_neumann::NOBILI_N_STATES, true);
+    cellular_automata::ui::egui::start_gui("von Neumann 29-state", PaletteEnvironment { env, palette })
+}
 
+fn start_loops() -> eframe::Result {
+    let usage = "Usage: loops [preset] | loops <table in Langton's format> <pattern.rle> [sheath state]";
+    let first = std::env::args().nth(2).unwrap_or(String::from("Langton"));
+    if let Some(colony) = LoopColony::preset(&first, 50) {
+        return cellular_automata::ui::egui::start_gui(&format!("{} loops", first), colony);
+    }
+
+    let table_text = std::fs::read_to_string(first).expect("Neither a preset nor a readable table file");
+    let pattern_text = std::fs::read_to_string(std::env::args().nth(3).expect(usage)).expect("Couldn't read the pattern file");
+    let sheath_state = std::env::args().nth(4).map_or(2, |state| state.parse().expect(usage));
+
+    // One digit per state, so this covers every table in the format, up to Evoloops' 9 states.
+    let table = RuleTable::from_langton_format(&table_text, 10, Symmetry::Rotate4).expect("Malformed rule table");
+    let pattern = cellular_automata::rle::parse_rle(&pattern_text).expect("Malformed pattern");
+    let colony = LoopColony::new(&table, &pattern.to_grid(100), sheath_state);
+    cellular_automata::ui::egui::start_gui("Self-replicating loops", colony)
+}
+
+fn start_golly_rule() -> eframe::Result {
+    let usage = "Usage: golly <file.rule> <pattern.rle>";
+    let rule_text = std::fs::read_to_string(std::env::args().nth(2).expect(usage)).expect("Couldn't read the rule file");
+    let pattern_text = std::fs::read_to_string(std::env::args().nth(3).expect(usage)).expect("Couldn't read the pattern file");
+
+    let rule = GollyRule::parse(&rule_text).expect("Unsupported or malformed rule file");
+    let grid = cellular_automata::rle::parse_rle(&pattern_text).expect("Malformed pattern").to_grid(100);
+    let env = Environment::new(grid.get_width(), grid.get_height(), |x, y| grid.get_cell(x, y), rule.rule(Boundary::Bounded));
+    cellular_automata::ui::egui::start_gui(rule.get_name(), PaletteEnvironment { env, palette: golly_palette(&rule) })
+}
+
+fn start_lookup_rule() -> eframe::Result {
+    let rule_string = std::env::args().nth(2).unwrap_or(String::from("B2-a/S12"));
+    let rule = LookupRule::parse(&rule_string).expect("Not a Hensel or MAP rule");
+    let mut rng = rand::rng();
+    let soup: Vec<bool> = (0..150 * 150).map(|_| rng.random_bool(0.3)).collect();
+    // A random soup in the middle, with room around it for anything that escapes.
+    let mut env = Environment::new(150, 150, |x, y| ((50..100).contains(&x) && (50..100).contains(&y) && soup[y * 150 + x]) as u8, rule.rule(Boundary::Wrapping));
+    env.track_ages();
+    cellular_automata::ui::egui::start_gui(&rule_string, PaletteEnvironment { env, palette: generate_palette(2, true) })
+}
+
+fn start_larger_than_life() -> eframe::Result {
+    let rule_string = std::env::args().nth(2).unwrap_or(String::from("R5,C0,M1,S34..58,B34..45,NM"));
+    let rule = LtlRule::parse(&rule_string).expect("Not a Larger than Life rule");
+    let mut rng = rand::rng();
+    let soup: Vec<bool> = (0..200 * 200).map(|_| rng.random_bool(0.5)).collect();
+    let ltl = LargerThanLife::new(200, 200, rule, Boundary::Wrapping, |x, y| ((50..150).contains(&x) && (50..150).contains(&y) && soup[y * 200 + x]) as u8);
+    cellular_automata::ui::egui::start_gui(&rule_string, ltl)
+}
+
+fn start_hybrid() -> eframe::Result {
+    let rules = vec![wireworld_rule(Boundary::Bounded), LookupRule::parse("B3/S23").unwrap().rule(Boundary::Bounded)];
+    // Three parallel wires from a loop clock run into a Life region, where the electrons arriving together seed growth.
+    let wire = |x: usize, y: usize| {
+        let clock = ((x == 4 || x == 9) && (36..=44).contains(&y)) || ((y == 36 || y == 44) && (4..=9).contains(&x));
+        let wires = ((y == 38 || y == 40 || y == 42) && (10..60).contains(&x)) || (x == 10 && (38..=42).contains(&y));
+        clock || wires
+    };
+    let mut env = Environment::new_non_uniform(150, 80, |x, y| if wire(x, y) { CONDUCTOR } else { EMPTY }, rules, |x, _y| (x >= 60) as usize);
+    env.bulk_set_readable(vec![(4, 40, ELECTRON_HEAD), (4, 41, ELECTRON_TAIL)]);
+    cellular_automata::ui::egui::start_gui("WireWorld feeding Life", PaletteEnvironment { env, palette: generate_palette(4, true) })
+}
+
+fn start_schedule() -> eframe::Result {
+    let description = match std::env::args().nth(2) {
+        Some(path) => RunDescription::parse(&std::fs::read_to_string(path).expect("Couldn't read the run description")).expect("Not a run description"),
+        None => {
+            let soup = InitialCells::Soup { seed: rand::rng().random(), density: 0.3 };
+            RunDescription::new(150, 150, soup, "life-like", RuleSchedule::cycling(&["B3/S23", "B36/S23"], 1).unwrap()).unwrap()
+        },
+    };
+    assert_eq!(description.get_family(), "life-like", "Only life-like runs can be shown");
+    let run = description.start(Box::new(|rule, _parameters, _generation| {
+        LookupRule::parse(rule).map(|rule| rule.rule(Boundary::Wrapping))
+    })).expect("The schedule has a rule that isn't a Hensel or MAP rule");
+    cellular_automata::ui::egui::start_gui("Rule schedule", run)
+}
+
+fn start_memory() -> eframe::Result {
+    let depth = std::env::args().nth(2).map_or(3, |depth| depth.parse().expect("The depth should be a number"));
+    let decay = std::env::args().nth(3).map_or(1.0, |decay| decay.parse().expect("The decay should be a number"));
+    let mut rng = rand::rng();
+    let soup: Vec<bool> = (0..100 * 100).map(|_| rng.random_bool(0.3)).collect();
+    let canvas = MemoryComparisonCanvas::new(100, 100, |x, y| soup[y * 100 + x] as u8, || conway::life_rule(Boundary::Wrapping), MemoryWeighting::geometric(depth, decay));
+    cellular_automata::ui::egui::start_gui("Life with and without memory", canvas)
+}
+
+fn start_layers() -> eframe::Result {
+    let mut rng = rand::rng();
+    let soup: Vec<bool> = (0..150 * 150).map(|_| rng.random_bool(0.3)).collect();
+    let mut env = LayeredEnvironment::new(150, 150);
+    // Islands of fertile ground in barren rock. Life only takes hold where it's fertile, but survives spilling out.
+    let terrain = env.add_layer("terrain", |x, y| {
+        let (dx, dy) = (x as f64 - 75.0, y as f64 - 75.0);
+        ((dx * 0.08).sin() + (dy * 0.08).cos() > 0.3) as u8
+    });
+    let life = env.add_layer("life", |x, y| soup[y * 150 + x] as u8);
+    env.set_rule(life, Box::new(move |layers, x, y| {
+        let next = conway::advance_cell(&layers[life], x, y, Boundary::Wrapping);
+        if layers[terrain].get_cell(x, y) == 0 && layers[life].get_cell(x, y) == 0 { 0 } else { next }
+    }));
+
+    let styles = vec![
+        LayerStyle { palette: generate_palette(2, false), visible: true, opacity: 0.4 },
+        LayerStyle { palette: vec![Color32::TRANSPARENT, Color32::WHITE], visible: true, opacity: 1.0 },
+    ];
+    cellular_automata::ui::egui::start_gui("Life on terrain", LayeredCanvas { env, styles })
+}
+
+fn start_coupled_boards() -> eframe::Result {
+    // A clock board driving two wire boards, each wired to one of its ports, with gaps between the boards on screen.
+    let mut boards = CoupledEnvironments::new(EMPTY);
+    let clock = boards.add_board(8, 9, |x, y| {
+        let ring = ((x == 1 || x == 6) && (1..=7).contains(&y)) || ((y == 1 || y == 7) && (1..=6).contains(&x));
+        let ports = (y == 2 || y == 6) && x == 7;
+        if ring || ports { CONDUCTOR } else { EMPTY }
+    }, wireworld_rule(Boundary::Bounded));
+    let wire = |_x: usize, y: usize| if y == 1 { CONDUCTOR } else { EMPTY };
+    let upper = boards.add_board(40, 3, wire, wireworld_rule(Boundary::Bounded));
+    let lower = boards.add_board(20, 3, wire, wireworld_rule(Boundary::Bounded));
+    boards.connect(Edge { board: clock, side: Side::East, start: 1 }, Edge { board: upper, side: Side::West, start: 0 }, 3);
+    boards.connect(Edge { board: clock, side: Side::East, start: 5 }, Edge { board: lower, side: Side::West, start: 0 }, 3);
+    boards.bulk_set_readable(clock, vec![(1, 4, ELECTRON_HEAD), (1, 5, ELECTRON_TAIL)]);
+
+    let canvas = CoupledCanvas { boards, positions: vec![(0, 0), (10, 0), (10, 6)], palette: generate_palette(4, true) };
+    cellular_automata::ui::egui::start_gui("Coupled WireWorld boards", canvas)
+}
+
+fn start_wireworld() -> eframe::Result {
     let width = 20;
     let height = 20;
 
@@ -46,8 +317,41 @@ fn main() {
 
     let start_time = Instant::now();
 
-    wireworld::ui::egui::start_gui(env).unwrap();
+    let result = wireworld::ui::egui::start_gui(env);
 
     let duration = start_time.elapsed();
     println!("Time elapsed in main_loop() is: {:?}", duration);  // 16.7 seconds for 0.5 chance
+
+    result
+}
+
+fn main() {
+    let result = match std::env::args().nth(1).as_deref() {
+        Some("ant") => start_turmites(),
+        Some("sandpile") => start_sandpile(),
+        Some("sand") => start_falling_sand(),
+        Some("fhp") => start_lattice_gas(),
+        Some("spiral") => start_greenberg_hastings(),
+        Some("cyclic") => start_cyclic(),
+        Some("colors") => start_color_life(),
+        Some("epidemic") => start_epidemic(),
+        Some("nasch") => start_nagel_schreckenberg(),
+        Some("bml") => start_biham_middleton_levine(),
+        Some("wator") => start_wator(),
+        Some("schelling") => start_schelling(),
+        Some("ising") => start_ising(),
+        Some("jvn") => start_von_neumann(),
+        Some("loops") => start_loops(),
+        Some("golly") => start_golly_rule(),
+        Some("int") => start_lookup_rule(),
+        Some("ltl") => start_larger_than_life(),
+        Some("hybrid") => start_hybrid(),
+        Some("schedule") => start_schedule(),
+        Some("memory") => start_memory(),
+        Some("layers") => start_layers(),
+        Some("boards") => start_coupled_boards(),
+        _ => start_wireworld(),
+    };
+
+    result.unwrap();
 }
@@ -0,0 +1,211 @@
+use std::fmt::{Debug, Formatter};
+use crate::grid::Grid;
+use crate::margolus::rules::{BlockRule, BOTTOM_LEFT, BOTTOM_RIGHT, TOP_LEFT, TOP_RIGHT};
+
+const BLOCK_CORNERS: [(usize, usize, u8); 4] = [
+    (0, 0, TOP_LEFT),
+    (1, 0, TOP_RIGHT),
+    (0, 1, BOTTOM_LEFT),
+    (1, 1, BOTTOM_RIGHT),
+];
+
+/// A block cellular automaton using the Margolus neighborhood. The grid is partitioned into 2x2 blocks, and the
+/// partition shifts by one cell diagonally every generation. The grid wraps, so both dimensions must be even.
+pub struct Environment {
+    grid: Grid<bool>,
+    rule: BlockRule,
+    // Signed, since a reversible rule can be stepped back past its starting configuration.
+    generation: isize,
+}
+
+impl Environment {
+    pub fn new(width: usize, height: usize, rule: BlockRule, initial_cell_producer: impl Fn(usize, usize) -> bool) -> Environment {
+        assert!(width.is_multiple_of(2) && height.is_multiple_of(2), "Margolus grids must have even dimensions. Got ({}, {})", width, height);
+
+        Environment {
+            grid: Grid::new(width, height, initial_cell_producer),
+            rule,
+            generation: 0,
+        }
+    }
+
+    pub fn new_empty(width: usize, height: usize, rule: BlockRule) -> Environment {
+        Self::new(width, height, rule, |_x, _y| false)
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> bool {
+        self.grid.get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.grid.get_width(), self.grid.get_height())
+    }
+
+    pub fn get_generation(&self) -> isize {
+        self.generation
+    }
+
+    pub fn bulk_set_readable(&mut self, cells: Vec<(usize, usize, bool)>) {
+        let (width, height) = self.get_dimensions();
+
+        for (x, y, alive) in cells {
+            if x < width && y < height {
+                self.grid.set_cell(x, y, alive);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
+    }
+
+    fn block_offset(generation: isize) -> usize {
+        generation.rem_euclid(2) as usize
+    }
+
+    fn read_block(&self, block_x: usize, block_y: usize) -> u8 {
+        let (width, height) = self.get_dimensions();
+
+        BLOCK_CORNERS.iter().fold(0, |block, &(dx, dy, bit)| {
+            if self.grid.get_cell((block_x + dx) % width, (block_y + dy) % height) { block | bit } else { block }
+        })
+    }
+
+    fn write_block(&mut self, block_x: usize, block_y: usize, block: u8) {
+        let (width, height) = self.get_dimensions();
+
+        for (dx, dy, bit) in BLOCK_CORNERS {
+            self.grid.set_cell((block_x + dx) % width, (block_y + dy) % height, block & bit != 0);
+        }
+    }
+
+    // Blocks never overlap within a single partition, so they can be transformed in place without a second grid.
+    fn transform_blocks(&mut self, offset: usize, transform: impl Fn(&BlockRule, u8) -> u8) {
+        let (width, height) = self.get_dimensions();
+
+        for block_y in (offset..height + offset).step_by(2) {
+            for block_x in (offset..width + offset).step_by(2) {
+                let block = self.read_block(block_x, block_y);
+                let next_block = transform(&self.rule, block);
+                self.write_block(block_x, block_y, next_block);
+            }
+        }
+    }
+
+    pub fn advance(&mut self) {
+        self.transform_blocks(Self::block_offset(self.generation), BlockRule::apply);
+        self.generation += 1;
+    }
+
+    /// Exactly undoes the last call to advance by applying the inverse block rule on the previous partition.
+    pub fn step_back(&mut self) {
+        self.generation -= 1;
+        self.transform_blocks(Self::block_offset(self.generation), BlockRule::apply_inverse);
+    }
+}
+
+impl Debug for Environment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (width, height) = self.get_dimensions();
+
+        let mut output: String = String::with_capacity((width + 1) * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                output.push(if self.get_cell(x, y) { '#' } else { ' ' });
+            }
+            output.push('\n');
+        }
+
+        write!(f, "{}", output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn live_cells(env: &Environment) -> Vec<(usize, usize)> {
+        let (width, height) = env.get_dimensions();
+        let mut cells = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                if env.get_cell(x, y) {
+                    cells.push((x, y));
+                }
+            }
+        }
+
+        cells
+    }
+
+    #[test]
+    fn test_billiard_ball_travels_diagonally() {
+        let mut env = Environment::new_empty(8, 8, BlockRule::billiard_ball_machine());
+        env.bulk_set_readable(vec![(2, 2, true)]);
+
+        env.advance();
+        assert_eq!(live_cells(&env), vec![(3, 3)]);
+
+        env.advance();
+        assert_eq!(live_cells(&env), vec![(4, 4)]);
+
+        env.advance();
+        assert_eq!(live_cells(&env), vec![(5, 5)]);
+    }
+
+    #[test]
+    fn test_billiard_ball_wraps_around_edges() {
+        let mut env = Environment::new_empty(4, 4, BlockRule::billiard_ball_machine());
+        env.bulk_set_readable(vec![(3, 3, true)]);
+
+        env.advance();
+        assert_eq!(live_cells(&env), vec![(2, 2)]);
+
+        env.advance();
+        assert_eq!(live_cells(&env), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_step_back_restores_random_soup() {
+        let mut rng = StdRng::seed_from_u64(26);
+
+        for rule in [BlockRule::billiard_ball_machine(), BlockRule::critters(), BlockRule::tron()] {
+            let mut env = Environment::new(16, 12, rule, |_x, _y| false);
+            let soup: Vec<(usize, usize, bool)> = (0..16 * 12).map(|i| (i % 16, i / 16, rng.random_bool(0.4))).collect();
+            env.bulk_set_readable(soup);
+            let initial_cells = live_cells(&env);
+
+            for _ in 0..25 {
+                env.advance();
+            }
+            for _ in 0..25 {
+                env.step_back();
+            }
+
+            assert_eq!(env.get_generation(), 0);
+            assert_eq!(live_cells(&env), initial_cells);
+        }
+    }
+
+    #[test]
+    fn test_step_back_before_start_then_advance() {
+        let mut env = Environment::new_empty(6, 6, BlockRule::critters());
+        env.bulk_set_readable(vec![(1, 1, true), (2, 1, true), (2, 2, true)]);
+        let initial_cells = live_cells(&env);
+
+        env.step_back();
+        env.step_back();
+        assert_eq!(env.get_generation(), -2);
+
+        env.advance();
+        env.advance();
+        assert_eq!(live_cells(&env), initial_cells);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_odd_dimensions_rejected() {
+        Environment::new_empty(5, 4, BlockRule::tron());
+    }
+}
@@ -0,0 +1,126 @@
+// A 2x2 block is packed into the low four bits of a u8:
+//   bit 0: top-left,    bit 1: top-right
+//   bit 2: bottom-left, bit 3: bottom-right
+pub const TOP_LEFT: u8 = 0b0001;
+pub const TOP_RIGHT: u8 = 0b0010;
+pub const BOTTOM_LEFT: u8 = 0b0100;
+pub const BOTTOM_RIGHT: u8 = 0b1000;
+
+pub const BLOCK_STATES: usize = 16;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockRule {
+    forward: [u8; BLOCK_STATES],
+    backward: [u8; BLOCK_STATES],
+}
+
+impl BlockRule {
+    /// Returns None if the table isn't a permutation of the 16 block states, since only those rules can be stepped back.
+    pub fn from_table(forward: [u8; BLOCK_STATES]) -> Option<BlockRule> {
+        let mut backward = [u8::MAX; BLOCK_STATES];
+
+        for (block, &next_block) in forward.iter().enumerate() {
+            let next_index = next_block as usize;
+            if next_index >= BLOCK_STATES || backward[next_index] != u8::MAX {
+                return None;
+            }
+
+            backward[next_index] = block as u8;
+        }
+
+        Some(BlockRule { forward, backward })
+    }
+
+    pub fn from_block_f(block_f: fn(u8) -> u8) -> Option<BlockRule> {
+        let mut table = [0; BLOCK_STATES];
+        for (block, next_block) in table.iter_mut().enumerate() {
+            *next_block = block_f(block as u8);
+        }
+
+        Self::from_table(table)
+    }
+
+    pub fn billiard_ball_machine() -> BlockRule {
+        Self::from_block_f(|block| match block {
+            TOP_LEFT => BOTTOM_RIGHT,
+            TOP_RIGHT => BOTTOM_LEFT,
+            BOTTOM_LEFT => TOP_RIGHT,
+            BOTTOM_RIGHT => TOP_LEFT,
+            0b1001 => 0b0110,
+            0b0110 => 0b1001,
+            _ => block,
+        }).unwrap()
+    }
+
+    pub fn critters() -> BlockRule {
+        Self::from_block_f(|block| match block.count_ones() {
+            2 => block,
+            3 => rotate_half_turn(!block & 0b1111),
+            _ => !block & 0b1111,
+        }).unwrap()
+    }
+
+    pub fn tron() -> BlockRule {
+        Self::from_block_f(|block| match block {
+            0b0000 | 0b1111 => !block & 0b1111,
+            _ => block,
+        }).unwrap()
+    }
+
+    pub fn apply(&self, block: u8) -> u8 {
+        self.forward[block as usize]
+    }
+
+    pub fn apply_inverse(&self, block: u8) -> u8 {
+        self.backward[block as usize]
+    }
+}
+
+fn rotate_half_turn(block: u8) -> u8 {
+    ((block & TOP_LEFT) << 3)
+        | ((block & TOP_RIGHT) << 1)
+        | ((block & BOTTOM_LEFT) >> 1)
+        | ((block & BOTTOM_RIGHT) >> 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_table_rejects_non_permutation() {
+        let mut table = [0; BLOCK_STATES];
+        for (block, next_block) in table.iter_mut().enumerate() {
+            *next_block = block as u8;
+        }
+        table[3] = 2;
+
+        assert_eq!(BlockRule::from_table(table), None);
+    }
+
+    #[test]
+    fn test_rotate_half_turn() {
+        assert_eq!(rotate_half_turn(TOP_LEFT), BOTTOM_RIGHT);
+        assert_eq!(rotate_half_turn(TOP_LEFT | TOP_RIGHT), BOTTOM_LEFT | BOTTOM_RIGHT);
+        assert_eq!(rotate_half_turn(0b1110), 0b0111);
+    }
+
+    #[test]
+    fn test_inverse_undoes_every_preset() {
+        for rule in [BlockRule::billiard_ball_machine(), BlockRule::critters(), BlockRule::tron()] {
+            for block in 0..BLOCK_STATES as u8 {
+                assert_eq!(rule.apply_inverse(rule.apply(block)), block);
+            }
+        }
+    }
+
+    #[test]
+    fn test_critters_block_cases() {
+        let rule = BlockRule::critters();
+
+        assert_eq!(rule.apply(0b0000), 0b1111);
+        assert_eq!(rule.apply(0b0101), 0b0101);
+        assert_eq!(rule.apply(TOP_LEFT), 0b1110);
+        assert_eq!(rule.apply(0b0111), TOP_LEFT);
+    }
+}
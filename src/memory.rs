@@ -0,0 +1,173 @@
+use crate::environment::AdvanceCellF;
+use crate::grid::Grid;
+
+/// How much a cell's past states count towards its featured state. Each state in the memory is weighted by
+/// `decay.powi(age)`, with the current state at age 0, so a decay of 1 counts the last k states equally.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct MemoryWeighting {
+    pub depth: usize,
+    pub decay: f64,
+}
+
+impl MemoryWeighting {
+    /// The majority of the last `depth` states.
+    pub fn majority(depth: usize) -> MemoryWeighting {
+        MemoryWeighting { depth, decay: 1.0 }
+    }
+
+    /// Past states count less the older they are, by a factor of `decay` each generation.
+    pub fn geometric(depth: usize, decay: f64) -> MemoryWeighting {
+        MemoryWeighting { depth, decay }
+    }
+
+    /// The state with the most weight in a cell's memory, given newest first. Ties go to the most recent of the tied
+    /// states, so with an even split a cell keeps its current state.
+    pub fn featured_state<T: Copy + PartialEq>(&self, memory: impl Iterator<Item = T>) -> T {
+        let mut weights: Vec<(T, f64)> = vec![];
+        let mut weight = 1.0;
+        for state in memory.take(self.depth) {
+            match weights.iter_mut().find(|(seen, _)| *seen == state) {
+                Some((_, total)) => *total += weight,
+                None => weights.push((state, weight)),
+            }
+            weight *= self.decay;
+        }
+
+        weights.iter().fold(weights[0], |best, &candidate| if candidate.1 > best.1 { candidate } else { best }).0
+    }
+}
+
+/// An environment with memory, in the style of Alonso-Sanz's memory automata. Each cell remembers its last few states
+/// in a ring of grids, and the rule is applied to every cell's featured state, a weighted mode of that memory, instead
+/// of its current state. The new states then become the newest entry of the ring. A depth of 1 runs the rule without
+/// memory.
+pub struct MemoryEnvironment<T> {
+    /// The last `depth` generations, filled in as the run goes, with the newest at `newest`.
+    history: Vec<Grid<T>>,
+    newest: usize,
+    weighting: MemoryWeighting,
+    advance_cell_f: Box<AdvanceCellF<T>>,
+    generation: usize,
+}
+
+impl<T: Copy + PartialEq> MemoryEnvironment<T> {
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, advance_cell_f: Box<AdvanceCellF<T>>, weighting: MemoryWeighting) -> MemoryEnvironment<T> {
+        assert!(weighting.depth >= 1, "A memory needs at least the current state");
+
+        MemoryEnvironment {
+            history: vec![Grid::new(width, height, initial_cell_producer)],
+            newest: 0,
+            weighting,
+            advance_cell_f,
+            generation: 0,
+        }
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> T {
+        self.history[self.newest].get_cell(x, y)
+    }
+
+    pub fn get_grid(&self) -> &Grid<T> {
+        &self.history[self.newest]
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.get_grid().get_width(), self.get_grid().get_height())
+    }
+
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn get_weighting(&self) -> MemoryWeighting {
+        self.weighting
+    }
+
+    /// A cell's remembered states, newest first.
+    pub fn get_memory(&self, x: usize, y: usize) -> Vec<T> {
+        let len = self.history.len();
+        (0..len).map(|age| self.history[(self.newest + len - age) % len].get_cell(x, y)).collect()
+    }
+
+    /// The states the next generation is computed from.
+    pub fn get_featured_grid(&self) -> Grid<T> {
+        let (width, height) = self.get_dimensions();
+        Grid::new(width, height, |x, y| self.weighting.featured_state(self.get_memory(x, y).into_iter()))
+    }
+
+    /// Sets the current state of cells, leaving their older memory as it was.
+    pub fn bulk_set_readable(&mut self, cells: Vec<(usize, usize, T)>) {
+        let (width, height) = self.get_dimensions();
+
+        for (x, y, cell) in cells {
+            if x < width && y < height {
+                self.history[self.newest].set_cell(x, y, cell);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
+    }
+
+    pub fn advance(&mut self) {
+        let featured = self.get_featured_grid();
+        let next = Grid::new(featured.get_width(), featured.get_height(), |x, y| (self.advance_cell_f)(&featured, x, y));
+
+        if self.history.len() < self.weighting.depth {
+            self.history.push(next);
+            self.newest = self.history.len() - 1;
+        } else {
+            self.newest = (self.newest + 1) % self.history.len();
+            self.history[self.newest] = next;
+        }
+        self.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway;
+    use crate::cell_types::conway::fixtures;
+    use crate::environment::Environment;
+    use crate::grid::Boundary;
+
+    #[test]
+    fn test_featured_state() {
+        let majority = MemoryWeighting::majority(3);
+        assert_eq!(majority.featured_state([1, 0, 0].into_iter()), 0);
+        assert_eq!(majority.featured_state([1, 0].into_iter()), 1);
+        // Only the last 3 states count.
+        assert_eq!(majority.featured_state([1, 1, 0, 0, 0].into_iter()), 1);
+
+        assert_eq!(MemoryWeighting::geometric(3, 0.5).featured_state([0, 1, 1].into_iter()), 0);
+        assert_eq!(MemoryWeighting::geometric(3, 0.9).featured_state([0, 1, 1].into_iter()), 1);
+    }
+
+    #[test]
+    fn test_depth_one_matches_memoryless_run() {
+        let mut with_memory = MemoryEnvironment::new(16, 16, fixtures::soup, conway::life_rule(Boundary::Wrapping), MemoryWeighting::majority(1));
+        let mut without = Environment::new(16, 16, fixtures::soup, conway::life_rule(Boundary::Wrapping));
+
+        for _ in 0..20 {
+            with_memory.advance();
+            without.advance();
+            assert_eq!(with_memory.get_grid(), without.get_grid());
+        }
+    }
+
+    #[test]
+    fn test_memory_ring_keeps_last_states() {
+        let mut env = MemoryEnvironment::new(5, 5, |_x, _y| 0, conway::life_rule(Boundary::Bounded), MemoryWeighting::majority(3));
+        env.bulk_set_readable(vec![(1, 2, 1), (2, 2, 1), (3, 2, 1)]);
+
+        for _ in 0..4 {
+            env.advance();
+        }
+
+        // The blinker still oscillates, since the majority of its last three phases is always the latest one.
+        assert_eq!(env.get_memory(2, 1), vec![0, 1, 0]);
+        assert_eq!(env.get_memory(1, 2), vec![1, 0, 1]);
+        assert_eq!(env.get_memory(2, 2), vec![1, 1, 1]);
+        assert_eq!(env.get_generation(), 4);
+    }
+}
@@ -0,0 +1,197 @@
+use std::ops::Range;
+use crate::environment::CellChange;
+use crate::grid::Grid;
+
+/// Identifies a registered observer, so that it can be removed later.
+pub type ObserverId = usize;
+
+pub type GenerationHookF<T> = dyn FnMut(&Grid<T>, isize);
+pub type ChangesHookF<T> = dyn FnMut(&Grid<T>, isize, &[CellChange<T>]);
+pub type CellHookF<T> = dyn FnMut(&CellChange<T>, isize);
+pub type PopulationHookF = dyn FnMut(Crossing, usize, isize);
+
+/// Which way a population crossed its threshold.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Crossing {
+    /// From below the threshold to at or above it.
+    Rising,
+    /// From at or above the threshold to below it.
+    Falling,
+}
+
+struct CellWatcher<T> {
+    id: ObserverId,
+    columns: Range<usize>,
+    rows: Range<usize>,
+    state: T,
+    hook: Box<CellHookF<T>>,
+}
+
+/// Counts the cells in some states, kept up to date from the changes of each generation instead of recounted.
+struct PopulationWatcher<T> {
+    id: ObserverId,
+    counts: Box<dyn Fn(T) -> bool>,
+    threshold: usize,
+    population: usize,
+    hook: Box<PopulationHookF>,
+}
+
+/// The callbacks registered on an environment.
+pub(crate) struct Observers<T> {
+    next_id: ObserverId,
+    before: Vec<(ObserverId, Box<GenerationHookF<T>>)>,
+    after: Vec<(ObserverId, Box<ChangesHookF<T>>)>,
+    cell_watchers: Vec<CellWatcher<T>>,
+    population_watchers: Vec<PopulationWatcher<T>>,
+}
+
+impl<T: Copy + PartialEq> Observers<T> {
+    pub(crate) fn new() -> Observers<T> {
+        Observers {
+            next_id: 0,
+            before: vec![],
+            after: vec![],
+            cell_watchers: vec![],
+            population_watchers: vec![],
+        }
+    }
+
+    fn take_id(&mut self) -> ObserverId {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    pub(crate) fn add_before(&mut self, hook: Box<GenerationHookF<T>>) -> ObserverId {
+        let id = self.take_id();
+        self.before.push((id, hook));
+        id
+    }
+
+    pub(crate) fn add_after(&mut self, hook: Box<ChangesHookF<T>>) -> ObserverId {
+        let id = self.take_id();
+        self.after.push((id, hook));
+        id
+    }
+
+    pub(crate) fn add_cell_watcher(&mut self, columns: Range<usize>, rows: Range<usize>, state: T, hook: Box<CellHookF<T>>) -> ObserverId {
+        let id = self.take_id();
+        self.cell_watchers.push(CellWatcher { id, columns, rows, state, hook });
+        id
+    }
+
+    pub(crate) fn add_population_watcher(&mut self, grid: &Grid<T>, counts: Box<dyn Fn(T) -> bool>, threshold: usize, hook: Box<PopulationHookF>) -> ObserverId {
+        let id = self.take_id();
+        let population = (0..grid.get_height()).map(|y| (0..grid.get_width()).filter(|&x| counts(grid.get_cell(x, y))).count()).sum();
+        self.population_watchers.push(PopulationWatcher { id, counts, threshold, population, hook });
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: ObserverId) -> bool {
+        let before = self.len();
+        self.before.retain(|(hook_id, _)| *hook_id != id);
+        self.after.retain(|(hook_id, _)| *hook_id != id);
+        self.cell_watchers.retain(|watcher| watcher.id != id);
+        self.population_watchers.retain(|watcher| watcher.id != id);
+        self.len() < before
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.before.len() + self.after.len() + self.cell_watchers.len() + self.population_watchers.len()
+    }
+
+    pub(crate) fn before_generation(&mut self, grid: &Grid<T>, generation: isize) {
+        for (_, hook) in self.before.iter_mut() {
+            hook(grid, generation);
+        }
+    }
+
+    /// Tells the cell and population watchers about changed cells, whether a generation or an edit changed them.
+    pub(crate) fn cells_changed(&mut self, changes: &[CellChange<T>], generation: isize) {
+        for watcher in self.cell_watchers.iter_mut() {
+            for change in changes {
+                if change.new == watcher.state && watcher.columns.contains(&change.x) && watcher.rows.contains(&change.y) {
+                    (watcher.hook)(change, generation);
+                }
+            }
+        }
+
+        for watcher in self.population_watchers.iter_mut() {
+            let previous = watcher.population;
+            for change in changes {
+                watcher.population = watcher.population + (watcher.counts)(change.new) as usize - (watcher.counts)(change.old) as usize;
+            }
+
+            let was_above = previous >= watcher.threshold;
+            let is_above = watcher.population >= watcher.threshold;
+            if was_above != is_above {
+                let crossing = if is_above { Crossing::Rising } else { Crossing::Falling };
+                (watcher.hook)(crossing, watcher.population, generation);
+            }
+        }
+    }
+
+    pub(crate) fn after_generation(&mut self, grid: &Grid<T>, generation: isize, changes: &[CellChange<T>]) {
+        self.cells_changed(changes, generation);
+        for (_, hook) in self.after.iter_mut() {
+            hook(grid, generation, changes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::cell_types::conway;
+    use crate::environment::Environment;
+    use crate::grid::Boundary;
+
+    fn blinker() -> Environment<u8> {
+        let mut env = Environment::new(5, 5, |_x, _y| 0, conway::life_rule(Boundary::Bounded));
+        env.bulk_set_readable(vec![(1, 2, 1), (2, 2, 1), (3, 2, 1)]);
+        env
+    }
+
+    #[test]
+    fn test_generation_hooks_and_cell_watchers() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut env = blinker();
+        let log = events.clone();
+        env.on_before_generation(Box::new(move |_grid, generation| log.borrow_mut().push(format!("before {}", generation))));
+        let log = events.clone();
+        env.on_after_generation(Box::new(move |_grid, generation, changes| log.borrow_mut().push(format!("after {} ({} changes)", generation, changes.len()))));
+        // Only the top arm of the blinker is in the region.
+        let log = events.clone();
+        let watcher = env.on_cell_change(0..5, 0..2, 1, Box::new(move |change, generation| log.borrow_mut().push(format!("({}, {}) on at {}", change.x, change.y, generation))));
+
+        env.run(2);
+        assert!(env.remove_observer(watcher));
+        assert!(!env.remove_observer(watcher));
+        env.run(1);
+
+        assert_eq!(*events.borrow(), vec![
+            "before 0", "(2, 1) on at 1", "after 1 (4 changes)",
+            "before 1", "after 2 (4 changes)",
+            "before 2", "after 3 (4 changes)",
+        ]);
+    }
+
+    #[test]
+    fn test_population_crossings() {
+        let crossings = Rc::new(RefCell::new(vec![]));
+        let mut env = blinker();
+        let log = crossings.clone();
+        env.on_population_crossing(Box::new(|state| state == 1), 4, Box::new(move |crossing, population, generation| log.borrow_mut().push((crossing, population, generation))));
+
+        // Adding a fourth cell by hand crosses the threshold, and it then dies along with the blinker's ends, leaving
+        // fewer than 4.
+        env.bulk_set_readable(vec![(0, 0, 1)]);
+        env.run(1);
+        // Dropping further doesn't cross it again.
+        env.bulk_set_readable(vec![(2, 2, 0)]);
+        env.run(1);
+
+        assert_eq!(*crossings.borrow(), vec![(Crossing::Rising, 4, 0), (Crossing::Falling, 3, 1)]);
+    }
+}
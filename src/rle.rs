@@ -0,0 +1,145 @@
+use crate::grid::Grid;
+
+/// A pattern read from Golly's run-length encoded format.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RlePattern {
+    pub width: usize,
+    pub height: usize,
+    /// The rule named in the header, if there was one.
+    pub rule: Option<String>,
+    /// Every non-zero cell, as (x, y, state).
+    pub cells: Vec<(usize, usize, u8)>,
+}
+
+impl RlePattern {
+    /// Lays the pattern out on a grid of state 0, with `margin` empty cells on every side.
+    pub fn to_grid(&self, margin: usize) -> Grid<u8> {
+        let mut grid = Grid::new_filled(self.width + 2 * margin, self.height + 2 * margin, 0);
+        for &(x, y, state) in &self.cells {
+            grid.set_cell(x + margin, y + margin, state);
+        }
+
+        grid
+    }
+}
+
+/// Parses a state token. Two-state patterns use b and o, and multistate patterns use . for 0, A to X for 1 to 24, and
+/// a prefix from p to y for each further block of 24 states.
+fn parse_state(chars: &[char], index: &mut usize) -> Option<u8> {
+    let c = chars[*index];
+    *index += 1;
+
+    match c {
+        'b' | '.' => Some(0),
+        'o' => Some(1),
+        'A'..='X' => Some(c as u8 - b'A' + 1),
+        'p'..='y' => {
+            let letter = *chars.get(*index)?;
+            *index += 1;
+            if !letter.is_ascii_uppercase() || letter > 'X' {
+                return None;
+            }
+
+            let state = (c as usize - 'p' as usize + 1) * 24 + (letter as usize - 'A' as usize + 1);
+            u8::try_from(state).ok()
+        },
+        _ => None,
+    }
+}
+
+fn parse_header(line: &str) -> Option<(usize, usize, Option<String>)> {
+    let mut width = None;
+    let mut height = None;
+    let mut rule = None;
+
+    for field in line.split(',') {
+        let (key, value) = field.split_once('=')?;
+        match key.trim() {
+            "x" => width = value.trim().parse().ok(),
+            "y" => height = value.trim().parse().ok(),
+            "rule" => rule = Some(value.trim().to_string()),
+            _ => {},
+        }
+    }
+
+    Some((width?, height?, rule))
+}
+
+/// Reads a pattern in RLE format. Returns None if the header is missing or the body has a token it doesn't recognize,
+/// or a cell outside the size given in the header.
+pub fn parse_rle(text: &str) -> Option<RlePattern> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let (width, height, rule) = parse_header(lines.next()?)?;
+
+    let chars: Vec<char> = lines.flat_map(str::chars).filter(|c| !c.is_whitespace()).collect();
+    let mut cells = vec![];
+    let mut x = 0;
+    let mut y = 0;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let mut run_length = 0;
+        while let Some(digit) = chars[index].to_digit(10) {
+            run_length = run_length * 10 + digit as usize;
+            index += 1;
+            if index == chars.len() {
+                return None;
+            }
+        }
+        let run_length = run_length.max(1);
+
+        match chars[index] {
+            '!' => break,
+            '$' => {
+                index += 1;
+                x = 0;
+                y += run_length;
+            },
+            _ => {
+                let state = parse_state(&chars, &mut index)?;
+                if state != 0 {
+                    if x + run_length > width || y >= height {
+                        return None;
+                    }
+                    cells.extend((x..x + run_length).map(|cell_x| (cell_x, y, state)));
+                }
+                x += run_length;
+            },
+        }
+    }
+
+    Some(RlePattern {
+        width,
+        height,
+        rule,
+        cells,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_state_glider() {
+        let pattern = parse_rle("#C A glider\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!").unwrap();
+
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(pattern.cells, vec![(1, 0, 1), (2, 1, 1), (0, 2, 1), (1, 2, 1), (2, 2, 1)]);
+    }
+
+    #[test]
+    fn test_multistate_tokens_and_blank_rows() {
+        let pattern = parse_rle("x = 4, y = 3, rule = JvN29\n.A2pA$\n$3.qB!").unwrap();
+
+        assert_eq!(pattern.cells, vec![(1, 0, 1), (2, 0, 25), (3, 0, 25), (3, 2, 50)]);
+        assert_eq!(pattern.to_grid(1).get_cell(4, 1), 25);
+    }
+
+    #[test]
+    fn test_rejects_malformed_patterns() {
+        assert_eq!(parse_rle("bob$2bo$3o!"), None);
+        assert_eq!(parse_rle("x = 2, y = 1\n3o!"), None);
+        assert_eq!(parse_rle("x = 2, y = 1\no?!"), None);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use crate::grid::{Boundary, Grid, VON_NEUMANN_OFFSETS};
+
+/// A cell with this many grains is unstable and topples, sending one grain to each of its von Neumann neighbors.
+pub const TOPPLE_THRESHOLD: u32 = 4;
+pub const MAX_STABLE: u32 = TOPPLE_THRESHOLD - 1;
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Avalanche {
+    /// The total number of times any cell toppled.
+    pub topplings: usize,
+    /// The number of distinct cells that toppled at least once.
+    pub area: usize,
+}
+
+/// The Abelian sandpile model. Grains that topple off the edge of the grid are lost, which guarantees that every
+/// configuration eventually stabilizes.
+///
+/// The pile can be updated either by relaxing it until it's stable, or by advancing one synchronous wave at a time,
+/// where every unstable cell topples once. Both reach the same stable configuration, since the model is Abelian.
+pub struct Sandpile {
+    grid: Grid<u32>,
+    avalanches: Vec<Avalanche>,
+}
+
+impl Sandpile {
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> u32) -> Sandpile {
+        Sandpile {
+            grid: Grid::new(width, height, initial_cell_producer),
+            avalanches: vec![],
+        }
+    }
+
+    pub fn new_empty(width: usize, height: usize) -> Sandpile {
+        Self::new(width, height, |_x, _y| 0)
+    }
+
+    /// The identity element of the sandpile group on this grid: the unique recurrent configuration that leaves every
+    /// other recurrent configuration unchanged when added to it. Computed as (2m - (2m)°)°, where m is the maximal
+    /// stable configuration, and ° is stabilization.
+    pub fn identity(width: usize, height: usize) -> Sandpile {
+        let mut doubled_max = Self::new(width, height, |_x, _y| 2 * MAX_STABLE);
+        doubled_max.relax();
+
+        let mut identity = Self::new(width, height, |x, y| 2 * MAX_STABLE - doubled_max.get_cell(x, y));
+        identity.relax();
+        identity.avalanches.clear();
+
+        identity
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> u32 {
+        self.grid.get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.grid.get_width(), self.grid.get_height())
+    }
+
+    pub fn get_total_grains(&self) -> u64 {
+        let (width, height) = self.get_dimensions();
+        (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| self.get_cell(x, y) as u64).sum()
+    }
+
+    pub fn is_stable(&self) -> bool {
+        let (width, height) = self.get_dimensions();
+        (0..height).all(|y| (0..width).all(|x| self.get_cell(x, y) < TOPPLE_THRESHOLD))
+    }
+
+    pub fn bulk_set_readable(&mut self, cells: Vec<(usize, usize, u32)>) {
+        let (width, height) = self.get_dimensions();
+
+        for (x, y, grains) in cells {
+            if x < width && y < height {
+                self.grid.set_cell(x, y, grains);
+            } else {
+                eprintln!("Could not set cell at {}, {}. Dimensions: ({}, {})", x, y, width, height);
+            }
+        }
+    }
+
+    /// Drops a single grain, then relaxes the pile. The resulting avalanche is recorded and returned.
+    pub fn add_grain(&mut self, x: usize, y: usize) -> Avalanche {
+        self.grid.set_cell(x, y, self.grid.get_cell(x, y) + 1);
+
+        let avalanche = self.relax();
+        self.avalanches.push(avalanche);
+
+        avalanche
+    }
+
+    /// Drops a grain without relaxing, so that the avalanche can be watched wave by wave using advance.
+    pub fn drop_grain(&mut self, x: usize, y: usize) {
+        self.grid.set_cell(x, y, self.grid.get_cell(x, y) + 1);
+    }
+
+    fn topple(&mut self, x: usize, y: usize, times: u32) -> Vec<(usize, usize)> {
+        self.grid.set_cell(x, y, self.grid.get_cell(x, y) - times * TOPPLE_THRESHOLD);
+
+        VON_NEUMANN_OFFSETS.iter().filter_map(|&offset| {
+            let (neighbor_x, neighbor_y) = self.grid.get_neighbor_coord(x, y, offset, Boundary::Bounded)?;
+            self.grid.set_cell(neighbor_x, neighbor_y, self.grid.get_cell(neighbor_x, neighbor_y) + times);
+            Some((neighbor_x, neighbor_y))
+        }).collect()
+    }
+
+    /// Topples cells until the whole pile is stable. Unstable cells topple as many times as they can at once, since
+    /// the order of topplings doesn't change the outcome.
+    pub fn relax(&mut self) -> Avalanche {
+        let (width, height) = self.get_dimensions();
+        let mut toppled = Grid::new_filled(width, height, false);
+        let mut avalanche = Avalanche::default();
+
+        let mut unstable: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get_cell(x, y) >= TOPPLE_THRESHOLD)
+            .collect();
+
+        while let Some((x, y)) = unstable.pop() {
+            let times = self.get_cell(x, y) / TOPPLE_THRESHOLD;
+            if times == 0 {
+                continue;
+            }
+
+            avalanche.topplings += times as usize;
+            if !toppled.get_cell(x, y) {
+                toppled.set_cell(x, y, true);
+                avalanche.area += 1;
+            }
+
+            for (neighbor_x, neighbor_y) in self.topple(x, y, times) {
+                if self.get_cell(neighbor_x, neighbor_y) >= TOPPLE_THRESHOLD {
+                    unstable.push((neighbor_x, neighbor_y));
+                }
+            }
+        }
+
+        avalanche
+    }
+
+    /// A single synchronous wave: every cell that's unstable at the start of the wave topples once.
+    pub fn advance(&mut self) -> usize {
+        let (width, height) = self.get_dimensions();
+        let unstable: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get_cell(x, y) >= TOPPLE_THRESHOLD)
+            .collect();
+
+        for &(x, y) in &unstable {
+            self.topple(x, y, 1);
+        }
+
+        unstable.len()
+    }
+
+    pub fn get_avalanches(&self) -> &[Avalanche] {
+        &self.avalanches
+    }
+
+    /// Maps each avalanche size, measured in topplings, to how many recorded avalanches had that size.
+    pub fn avalanche_size_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for avalanche in &self.avalanches {
+            *histogram.entry(avalanche.topplings).or_insert(0) += 1;
+        }
+
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells_of(pile: &Sandpile) -> Vec<u32> {
+        let (width, height) = pile.get_dimensions();
+        (0..width * height).map(|i| pile.get_cell(i % width, i / width)).collect()
+    }
+
+    #[test]
+    fn test_single_topple() {
+        let mut pile = Sandpile::new_empty(3, 3);
+        pile.bulk_set_readable(vec![(1, 1, 3)]);
+
+        let avalanche = pile.add_grain(1, 1);
+
+        assert_eq!(avalanche, Avalanche { topplings: 1, area: 1 });
+        assert_eq!(cells_of(&pile), vec![0, 1, 0, 1, 0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_grains_fall_off_edges() {
+        let mut pile = Sandpile::new_empty(1, 1);
+        pile.bulk_set_readable(vec![(0, 0, 3)]);
+
+        pile.add_grain(0, 0);
+
+        assert_eq!(pile.get_total_grains(), 0);
+    }
+
+    #[test]
+    fn test_waves_reach_same_configuration_as_relax() {
+        let mut relaxed = Sandpile::new_empty(7, 7);
+        let mut waved = Sandpile::new_empty(7, 7);
+        relaxed.bulk_set_readable(vec![(3, 3, 200), (1, 5, 30)]);
+        waved.bulk_set_readable(vec![(3, 3, 200), (1, 5, 30)]);
+
+        relaxed.relax();
+        while waved.advance() > 0 {}
+
+        assert!(waved.is_stable());
+        assert_eq!(cells_of(&waved), cells_of(&relaxed));
+    }
+
+    #[test]
+    fn test_identity_is_idempotent() {
+        let identity = Sandpile::identity(6, 5);
+        let mut doubled = Sandpile::new(6, 5, |x, y| 2 * identity.get_cell(x, y));
+
+        doubled.relax();
+
+        assert!(identity.is_stable());
+        assert_eq!(cells_of(&doubled), cells_of(&identity));
+    }
+
+    #[test]
+    fn test_identity_leaves_recurrent_configuration_unchanged() {
+        let identity = Sandpile::identity(5, 5);
+        let max_stable = Sandpile::new(5, 5, |_x, _y| MAX_STABLE);
+        let mut sum = Sandpile::new(5, 5, |x, y| identity.get_cell(x, y) + max_stable.get_cell(x, y));
+
+        sum.relax();
+
+        assert_eq!(cells_of(&sum), cells_of(&max_stable));
+    }
+
+    #[test]
+    fn test_avalanche_statistics() {
+        let mut pile = Sandpile::new_empty(5, 5);
+
+        for _ in 0..20 {
+            pile.add_grain(2, 2);
+        }
+
+        let histogram = pile.avalanche_size_histogram();
+        assert_eq!(pile.get_avalanches().len(), 20);
+        assert_eq!(histogram.values().sum::<usize>(), 20);
+        assert!(histogram[&0] >= 15);
+    }
+}
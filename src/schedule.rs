@@ -0,0 +1,450 @@
+use std::collections::BTreeMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::environment::{AdvanceCellF, Environment};
+use crate::grid::Grid;
+use crate::rle::parse_rle;
+
+/// A parameter that changes over a run, given as values at some generations. Between two keyframes the value is
+/// interpolated linearly, and before the first or after the last it holds steady.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Keyframes {
+    points: Vec<(usize, f64)>,
+}
+
+impl Keyframes {
+    /// Returns None without any keyframes, or if two share a generation.
+    pub fn new(mut points: Vec<(usize, f64)>) -> Option<Keyframes> {
+        points.sort_by_key(|&(generation, _)| generation);
+        if points.is_empty() || points.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return None;
+        }
+
+        Some(Keyframes { points })
+    }
+
+    pub fn constant(value: f64) -> Keyframes {
+        Keyframes { points: vec![(0, value)] }
+    }
+
+    pub fn value_at(&self, generation: usize) -> f64 {
+        let after = self.points.partition_point(|&(keyframe, _)| keyframe <= generation);
+        if after == 0 {
+            return self.points[0].1;
+        }
+        if after == self.points.len() {
+            return self.points[after - 1].1;
+        }
+
+        let (start, from) = self.points[after - 1];
+        let (end, to) = self.points[after];
+        from + (to - from) * (generation - start) as f64 / (end - start) as f64
+    }
+}
+
+/// Which rule a run follows at each generation, and the values of any parameters its rules take. Rules are given as
+/// strings, like `B3/S23`, so that the schedule can be saved as text as part of a `RunDescription` and the run
+/// repeated exactly.
+///
+/// The text has one entry per line: `at <generation> <rule>` switches to a rule, `repeat <period>` makes the switches
+/// start over every period generations, and `param <name> <generation>:<value> ...` gives a parameter's keyframes.
+/// Anything after a `#` is a comment.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuleSchedule {
+    switches: Vec<(usize, String)>,
+    period: Option<usize>,
+    parameters: BTreeMap<String, Keyframes>,
+}
+
+impl RuleSchedule {
+    pub fn constant(rule: &str) -> RuleSchedule {
+        Self::switching(vec![(0, rule.to_string())]).unwrap()
+    }
+
+    /// Switches to each rule at its generation. Returns None unless there's a rule from generation 0, or if two switches
+    /// share a generation.
+    pub fn switching(mut switches: Vec<(usize, String)>) -> Option<RuleSchedule> {
+        switches.sort_by_key(|&(generation, _)| generation);
+        if switches.first()?.0 != 0 || switches.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return None;
+        }
+
+        Some(RuleSchedule {
+            switches,
+            period: None,
+            parameters: BTreeMap::new(),
+        })
+    }
+
+    /// Cycles through the rules, following each one for `steps_per_rule` generations.
+    pub fn cycling(rules: &[&str], steps_per_rule: usize) -> Option<RuleSchedule> {
+        if steps_per_rule == 0 {
+            return None;
+        }
+        let switches = rules.iter().enumerate().map(|(index, &rule)| (index * steps_per_rule, rule.to_string())).collect();
+        let mut schedule = Self::switching(switches)?;
+        schedule.period = Some(rules.len() * steps_per_rule);
+
+        Some(schedule)
+    }
+
+    pub fn with_parameter(mut self, name: &str, keyframes: Keyframes) -> RuleSchedule {
+        self.parameters.insert(name.to_string(), keyframes);
+        self
+    }
+
+    pub fn parse(text: &str) -> Option<RuleSchedule> {
+        let mut switches = vec![];
+        let mut period = None;
+        let mut parameters = BTreeMap::new();
+
+        for line in text.lines().map(|line| line.split('#').next().unwrap().trim()).filter(|line| !line.is_empty()) {
+            let (keyword, rest) = line.split_once(char::is_whitespace)?;
+            let rest = rest.trim();
+            match keyword {
+                "at" => {
+                    let (generation, rule) = rest.split_once(char::is_whitespace)?;
+                    switches.push((generation.parse().ok()?, rule.trim().to_string()));
+                },
+                "repeat" => period = Some(rest.parse::<usize>().ok().filter(|&period| period > 0)?),
+                "param" => {
+                    let mut words = rest.split_whitespace();
+                    let name = words.next()?;
+                    let points = words.map(|point| {
+                        let (generation, value) = point.split_once(':')?;
+                        Some((generation.parse().ok()?, value.parse().ok()?))
+                    }).collect::<Option<Vec<(usize, f64)>>>()?;
+                    parameters.insert(name.to_string(), Keyframes::new(points)?);
+                },
+                _ => return None,
+            }
+        }
+
+        let mut schedule = Self::switching(switches)?;
+        if period.is_some_and(|period| schedule.switches.last().unwrap().0 >= period) {
+            return None;
+        }
+        schedule.period = period;
+        schedule.parameters = parameters;
+
+        Some(schedule)
+    }
+
+    /// Writes the schedule in the format `parse` reads.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (generation, rule) in &self.switches {
+            text.push_str(&format!("at {} {}\n", generation, rule));
+        }
+        if let Some(period) = self.period {
+            text.push_str(&format!("repeat {}\n", period));
+        }
+        for (name, keyframes) in &self.parameters {
+            let points: Vec<String> = keyframes.points.iter().map(|(generation, value)| format!("{}:{}", generation, value)).collect();
+            text.push_str(&format!("param {} {}\n", name, points.join(" ")));
+        }
+
+        text
+    }
+
+    /// Every rule the schedule switches to, in order.
+    pub fn get_rules(&self) -> Vec<&str> {
+        self.switches.iter().map(|(_, rule)| rule.as_str()).collect()
+    }
+
+    pub fn rule_at(&self, generation: usize) -> &str {
+        let generation = self.period.map_or(generation, |period| generation % period);
+        let index = self.switches.partition_point(|&(start, _)| start <= generation) - 1;
+        &self.switches[index].1
+    }
+
+    pub fn parameters_at(&self, generation: usize) -> BTreeMap<String, f64> {
+        self.parameters.iter().map(|(name, keyframes)| (name.clone(), keyframes.value_at(generation))).collect()
+    }
+}
+
+/// Builds a rule from a rule string and the parameter values for a generation, returning None if it can't read the
+/// string. The generation is passed in too, so stochastic rules can seed themselves differently every generation.
+pub type BuildRuleF<T> = dyn Fn(&str, &BTreeMap<String, f64>, usize) -> Option<Box<AdvanceCellF<T>>>;
+
+/// An environment that follows a rule schedule. Before each generation, the rule for it is built from the schedule.
+pub struct ScheduledRun<T> {
+    env: Environment<T>,
+    schedule: RuleSchedule,
+    build_rule: Box<BuildRuleF<T>>,
+    generation: usize,
+}
+
+impl<T: Copy + PartialEq> ScheduledRun<T> {
+    /// Returns None if any rule in the schedule can't be built.
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> T, schedule: RuleSchedule, build_rule: Box<BuildRuleF<T>>) -> Option<ScheduledRun<T>> {
+        let parameters = schedule.parameters_at(0);
+        if schedule.get_rules().iter().any(|rule| build_rule(rule, &parameters, 0).is_none()) {
+            return None;
+        }
+
+        let first_rule = build_rule(schedule.rule_at(0), &parameters, 0)?;
+        Some(ScheduledRun {
+            env: Environment::new(width, height, initial_cell_producer, first_rule),
+            schedule,
+            build_rule,
+            generation: 0,
+        })
+    }
+
+    pub fn get_environment(&self) -> &Environment<T> {
+        &self.env
+    }
+
+    pub fn get_environment_mut(&mut self) -> &mut Environment<T> {
+        &mut self.env
+    }
+
+    pub fn get_schedule(&self) -> &RuleSchedule {
+        &self.schedule
+    }
+
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The rule the next generation will follow.
+    pub fn current_rule(&self) -> &str {
+        self.schedule.rule_at(self.generation)
+    }
+
+    pub fn advance(&mut self) {
+        let parameters = self.schedule.parameters_at(self.generation);
+        let rule = (self.build_rule)(self.schedule.rule_at(self.generation), &parameters, self.generation).expect("Rule stopped building");
+        self.env.set_rule(rule);
+        self.env.advance();
+        self.generation += 1;
+    }
+}
+
+/// How the grid of a run starts out.
+#[derive(Debug, PartialEq, Clone)]
+pub enum InitialCells {
+    /// Each cell is 1 with probability `density`, drawn from a generator seeded with `seed`.
+    Soup { seed: u64, density: f64 },
+    /// The body of an RLE pattern, without its header, placed in the top left corner.
+    Pattern(String),
+}
+
+/// Everything needed to repeat a run: the grid size, how the grid starts, the family of rules the schedule's rule
+/// strings belong to, like `life-like`, and the schedule.
+///
+/// The text is the schedule's, plus a `size <width> <height>` line, a `family <name>` line, and either a
+/// `soup <seed> <density>` line or a `pattern <rle>` line with the pattern's body on one line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RunDescription {
+    width: usize,
+    height: usize,
+    initial: InitialCells,
+    family: String,
+    schedule: RuleSchedule,
+}
+
+impl RunDescription {
+    /// Returns None if the soup's density isn't between 0 and 1, or the pattern doesn't parse or doesn't fit.
+    pub fn new(width: usize, height: usize, initial: InitialCells, family: &str, schedule: RuleSchedule) -> Option<RunDescription> {
+        let description = RunDescription {
+            width,
+            height,
+            initial,
+            family: family.to_string(),
+            schedule,
+        };
+        match &description.initial {
+            InitialCells::Soup { density, .. } if !(0.0..=1.0).contains(density) => None,
+            InitialCells::Pattern(_) => description.initial_grid().map(|_| description),
+            InitialCells::Soup { .. } => Some(description),
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<RunDescription> {
+        let mut size = None;
+        let mut initial = None;
+        let mut family = None;
+        let mut schedule_lines = vec![];
+
+        for line in text.lines() {
+            let content = line.split('#').next().unwrap().trim();
+            let (keyword, rest) = content.split_once(char::is_whitespace).unwrap_or((content, ""));
+            let rest = rest.trim();
+            match keyword {
+                "size" => {
+                    let (width, height) = rest.split_once(char::is_whitespace)?;
+                    size = Some((width.parse().ok()?, height.trim().parse().ok()?));
+                },
+                "soup" => {
+                    let (seed, density) = rest.split_once(char::is_whitespace)?;
+                    initial = Some(InitialCells::Soup { seed: seed.parse().ok()?, density: density.trim().parse().ok()? });
+                },
+                "pattern" => initial = Some(InitialCells::Pattern(rest.to_string())),
+                "family" => family = Some(rest),
+                _ => schedule_lines.push(line),
+            }
+        }
+
+        let (width, height) = size?;
+        Self::new(width, height, initial?, family?, RuleSchedule::parse(&schedule_lines.join("\n"))?)
+    }
+
+    /// Writes the description in the format `parse` reads.
+    pub fn to_text(&self) -> String {
+        let initial = match &self.initial {
+            InitialCells::Soup { seed, density } => format!("soup {} {}", seed, density),
+            InitialCells::Pattern(body) => format!("pattern {}", body),
+        };
+
+        format!("size {} {}\n{}\nfamily {}\n{}", self.width, self.height, initial, self.family, self.schedule.to_text())
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get_initial(&self) -> &InitialCells {
+        &self.initial
+    }
+
+    pub fn get_family(&self) -> &str {
+        &self.family
+    }
+
+    pub fn get_schedule(&self) -> &RuleSchedule {
+        &self.schedule
+    }
+
+    /// The grid the run starts from, or None if the pattern doesn't parse or doesn't fit.
+    pub fn initial_grid(&self) -> Option<Grid<u8>> {
+        match &self.initial {
+            InitialCells::Soup { seed, density } => {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                let cells: Vec<u8> = (0..self.width * self.height).map(|_| rng.random_bool(*density) as u8).collect();
+                Some(Grid::new(self.width, self.height, |x, y| cells[y * self.width + x]))
+            },
+            InitialCells::Pattern(body) => {
+                let pattern = parse_rle(&format!("x = {}, y = {}\n{}", self.width, self.height, body))?;
+                let mut grid = Grid::new_filled(self.width, self.height, 0);
+                for (x, y, state) in pattern.cells {
+                    grid.set_cell(x, y, state);
+                }
+                Some(grid)
+            },
+        }
+    }
+
+    /// Starts the run, with `build_rule` reading the family's rule strings. Returns None if any rule in the schedule
+    /// can't be built.
+    pub fn start(&self, build_rule: Box<BuildRuleF<u8>>) -> Option<ScheduledRun<u8>> {
+        let grid = self.initial_grid()?;
+        ScheduledRun::new(self.width, self.height, |x, y| grid.get_cell(x, y), self.schedule.clone(), build_rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_types::conway::fixtures;
+    use crate::cell_types::epidemic::{Epidemic, EpidemicCell};
+    use crate::cell_types::isotropic::LookupRule;
+    use crate::grid::Boundary;
+
+    fn build_life_like(rule: &str, _parameters: &BTreeMap<String, f64>, _generation: usize) -> Option<Box<AdvanceCellF<u8>>> {
+        LookupRule::parse(rule).map(|rule| rule.rule(Boundary::Wrapping))
+    }
+
+    #[test]
+    fn test_keyframes_interpolate() {
+        let keyframes = Keyframes::new(vec![(10, 1.0), (0, 0.0), (20, 0.0)]).unwrap();
+
+        let values: Vec<f64> = [0, 5, 10, 15, 20, 100].iter().map(|&generation| keyframes.value_at(generation)).collect();
+        assert_eq!(values, vec![0.0, 0.5, 1.0, 0.5, 0.0, 0.0]);
+        assert_eq!(Keyframes::new(vec![(3, 1.0), (3, 2.0)]), None);
+    }
+
+    #[test]
+    fn test_schedule_text_round_trip() {
+        let text = "at 0 B3/S23  # Life\nat 1 B36/S23\nrepeat 2\nparam p 0:0.1 50:0.6\n";
+        let schedule = RuleSchedule::parse(text).unwrap();
+
+        assert_eq!(schedule, RuleSchedule::cycling(&["B3/S23", "B36/S23"], 1).unwrap().with_parameter("p", Keyframes::new(vec![(0, 0.1), (50, 0.6)]).unwrap()));
+        assert_eq!(RuleSchedule::parse(&schedule.to_text()).unwrap(), schedule);
+        assert_eq!((schedule.rule_at(0), schedule.rule_at(7)), ("B3/S23", "B36/S23"));
+        assert_eq!(schedule.parameters_at(25)["p"], 0.35);
+
+        assert_eq!(RuleSchedule::parse("at 5 B3/S23\n"), None);
+        assert_eq!(RuleSchedule::parse("at 0 B3/S23\nat 4 B36/S23\nrepeat 4\n"), None);
+    }
+
+    #[test]
+    fn test_run_description_round_trip() {
+        let text = "size 20 10\nsoup 44 0.3\nfamily life-like\nat 0 B3/S23\nat 5 B36/S23\n";
+        let description = RunDescription::parse(text).unwrap();
+
+        assert_eq!(description.get_dimensions(), (20, 10));
+        assert_eq!(description.get_initial(), &InitialCells::Soup { seed: 44, density: 0.3 });
+        assert_eq!(description.get_family(), "life-like");
+        assert_eq!(description.get_schedule().get_rules(), vec!["B3/S23", "B36/S23"]);
+        assert_eq!(RunDescription::parse(&description.to_text()).unwrap(), description);
+
+        let glider = RunDescription::parse("size 8 8  # A glider\npattern bo$2bo$3o!\nfamily life-like\nat 0 B3/S23\n").unwrap();
+        assert_eq!(RunDescription::parse(&glider.to_text()).unwrap(), glider);
+        assert_eq!(glider.initial_grid().unwrap().get_cell(2, 2), 1);
+
+        assert_eq!(RunDescription::parse("size 20 10\nsoup 44 1.5\nfamily life-like\nat 0 B3/S23\n"), None);
+        assert_eq!(RunDescription::parse("size 2 2\npattern 3o!\nfamily life-like\nat 0 B3/S23\n"), None);
+        assert_eq!(RunDescription::parse("soup 44 0.3\nfamily life-like\nat 0 B3/S23\n"), None);
+    }
+
+    #[test]
+    fn test_run_description_repeats_run() {
+        let description = RunDescription::parse("size 16 16\nsoup 7 0.4\nfamily life-like\nat 0 B3/S23\nat 1 B36/S23\nrepeat 2\n").unwrap();
+        let mut first = description.start(Box::new(build_life_like)).unwrap();
+        let mut second = RunDescription::parse(&description.to_text()).unwrap().start(Box::new(build_life_like)).unwrap();
+
+        for _ in 0..10 {
+            first.advance();
+            second.advance();
+        }
+        assert_eq!(first.get_environment().get_grid(), second.get_environment().get_grid());
+    }
+
+    #[test]
+    fn test_alternating_rules_match_manual_switching() {
+        let schedule = RuleSchedule::cycling(&["B3/S23", "B36/S23"], 1).unwrap();
+        let mut run = ScheduledRun::new(20, 20, fixtures::soup, schedule, Box::new(build_life_like)).unwrap();
+        let mut manual = Environment::new(20, 20, fixtures::soup, build_life_like("B3/S23", &BTreeMap::new(), 0).unwrap());
+
+        for generation in 0..10 {
+            let rule = if generation % 2 == 0 { "B3/S23" } else { "B36/S23" };
+            assert_eq!(run.current_rule(), rule);
+            manual.set_rule(build_life_like(rule, &BTreeMap::new(), 0).unwrap());
+            manual.advance();
+            run.advance();
+            assert_eq!(run.get_environment().get_grid(), manual.get_grid());
+        }
+
+        let unreadable = RuleSchedule::switching(vec![(0, "B3/S23".to_string()), (4, "nonsense".to_string())]).unwrap();
+        assert!(ScheduledRun::new(20, 20, fixtures::soup, unreadable, Box::new(build_life_like)).is_none());
+    }
+
+    #[test]
+    fn test_interpolated_probability_reaches_rule() {
+        // The infection probability ramps up from 0, so the outbreak can't start until a few generations in.
+        let schedule = RuleSchedule::constant("sir").with_parameter("p", Keyframes::new(vec![(0, 0.0), (3, 0.0), (4, 1.0)]).unwrap());
+        let build = |_rule: &str, parameters: &BTreeMap<String, f64>, generation: usize| -> Option<Box<AdvanceCellF<EpidemicCell>>> {
+            Some(Epidemic::sir(parameters["p"], 100).rule(generation as u64))
+        };
+        let mut run = ScheduledRun::new(5, 1, |x, _y| if x == 0 { EpidemicCell::Infected { remaining: 100 } } else { EpidemicCell::Susceptible }, schedule, Box::new(build)).unwrap();
+
+        for _ in 0..4 {
+            run.advance();
+            assert_eq!(run.get_environment().get_cell(1, 0), EpidemicCell::Susceptible);
+        }
+        run.advance();
+        assert!(matches!(run.get_environment().get_cell(1, 0), EpidemicCell::Infected { .. }));
+    }
+}
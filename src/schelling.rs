@@ -0,0 +1,187 @@
+use rand::rngs::StdRng;
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::{Rng, SeedableRng};
+use crate::grid::{Boundary, Grid};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Resident {
+    pub group: u8,
+    /// How many times this resident has relocated.
+    pub moves: usize,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SegregationStatistics {
+    pub unhappy: usize,
+    /// The average fraction of each resident's neighbors that are in the same group.
+    pub mean_similarity: f64,
+}
+
+/// Schelling's segregation model. A resident is unhappy when fewer than `tolerance` of its occupied neighbors are in its
+/// own group. Every step, residents take turns in a random order, and each one that's unhappy on its turn moves to a
+/// random vacant cell.
+pub struct Schelling {
+    grid: Grid<Option<Resident>>,
+    tolerance: f64,
+    boundary: Boundary,
+    rng: StdRng,
+    history: Vec<SegregationStatistics>,
+}
+
+impl Schelling {
+    pub fn new(width: usize, height: usize, tolerance: f64, initial_cell_producer: impl Fn(usize, usize) -> Option<u8>, seed: u64) -> Schelling {
+        let grid = Grid::new(width, height, |x, y| initial_cell_producer(x, y).map(|group| Resident { group, moves: 0 }));
+        let mut schelling = Schelling {
+            grid,
+            tolerance,
+            boundary: Boundary::Wrapping,
+            rng: StdRng::seed_from_u64(seed),
+            history: vec![],
+        };
+        schelling.history.push(schelling.statistics());
+
+        schelling
+    }
+
+    /// Fills the grid randomly, leaving `vacancy` of the cells empty, and splitting the rest evenly between the groups.
+    /// Panics if there are no groups.
+    pub fn new_random(width: usize, height: usize, n_groups: u8, vacancy: f64, tolerance: f64, seed: u64) -> Schelling {
+        assert!(n_groups > 0, "Residents need at least one group");
+        let mut rng = StdRng::seed_from_u64(seed);
+        let cells: Vec<Option<u8>> = (0..width * height).map(|_| {
+            if rng.random_bool(vacancy) { None } else { Some(rng.random_range(0..n_groups)) }
+        }).collect();
+
+        Self::new(width, height, tolerance, |x, y| cells[y * width + x], seed)
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> Option<Resident> {
+        self.grid.get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.grid.get_width(), self.grid.get_height())
+    }
+
+    /// The statistics at the start, and after every step since.
+    pub fn get_history(&self) -> &[SegregationStatistics] {
+        &self.history
+    }
+
+    /// The fraction of occupied neighbors in the same group, or None if the cell is empty or has no neighbors.
+    pub fn similarity(&self, x: usize, y: usize) -> Option<f64> {
+        let resident = self.get_cell(x, y)?;
+        let neighbors: Vec<Resident> = self.grid.get_moore_neighborhood_around(x, y, self.boundary).flatten().collect();
+        if neighbors.is_empty() {
+            return None;
+        }
+
+        let same_group = neighbors.iter().filter(|neighbor| neighbor.group == resident.group).count();
+        Some(same_group as f64 / neighbors.len() as f64)
+    }
+
+    pub fn is_unhappy(&self, x: usize, y: usize) -> bool {
+        self.similarity(x, y).is_some_and(|similarity| similarity < self.tolerance)
+    }
+
+    pub fn statistics(&self) -> SegregationStatistics {
+        let (width, height) = self.get_dimensions();
+        let mut unhappy = 0;
+        let mut similarities = vec![];
+
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(similarity) = self.similarity(x, y) {
+                    similarities.push(similarity);
+                    if similarity < self.tolerance {
+                        unhappy += 1;
+                    }
+                }
+            }
+        }
+
+        let mean_similarity = if similarities.is_empty() { 1.0 } else { similarities.iter().sum::<f64>() / similarities.len() as f64 };
+        SegregationStatistics { unhappy, mean_similarity }
+    }
+
+    pub fn advance(&mut self) {
+        let (width, height) = self.get_dimensions();
+        let all_cells: Vec<(usize, usize)> = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).collect();
+
+        let mut residents: Vec<(usize, usize)> = all_cells.iter().copied().filter(|&(x, y)| self.get_cell(x, y).is_some()).collect();
+        let mut vacancies: Vec<(usize, usize)> = all_cells.iter().copied().filter(|&(x, y)| self.get_cell(x, y).is_none()).collect();
+        residents.shuffle(&mut self.rng);
+
+        for (x, y) in residents {
+            if !self.is_unhappy(x, y) {
+                continue;
+            }
+
+            let Some(&vacancy) = vacancies.choose(&mut self.rng) else { break };
+            self.grid.swap_cells((x, y), vacancy);
+            self.grid.get_cell_mut(vacancy.0, vacancy.1).as_mut().unwrap().moves += 1;
+
+            let vacancy_index = vacancies.iter().position(|&cell| cell == vacancy).unwrap();
+            vacancies[vacancy_index] = (x, y);
+        }
+
+        self.history.push(self.statistics());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        let schelling = Schelling::new(3, 3, 0.5, |x, y| match (x, y) {
+            (1, 1) | (0, 0) => Some(0),
+            (2, 2) | (2, 1) | (0, 2) => Some(1),
+            _ => None,
+        }, 0);
+
+        assert_eq!(schelling.similarity(1, 1), Some(0.25));
+        assert!(schelling.is_unhappy(1, 1));
+        assert_eq!(schelling.similarity(0, 1), None);
+    }
+
+    #[test]
+    fn test_unhappy_resident_moves_and_happy_resident_stays() {
+        let mut schelling = Schelling::new(5, 1, 0.5, |x, _y| match x {
+            0 => Some(0),
+            1 => Some(1),
+            2 => Some(1),
+            _ => None,
+        }, 0);
+        schelling.boundary = Boundary::Bounded;
+
+        schelling.advance();
+
+        assert_eq!(schelling.get_cell(2, 0).map(|resident| resident.moves), Some(0));
+        assert_eq!(schelling.get_cell(0, 0), None);
+        let moved = (3..5).filter_map(|x| schelling.get_cell(x, 0)).find(|resident| resident.group == 0);
+        assert_eq!(moved.map(|resident| resident.moves), Some(1));
+    }
+
+    #[test]
+    fn test_segregation_increases() {
+        let mut schelling = Schelling::new_random(40, 40, 2, 0.1, 0.5, 35);
+        let initial = schelling.get_history()[0];
+
+        for _ in 0..30 {
+            schelling.advance();
+        }
+
+        let last = *schelling.get_history().last().unwrap();
+        assert_eq!(schelling.get_history().len(), 31);
+        assert!(last.mean_similarity > initial.mean_similarity);
+        assert!(last.unhappy < initial.unhappy);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_zero_groups() {
+        Schelling::new_random(10, 10, 0, 0.1, 0.5, 0);
+    }
+}
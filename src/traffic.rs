@@ -0,0 +1,339 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::grid::{Boundary, Grid};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct FlowStatistics {
+    /// Vehicles per cell.
+    pub density: f64,
+    /// The average distance moved per vehicle in the last step.
+    pub mean_velocity: f64,
+    /// Vehicles passing a point per step, which is density times mean velocity.
+    pub flow: f64,
+}
+
+impl FlowStatistics {
+    fn new(n_vehicles: usize, n_cells: usize, total_distance: usize) -> FlowStatistics {
+        let density = n_vehicles as f64 / n_cells as f64;
+        let mean_velocity = if n_vehicles == 0 { 0.0 } else { total_distance as f64 / n_vehicles as f64 };
+
+        FlowStatistics {
+            density,
+            mean_velocity,
+            flow: density * mean_velocity,
+        }
+    }
+}
+
+/// The Nagel–Schreckenberg model of single-lane traffic on a circular road. Each cell is either empty or holds a vehicle
+/// with its current velocity. Every step, all vehicles simultaneously accelerate, brake to avoid the vehicle ahead,
+/// randomly slow down, then move forward by their velocity.
+pub struct NagelSchreckenberg {
+    road: Vec<Option<u8>>,
+    max_velocity: u8,
+    slowdown_probability: f64,
+    rng: StdRng,
+    last_distance: usize,
+}
+
+fn assert_probability(probability: f64, name: &str) {
+    assert!((0.0..=1.0).contains(&probability), "The {} must be between 0 and 1, not {}", name, probability);
+}
+
+impl NagelSchreckenberg {
+    /// Panics if the slowdown probability isn't between 0 and 1.
+    pub fn new(length: usize, max_velocity: u8, slowdown_probability: f64, seed: u64) -> NagelSchreckenberg {
+        assert_probability(slowdown_probability, "slowdown probability");
+
+        NagelSchreckenberg {
+            road: vec![None; length],
+            max_velocity,
+            slowdown_probability,
+            rng: StdRng::seed_from_u64(seed),
+            last_distance: 0,
+        }
+    }
+
+    /// Places stationary vehicles in random cells, with each cell occupied with the given probability. Panics if the
+    /// density or slowdown probability isn't between 0 and 1.
+    pub fn new_random(length: usize, density: f64, max_velocity: u8, slowdown_probability: f64, seed: u64) -> NagelSchreckenberg {
+        assert_probability(density, "density");
+        let mut model = Self::new(length, max_velocity, slowdown_probability, seed);
+        for position in 0..length {
+            if model.rng.random_bool(density) {
+                model.road[position] = Some(0);
+            }
+        }
+
+        model
+    }
+
+    pub fn get_cell(&self, position: usize) -> Option<u8> {
+        self.road[position]
+    }
+
+    pub fn set_cell(&mut self, position: usize, vehicle: Option<u8>) {
+        self.road[position] = vehicle;
+    }
+
+    pub fn get_length(&self) -> usize {
+        self.road.len()
+    }
+
+    pub fn count_vehicles(&self) -> usize {
+        self.road.iter().filter(|cell| cell.is_some()).count()
+    }
+
+    fn gap_ahead(&self, position: usize) -> usize {
+        let length = self.road.len();
+        (1..length).find(|distance| self.road[(position + distance) % length].is_some()).map_or(length - 1, |distance| distance - 1)
+    }
+
+    pub fn advance(&mut self) {
+        let length = self.road.len();
+        let mut next_road = vec![None; length];
+        self.last_distance = 0;
+
+        for position in 0..length {
+            let Some(velocity) = self.road[position] else { continue };
+
+            let mut velocity = velocity.saturating_add(1).min(self.max_velocity);
+            velocity = velocity.min(self.gap_ahead(position).min(u8::MAX as usize) as u8);
+            if velocity > 0 && self.rng.random_bool(self.slowdown_probability) {
+                velocity -= 1;
+            }
+
+            next_road[(position + velocity as usize) % length] = Some(velocity);
+            self.last_distance += velocity as usize;
+        }
+
+        self.road = next_road;
+    }
+
+    pub fn flow_statistics(&self) -> FlowStatistics {
+        FlowStatistics::new(self.count_vehicles(), self.road.len(), self.last_distance)
+    }
+}
+
+/// Runs the model at each density, and averages the flow over `measured_steps` after letting it settle for
+/// `warmup_steps`. The result is the fundamental diagram of traffic flow, as (density, flow) pairs. Panics if any density,
+/// or the slowdown probability, isn't between 0 and 1.
+pub fn nagel_schreckenberg_fundamental_diagram(length: usize, max_velocity: u8, slowdown_probability: f64, densities: &[f64], warmup_steps: usize, measured_steps: usize, seed: u64) -> Vec<(f64, f64)> {
+    assert_probability(slowdown_probability, "slowdown probability");
+    for &density in densities {
+        assert_probability(density, "density");
+    }
+
+    densities.iter().map(|&density| {
+        let mut model = NagelSchreckenberg::new_random(length, density, max_velocity, slowdown_probability, seed);
+        for _ in 0..warmup_steps {
+            model.advance();
+        }
+
+        let mut total_flow = 0.0;
+        for _ in 0..measured_steps {
+            model.advance();
+            total_flow += model.flow_statistics().flow;
+        }
+
+        (model.flow_statistics().density, total_flow / measured_steps.max(1) as f64)
+    }).collect()
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BmlCell {
+    Empty,
+    /// Moves east on even steps.
+    Red,
+    /// Moves south on odd steps.
+    Blue,
+}
+
+/// The Biham–Middleton–Levine traffic model on a torus. Red cars try to move one cell east on even steps, and blue cars
+/// try to move one cell south on odd steps. A car only moves if the cell ahead of it was empty at the start of the step.
+pub struct BihamMiddletonLevine {
+    grid: Grid<BmlCell>,
+    generation: usize,
+    last_moved: usize,
+}
+
+impl BihamMiddletonLevine {
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> BmlCell) -> BihamMiddletonLevine {
+        BihamMiddletonLevine {
+            grid: Grid::new(width, height, initial_cell_producer),
+            generation: 0,
+            last_moved: 0,
+        }
+    }
+
+    /// Fills each cell with a car with probability density, with red and blue cars equally likely. Panics if the density
+    /// isn't between 0 and 1.
+    pub fn new_random(width: usize, height: usize, density: f64, seed: u64) -> BihamMiddletonLevine {
+        assert_probability(density, "density");
+        let mut rng = StdRng::seed_from_u64(seed);
+        let cells: Vec<BmlCell> = (0..width * height).map(|_| {
+            if !rng.random_bool(density) {
+                BmlCell::Empty
+            } else if rng.random_bool(0.5) {
+                BmlCell::Red
+            } else {
+                BmlCell::Blue
+            }
+        }).collect();
+
+        Self::new(width, height, |x, y| cells[y * width + x])
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> BmlCell {
+        self.grid.get_cell(x, y)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.grid.get_width(), self.grid.get_height())
+    }
+
+    pub fn count(&self, cell: BmlCell) -> usize {
+        let (width, height) = self.get_dimensions();
+        (0..height).map(|y| (0..width).filter(|&x| self.get_cell(x, y) == cell).count()).sum()
+    }
+
+    pub fn advance(&mut self) {
+        let (moving, offset) = if self.generation.is_multiple_of(2) { (BmlCell::Red, (1, 0)) } else { (BmlCell::Blue, (0, 1)) };
+        let (width, height) = self.get_dimensions();
+        let mut next_grid = self.grid.clone();
+        self.last_moved = 0;
+
+        for y in 0..height {
+            for x in 0..width {
+                if self.grid.get_cell(x, y) != moving {
+                    continue;
+                }
+
+                let (ahead_x, ahead_y) = self.grid.get_neighbor_coord(x, y, offset, Boundary::Wrapping).unwrap();
+                if self.grid.get_cell(ahead_x, ahead_y) == BmlCell::Empty {
+                    next_grid.set_cell(x, y, BmlCell::Empty);
+                    next_grid.set_cell(ahead_x, ahead_y, moving);
+                    self.last_moved += 1;
+                }
+            }
+        }
+
+        self.grid = next_grid;
+        self.generation += 1;
+    }
+
+    /// Statistics for the cars that were allowed to move in the last step, so density only counts that color.
+    pub fn flow_statistics(&self) -> FlowStatistics {
+        let (width, height) = self.get_dimensions();
+        let last_moving = if !self.generation.is_multiple_of(2) { BmlCell::Red } else { BmlCell::Blue };
+
+        FlowStatistics::new(self.count(last_moving), width * height, self.last_moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lone_vehicle_reaches_max_velocity() {
+        let mut road = NagelSchreckenberg::new(50, 5, 0.0, 0);
+        road.set_cell(0, Some(0));
+
+        let mut positions = vec![];
+        for _ in 0..7 {
+            road.advance();
+            positions.push((0..50).find(|&position| road.get_cell(position).is_some()).unwrap());
+        }
+
+        assert_eq!(positions, vec![1, 3, 6, 10, 15, 20, 25]);
+        assert_eq!(road.flow_statistics().mean_velocity, 5.0);
+    }
+
+    #[test]
+    fn test_vehicles_brake_for_vehicle_ahead() {
+        let mut road = NagelSchreckenberg::new(10, 5, 0.0, 0);
+        road.set_cell(0, Some(5));
+        road.set_cell(3, Some(0));
+
+        road.advance();
+
+        assert_eq!(road.get_cell(2), Some(2));
+        assert_eq!(road.get_cell(4), Some(1));
+    }
+
+    #[test]
+    fn test_vehicles_are_conserved_and_never_collide() {
+        let mut road = NagelSchreckenberg::new_random(200, 0.3, 5, 0.3, 34);
+        let n_vehicles = road.count_vehicles();
+
+        for _ in 0..300 {
+            road.advance();
+            assert_eq!(road.count_vehicles(), n_vehicles);
+        }
+    }
+
+    #[test]
+    fn test_fundamental_diagram_jams_at_high_density() {
+        let diagram = nagel_schreckenberg_fundamental_diagram(300, 5, 0.2, &[0.05, 0.15, 0.9], 200, 200, 34);
+
+        assert_eq!(diagram.len(), 3);
+        assert!(diagram[1].1 > diagram[0].1);
+        assert!(diagram[1].1 > diagram[2].1);
+    }
+
+    #[test]
+    fn test_bml_colors_alternate() {
+        let mut model = BihamMiddletonLevine::new(3, 3, |x, y| match (x, y) {
+            (0, 0) => BmlCell::Red,
+            (1, 1) => BmlCell::Blue,
+            _ => BmlCell::Empty,
+        });
+
+        model.advance();
+        assert_eq!(model.get_cell(1, 0), BmlCell::Red);
+        assert_eq!(model.get_cell(1, 1), BmlCell::Blue);
+
+        model.advance();
+        assert_eq!(model.get_cell(1, 0), BmlCell::Red);
+        assert_eq!(model.get_cell(1, 2), BmlCell::Blue);
+        assert_eq!(model.flow_statistics().flow, 1.0 / 9.0);
+    }
+
+    #[test]
+    fn test_bml_blocked_car_waits() {
+        let mut model = BihamMiddletonLevine::new(3, 1, |x, _y| if x < 2 { BmlCell::Red } else { BmlCell::Empty });
+
+        model.advance();
+
+        assert_eq!(model.get_cell(0, 0), BmlCell::Red);
+        assert_eq!(model.get_cell(1, 0), BmlCell::Empty);
+        assert_eq!(model.get_cell(2, 0), BmlCell::Red);
+    }
+
+    #[test]
+    fn test_bml_conserves_cars() {
+        let mut model = BihamMiddletonLevine::new_random(32, 32, 0.35, 34);
+        let reds = model.count(BmlCell::Red);
+        let blues = model.count(BmlCell::Blue);
+
+        for _ in 0..100 {
+            model.advance();
+        }
+
+        assert_eq!(model.count(BmlCell::Red), reds);
+        assert_eq!(model.count(BmlCell::Blue), blues);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_slowdown_probability_past_one() {
+        NagelSchreckenberg::new(10, 5, 1.5, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_nan_density() {
+        BihamMiddletonLevine::new_random(8, 8, f64::NAN, 0);
+    }
+}
@@ -0,0 +1,341 @@
+use crate::environment::Agent;
+use crate::grid::{Boundary, Grid};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    fn turned(self, turn: Turn) -> Direction {
+        const CLOCKWISE: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+        let index = CLOCKWISE.iter().position(|&direction| direction == self).unwrap();
+
+        let quarter_turns = match turn {
+            Turn::NoTurn => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        };
+
+        CLOCKWISE[(index + quarter_turns) % 4]
+    }
+
+    fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Turn {
+    NoTurn,
+    Right,
+    UTurn,
+    Left,
+}
+
+impl Turn {
+    // Golly's turmite notation encodes turns as bit flags.
+    fn from_golly_code(code: usize) -> Option<Turn> {
+        match code {
+            1 => Some(Turn::NoTurn),
+            2 => Some(Turn::Right),
+            4 => Some(Turn::UTurn),
+            8 => Some(Turn::Left),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Transition {
+    pub write_color: u8,
+    pub turn: Turn,
+    pub next_state: usize,
+}
+
+/// The rule table of a 2D Turing machine, indexed by the turmite's internal state, then by the color it's standing on.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TurmiteRule {
+    transitions: Vec<Vec<Transition>>,
+}
+
+impl TurmiteRule {
+    /// Returns None if any state is missing a transition for a color, or if a transition leads to a color or state
+    /// that doesn't exist.
+    pub fn new(transitions: Vec<Vec<Transition>>) -> Option<TurmiteRule> {
+        let n_colors = transitions.first()?.len();
+        let n_states = transitions.len();
+
+        let is_valid = transitions.iter().all(|state_transitions| {
+            state_transitions.len() == n_colors && state_transitions.iter().all(|transition| {
+                (transition.write_color as usize) < n_colors && transition.next_state < n_states
+            })
+        });
+
+        if is_valid && n_colors > 0 { Some(TurmiteRule { transitions }) } else { None }
+    }
+
+    /// Parses a multi-color ant like "RL" (Langton's ant) or "RLLR". When standing on color i, the ant turns according
+    /// to the i-th letter and paints the cell with color i + 1. L and R turn, N doesn't turn, and U turns around.
+    pub fn from_ant_string(ant_string: &str) -> Option<TurmiteRule> {
+        let turns = ant_string.chars().map(|char| match char.to_ascii_uppercase() {
+            'L' => Some(Turn::Left),
+            'R' => Some(Turn::Right),
+            'N' => Some(Turn::NoTurn),
+            'U' => Some(Turn::UTurn),
+            _ => None,
+        }).collect::<Option<Vec<Turn>>>()?;
+
+        if turns.len() > u8::MAX as usize {
+            return None;
+        }
+
+        let n_colors = turns.len();
+        let transitions = turns.iter().enumerate().map(|(color, &turn)| Transition {
+            write_color: ((color + 1) % n_colors) as u8,
+            turn,
+            next_state: 0,
+        }).collect();
+
+        Self::new(vec![transitions])
+    }
+
+    /// Parses Golly's turmite notation, where each state is a list of {write_color, turn, next_state} triples, one per
+    /// color. Langton's ant is {{{1,2,0},{0,8,0}}}.
+    pub fn from_golly_string(spec: &str) -> Option<TurmiteRule> {
+        let chars: Vec<char> = spec.chars().filter(|char| !char.is_whitespace()).collect();
+        let mut index = 0;
+        let parsed = parse_nested(&chars, &mut index)?;
+        if index != chars.len() {
+            return None;
+        }
+
+        let transitions = parsed.as_list()?.iter().map(|state| {
+            state.as_list()?.iter().map(|triple| {
+                match triple.as_list()?.iter().map(Nested::as_number).collect::<Option<Vec<usize>>>()?.as_slice() {
+                    &[write_color, turn_code, next_state] => Some(Transition {
+                        write_color: u8::try_from(write_color).ok()?,
+                        turn: Turn::from_golly_code(turn_code)?,
+                        next_state,
+                    }),
+                    _ => None,
+                }
+            }).collect::<Option<Vec<Transition>>>()
+        }).collect::<Option<Vec<Vec<Transition>>>>()?;
+
+        Self::new(transitions)
+    }
+
+    pub fn langtons_ant() -> TurmiteRule {
+        Self::from_ant_string("RL").unwrap()
+    }
+
+    pub fn get_n_colors(&self) -> usize {
+        self.transitions[0].len()
+    }
+
+    pub fn get_transition(&self, state: usize, color: u8) -> Transition {
+        self.transitions[state][color as usize]
+    }
+}
+
+enum Nested {
+    Number(usize),
+    List(Vec<Nested>),
+}
+
+impl Nested {
+    fn as_number(&self) -> Option<usize> {
+        match self {
+            Nested::Number(number) => Some(*number),
+            Nested::List(_) => None,
+        }
+    }
+
+    fn as_list(&self) -> Option<&Vec<Nested>> {
+        match self {
+            Nested::Number(_) => None,
+            Nested::List(list) => Some(list),
+        }
+    }
+}
+
+fn parse_nested(chars: &[char], index: &mut usize) -> Option<Nested> {
+    if *chars.get(*index)? == '{' {
+        *index += 1;
+        let mut list = vec![];
+        loop {
+            list.push(parse_nested(chars, index)?);
+            match chars.get(*index)? {
+                ',' => *index += 1,
+                '}' => {
+                    *index += 1;
+                    return Some(Nested::List(list));
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    let start = *index;
+    while chars.get(*index).is_some_and(|char| char.is_ascii_digit()) {
+        *index += 1;
+    }
+
+    chars[start..*index].iter().collect::<String>().parse().ok().map(Nested::Number)
+}
+
+/// A turmite walking on a grid of colors. Every step, it looks up the transition for its state and the color it's
+/// standing on, paints the cell, turns, and moves forward one cell. At a bounded edge, it turns but stays in place.
+#[derive(Debug, Clone)]
+pub struct Turmite {
+    x: usize,
+    y: usize,
+    direction: Direction,
+    state: usize,
+    rule: TurmiteRule,
+    boundary: Boundary,
+}
+
+impl Turmite {
+    pub fn new(x: usize, y: usize, direction: Direction, rule: TurmiteRule, boundary: Boundary) -> Turmite {
+        Turmite {
+            x,
+            y,
+            direction,
+            state: 0,
+            rule,
+            boundary,
+        }
+    }
+
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn get_state(&self) -> usize {
+        self.state
+    }
+}
+
+impl Agent<u8> for Turmite {
+    fn get_position(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    fn step(&mut self, grid: &mut Grid<u8>) {
+        // Colors that this rule doesn't know about are treated as the background color.
+        let color = grid.get_cell(self.x, self.y);
+        let color = if (color as usize) < self.rule.get_n_colors() { color } else { 0 };
+        let transition = self.rule.get_transition(self.state, color);
+
+        grid.set_cell(self.x, self.y, transition.write_color);
+        self.direction = self.direction.turned(transition.turn);
+        self.state = transition.next_state;
+
+        if let Some((next_x, next_y)) = grid.get_neighbor_coord(self.x, self.y, self.direction.offset(), self.boundary) {
+            self.x = next_x;
+            self.y = next_y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+
+    fn cells_of(env: &Environment<u8>) -> Vec<u8> {
+        let (width, height) = env.get_dimensions();
+        (0..width * height).map(|i| env.get_cell(i % width, i / width)).collect()
+    }
+
+    #[test]
+    fn test_langtons_ant_first_steps() {
+        let mut env = Environment::new_static(9, 9, |_x, _y| 0);
+        env.add_agent(Box::new(Turmite::new(4, 4, Direction::North, TurmiteRule::langtons_ant(), Boundary::Wrapping)));
+
+        env.advance();
+        assert_eq!(env.get_cell(4, 4), 1);
+        assert_eq!(env.get_agents()[0].get_position(), (5, 4));
+
+        env.advance();
+        assert_eq!(env.get_cell(5, 4), 1);
+        assert_eq!(env.get_agents()[0].get_position(), (5, 5));
+
+        env.advance();
+        env.advance();
+        assert_eq!(env.get_agents()[0].get_position(), (4, 4));
+
+        // Back on a painted cell, so the ant turns left and clears it.
+        env.advance();
+        assert_eq!(env.get_cell(4, 4), 0);
+        assert_eq!(env.get_agents()[0].get_position(), (3, 4));
+    }
+
+    #[test]
+    fn test_golly_string_matches_ant_string() {
+        let golly_rule = TurmiteRule::from_golly_string("{{{1, 2, 0}, {0, 8, 0}}}").unwrap();
+        assert_eq!(golly_rule, TurmiteRule::langtons_ant());
+
+        let golly_rllr = TurmiteRule::from_golly_string("{{{1,2,0},{2,8,0},{3,8,0},{0,2,0}}}").unwrap();
+        assert_eq!(golly_rllr, TurmiteRule::from_ant_string("RLLR").unwrap());
+    }
+
+    #[test]
+    fn test_golly_string_multi_state() {
+        // Fibonacci spiral turmite.
+        let rule = TurmiteRule::from_golly_string("{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}").unwrap();
+
+        assert_eq!(rule.get_n_colors(), 2);
+        assert_eq!(rule.get_transition(1, 1), Transition { write_color: 0, turn: Turn::NoTurn, next_state: 0 });
+    }
+
+    #[test]
+    fn test_invalid_rules_rejected() {
+        assert_eq!(TurmiteRule::from_ant_string(""), None);
+        assert_eq!(TurmiteRule::from_ant_string("RXL"), None);
+        assert_eq!(TurmiteRule::from_golly_string("{{{1,3,0},{0,8,0}}}"), None);
+        assert_eq!(TurmiteRule::from_golly_string("{{{1,2,1},{0,8,0}}}"), None);
+        assert_eq!(TurmiteRule::from_golly_string("{{{1,2,0},{0,8,0}}"), None);
+    }
+
+    #[test]
+    fn test_multiple_ants_share_grid() {
+        let mut single = Environment::new_static(20, 20, |_x, _y| 0);
+        single.add_agent(Box::new(Turmite::new(5, 5, Direction::North, TurmiteRule::from_ant_string("RLLR").unwrap(), Boundary::Wrapping)));
+
+        let mut double = Environment::new_static(20, 20, |_x, _y| 0);
+        double.add_agent(Box::new(Turmite::new(5, 5, Direction::North, TurmiteRule::from_ant_string("RLLR").unwrap(), Boundary::Wrapping)));
+        double.add_agent(Box::new(Turmite::new(14, 14, Direction::South, TurmiteRule::langtons_ant(), Boundary::Wrapping)));
+
+        for _ in 0..200 {
+            single.advance();
+            double.advance();
+        }
+
+        assert_eq!(double.get_agents().len(), 2);
+        assert_ne!(cells_of(&single), cells_of(&double));
+    }
+
+    #[test]
+    fn test_bounded_ant_stays_on_grid() {
+        let mut env = Environment::new_static(3, 3, |_x, _y| 0);
+        env.add_agent(Box::new(Turmite::new(2, 0, Direction::North, TurmiteRule::langtons_ant(), Boundary::Bounded)));
+
+        for _ in 0..100 {
+            env.advance();
+            let (x, y) = env.get_agents()[0].get_position();
+            assert!(x < 3 && y < 3);
+        }
+    }
+}
@@ -0,0 +1,716 @@
+extern crate eframe;
+
+
+use std::cmp::Ordering;
+use std::time::Duration;
+use eframe::egui;
+use eframe::egui::{Color32, Pos2, Rect};
+use crate::cell_types::golly_rule::GollyRule;
+use crate::cell_types::loops::LoopColony;
+use crate::cell_types::epidemic::{EpidemicCell, EpidemicRun};
+use crate::coupling::CoupledEnvironments;
+use crate::environment::{AdvanceCellF, Environment};
+use crate::falling_sand::{FallingSand, Material, MATERIALS};
+use crate::ising::{Dynamics, Ising, CRITICAL_TEMPERATURE, UP};
+use crate::larger_than_life::LargerThanLife;
+use crate::lattice_gas::{average_velocity_field, Lattice, OBSTACLE};
+use crate::schedule::ScheduledRun;
+use crate::layers::LayeredEnvironment;
+use crate::memory::{MemoryEnvironment, MemoryWeighting};
+use crate::sandpile::{Sandpile, MAX_STABLE};
+use crate::schelling::Schelling;
+use crate::traffic::{BihamMiddletonLevine, BmlCell, NagelSchreckenberg};
+use crate::wator::{Species, Wator};
+
+const INITIAL_WINDOW_SIZE: [usize; 2] = [750, 750];
+
+/// Anything that can be drawn as a grid of colored cells and stepped once per frame.
+pub trait CanvasModel {
+    fn get_dimensions(&self) -> (usize, usize);
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32;
+
+    fn advance(&mut self);
+
+    /// Markers drawn on top of the cells, like the position of agents.
+    fn markers(&self) -> Vec<(usize, usize, Color32)> {
+        vec![]
+    }
+
+    /// How many generations a cell has been in its current state, for models that track it. The renderer can color
+    /// cells by age instead of by state.
+    fn cell_age(&self, _x: usize, _y: usize) -> Option<usize> {
+        None
+    }
+
+    fn on_click(&mut self, _x: usize, _y: usize) {}
+
+    /// Called every frame that the primary button is held down over a cell, so that cells can be painted by dragging.
+    fn on_paint(&mut self, _x: usize, _y: usize) {}
+
+    fn has_controls(&self) -> bool {
+        false
+    }
+
+    /// Widgets shown in a floating controls window, if has_controls is true.
+    fn controls(&mut self, _ui: &mut egui::Ui) {}
+}
+
+struct GuiState<M> {
+    model: M,
+    controls_rect: Option<Rect>,
+    color_by_age: bool,
+}
+
+impl<M: CanvasModel> GuiState<M> {
+    fn new(model: M) -> Self {
+        GuiState {
+            model,
+            controls_rect: None,
+            color_by_age: false,
+        }
+    }
+}
+
+pub fn start_gui<M: CanvasModel + 'static>(title: &str, model: M) -> eframe::Result {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([INITIAL_WINDOW_SIZE[0] as f32, INITIAL_WINDOW_SIZE[1] as f32]),
+        ..Default::default()
+    };
+
+    let state = GuiState::new(model);
+
+    eframe::run_native(
+        title,
+        options,
+        Box::new(|_cc| {
+            Ok(Box::<GuiState<M>>::new(state))
+        }),
+    )
+}
+
+impl<M: CanvasModel> GuiState<M> {
+    fn calculate_pixel_width(&self, ctx: &egui::Context) -> f32 {
+        let (env_width, env_height) = self.model.get_dimensions();
+        let window_rect = ctx.input(|i| i.viewport().inner_rect.unwrap());
+        let window_width = window_rect.width();
+        let window_height = window_rect.height();
+
+        (window_width / env_width as f32).min(window_height / env_height as f32)
+    }
+
+    fn window_dimensions(&self, ctx: &egui::Context) -> (f32, f32) {
+        let window_rect = ctx.input(|i| i.viewport().inner_rect.unwrap());
+
+        (window_rect.width(), window_rect.height())
+    }
+}
+
+fn map_dimension(value: f32, current_min: f32, current_max: f32, new_min: f32, new_max: f32) -> f32 {
+    let current_range = current_max - current_min;
+    let current_perc = (value - current_min) / (current_range - current_min);
+
+    let new_range = new_max - new_min;
+    (new_range * current_perc) + new_min
+}
+
+/// Colors a cell by how many generations it has been in its current state, from white for cells that just changed,
+/// through yellow and red, fading to dark blue for cells that have been stable for hundreds of generations.
+pub fn age_color(age: usize) -> Color32 {
+    let t = ((age as f32 + 1.0).ln() / 300f32.ln()).min(1.0);
+    let saturation = (t * 4.0).min(1.0) * 0.9;
+    egui::ecolor::Hsva::new(0.66 * t, saturation, 1.0 - 0.75 * t, 1.0).into()
+}
+
+/// Picks evenly spaced hues for each state, with state 0 always black.
+pub fn palette_color(state: usize, n_states: usize) -> Color32 {
+    if state == 0 || n_states <= 1 {
+        return Color32::BLACK;
+    }
+
+    let hue = (state - 1) as f32 / (n_states - 1) as f32;
+    egui::ecolor::Hsva::new(hue, 0.85, 0.95, 1.0).into()
+}
+
+/// A palette for any number of states. With a black background, state 0 is black and the rest are spread around the
+/// color wheel. Without one, every state gets its own hue, which suits rules like cyclic CAs where no state is special.
+pub fn generate_palette(n_states: usize, black_background: bool) -> Vec<Color32> {
+    (0..n_states).map(|state| {
+        if black_background {
+            palette_color(state, n_states)
+        } else {
+            egui::ecolor::Hsva::new(state as f32 / n_states as f32, 0.85, 0.95, 1.0).into()
+        }
+    }).collect()
+}
+
+impl<M: CanvasModel> eframe::App for GuiState<M> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let (window_width, window_height) = self.window_dimensions(ctx);
+        let block_width = self.calculate_pixel_width(ctx);
+
+        let (env_width, env_height) = self.model.get_dimensions();
+        let (pointer_pos, clicked, held) = ctx.input(|i| (i.pointer.interact_pos(), i.pointer.primary_clicked(), i.pointer.primary_down()));
+        let pointer_on_controls = pointer_pos.is_some_and(|pos| self.controls_rect.is_some_and(|rect| rect.contains(pos)));
+        if let (Some(pos), false) = (pointer_pos, pointer_on_controls) {
+            let env_x = map_dimension(pos.x, 0f32, window_width, 0f32, env_width as f32);
+            let env_y = map_dimension(pos.y, 0f32, window_height, 0f32, env_height as f32);
+            if env_x >= 0.0 && env_y >= 0.0 && (env_x as usize) < env_width && (env_y as usize) < env_height {
+                if clicked {
+                    self.model.on_click(env_x as usize, env_y as usize);
+                }
+                if held {
+                    self.model.on_paint(env_x as usize, env_y as usize);
+                }
+            }
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::Frame::canvas(ui.style()).show(ui, |ui| {
+                let painter = ui.painter();
+
+                let to_window = |env_x: usize, env_y: usize| Pos2 {
+                    x: map_dimension(env_x as f32, 0f32, env_width as f32, 0f32, window_width),
+                    y: map_dimension(env_y as f32, 0f32, env_height as f32, 0f32, window_height),
+                };
+
+                for env_y in 0..env_height {
+                    for env_x in 0..env_width {
+                        let min = to_window(env_x, env_y);
+                        let rect = Rect {
+                            min,
+                            max: Pos2 { x: min.x + block_width, y: min.y + block_width }
+                        };
+                        let age = self.model.cell_age(env_x, env_y).filter(|_| self.color_by_age);
+                        let color = age.map_or_else(|| self.model.cell_color(env_x, env_y), age_color);
+                        painter.rect_filled(rect, 1.0, color);
+                    }
+                }
+
+                for (env_x, env_y, color) in self.model.markers() {
+                    let min = to_window(env_x, env_y);
+                    let center = Pos2 { x: min.x + block_width / 2.0, y: min.y + block_width / 2.0 };
+                    painter.circle_filled(center, block_width / 2.5, color);
+                }
+            });
+        });
+
+        let tracks_ages = self.model.cell_age(0, 0).is_some();
+        if self.model.has_controls() || tracks_ages {
+            let controls = egui::Window::new("Controls").resizable(false).show(ctx, |ui| {
+                if tracks_ages {
+                    ui.checkbox(&mut self.color_by_age, "Color by age");
+                }
+                self.model.controls(ui);
+            });
+            self.controls_rect = controls.map(|inner| inner.response.rect);
+        }
+
+        self.model.advance();
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
+}
+
+/// The colors from a Golly rule's `@COLORS` section, with generated colors for any states it leaves out.
+pub fn golly_palette(rule: &GollyRule) -> Vec<Color32> {
+    let n_states = rule.get_n_states() as usize;
+    (0..n_states).map(|state| match rule.get_color(state as u8) {
+        Some([red, green, blue]) => Color32::from_rgb(red, green, blue),
+        None => palette_color(state, n_states),
+    }).collect()
+}
+
+/// Environments of numbered states, like turmite colors, drawn using a palette indexed by state. States past the end of
+/// the palette are drawn white. Agents are drawn as white markers.
+pub struct PaletteEnvironment {
+    pub env: Environment<u8>,
+    pub palette: Vec<Color32>,
+}
+
+impl CanvasModel for PaletteEnvironment {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.env.get_dimensions()
+    }
+
+    fn cell_age(&self, x: usize, y: usize) -> Option<usize> {
+        self.env.get_age(x, y)
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        self.palette.get(self.env.get_cell(x, y) as usize).copied().unwrap_or(Color32::WHITE)
+    }
+
+    fn advance(&mut self) {
+        self.env.advance();
+    }
+
+    fn markers(&self) -> Vec<(usize, usize, Color32)> {
+        self.env.get_agents().iter().map(|agent| {
+            let (x, y) = agent.get_position();
+            (x, y, Color32::WHITE)
+        }).collect()
+    }
+}
+
+impl CanvasModel for Sandpile {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        match self.get_cell(x, y) {
+            0 => Color32::BLACK,
+            1 => Color32::from_rgb(40, 80, 200),
+            2 => Color32::from_rgb(230, 200, 40),
+            grains if grains <= MAX_STABLE => Color32::from_rgb(200, 40, 40),
+            _ => Color32::WHITE,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+
+    fn on_click(&mut self, x: usize, y: usize) {
+        self.add_grain(x, y);
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Avalanches: {}", self.get_avalanches().len()));
+        match self.get_avalanches().last() {
+            Some(avalanche) => ui.label(format!("Last avalanche: {} topplings over {} cells", avalanche.topplings, avalanche.area)),
+            None => ui.label("Click to drop a grain"),
+        };
+    }
+}
+
+fn material_color(material: Material) -> Color32 {
+    match material {
+        Material::Empty => Color32::BLACK,
+        Material::Sand => Color32::from_rgb(220, 190, 110),
+        Material::Water => Color32::from_rgb(40, 90, 220),
+        Material::Stone => Color32::GRAY,
+        Material::Fire => Color32::from_rgb(240, 90, 20),
+    }
+}
+
+/// The falling-sand world, along with the material currently used to paint it.
+pub struct FallingSandCanvas {
+    pub world: FallingSand,
+    pub brush: Material,
+}
+
+impl CanvasModel for FallingSandCanvas {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.world.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        material_color(self.world.get_cell(x, y))
+    }
+
+    fn advance(&mut self) {
+        self.world.advance();
+    }
+
+    fn on_paint(&mut self, x: usize, y: usize) {
+        self.world.set_cell(x, y, self.brush);
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        for material in MATERIALS {
+            ui.radio_value(&mut self.brush, material, format!("{:?}", material));
+        }
+    }
+}
+
+/// A lattice gas, drawn as its averaged velocity field. Hue shows the direction of flow, and brightness shows speed.
+pub struct LatticeGasCanvas {
+    env: Environment<u8>,
+    lattice: Lattice,
+    block_size: usize,
+    velocity_field: Vec<Vec<(f32, f32)>>,
+}
+
+impl LatticeGasCanvas {
+    pub fn new(env: Environment<u8>, lattice: Lattice, block_size: usize) -> LatticeGasCanvas {
+        let velocity_field = average_velocity_field(env.get_grid(), lattice, block_size);
+
+        LatticeGasCanvas {
+            env,
+            lattice,
+            block_size,
+            velocity_field,
+        }
+    }
+}
+
+impl CanvasModel for LatticeGasCanvas {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.env.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        if self.env.get_cell(x, y) & OBSTACLE != 0 {
+            return Color32::GRAY;
+        }
+
+        let (velocity_x, velocity_y) = self.velocity_field[y / self.block_size][x / self.block_size];
+        let hue = (-velocity_y).atan2(velocity_x) / std::f32::consts::TAU;
+        let speed = (velocity_x * velocity_x + velocity_y * velocity_y).sqrt();
+
+        egui::ecolor::Hsva::new(hue.rem_euclid(1.0), 0.9, speed.min(1.0), 1.0).into()
+    }
+
+    fn advance(&mut self) {
+        self.env.advance();
+        self.velocity_field = average_velocity_field(self.env.get_grid(), self.lattice, self.block_size);
+    }
+}
+
+impl CanvasModel for EpidemicRun {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_environment().get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        match self.get_environment().get_cell(x, y) {
+            EpidemicCell::Susceptible => Color32::from_rgb(60, 60, 60),
+            EpidemicCell::Exposed { .. } => Color32::from_rgb(240, 170, 30),
+            EpidemicCell::Infected { .. } => Color32::RED,
+            EpidemicCell::Recovered => Color32::from_rgb(40, 160, 80),
+            EpidemicCell::Vaccinated => Color32::from_rgb(60, 110, 220),
+        }
+    }
+
+    fn advance(&mut self) {
+        if !self.is_over() {
+            self.advance();
+        }
+    }
+}
+
+impl CanvasModel for LoopColony {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_environment().get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        palette_color(self.get_environment().get_cell(x, y) as usize, self.get_n_states() as usize)
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Generation: {}", self.get_history().len() - 1));
+        ui.label(format!("Loops: {}", self.get_history().last().unwrap()));
+    }
+}
+
+impl CanvasModel for LargerThanLife {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        palette_color(self.get_cell(x, y) as usize, self.get_rule().n_states as usize)
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Generation: {}", self.get_generation()));
+        ui.label(format!("Alive: {}", self.count_alive()));
+    }
+}
+
+impl CanvasModel for ScheduledRun<u8> {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_environment().get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        if self.get_environment().get_cell(x, y) == 0 { Color32::BLACK } else { Color32::WHITE }
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Generation: {}", self.get_generation()));
+        ui.label(format!("Rule: {}", self.current_rule()));
+    }
+}
+
+impl CanvasModel for BihamMiddletonLevine {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        match self.get_cell(x, y) {
+            BmlCell::Empty => Color32::WHITE,
+            BmlCell::Red => Color32::RED,
+            BmlCell::Blue => Color32::BLUE,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+}
+
+impl CanvasModel for Wator {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        match self.get_cell(x, y).map(|creature| creature.species) {
+            None => Color32::from_rgb(0, 0, 80),
+            Some(Species::Fish) => Color32::GREEN,
+            Some(Species::Shark) => Color32::RED,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+}
+
+impl CanvasModel for Schelling {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        match self.get_cell(x, y) {
+            None => Color32::WHITE,
+            Some(resident) => palette_color(resident.group as usize + 1, 5),
+        }
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+}
+
+impl CanvasModel for Ising {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        if self.get_cell(x, y) == UP { Color32::WHITE } else { Color32::BLACK }
+    }
+
+    fn advance(&mut self) {
+        self.advance();
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.temperature, 0.0..=5.0).text("Temperature"));
+        ui.add(egui::Slider::new(&mut self.external_field, -1.0..=1.0).text("External field"));
+        if ui.button(format!("Critical temperature ({:.3})", CRITICAL_TEMPERATURE)).clicked() {
+            self.temperature = CRITICAL_TEMPERATURE;
+        }
+        ui.radio_value(&mut self.dynamics, Dynamics::Metropolis, "Metropolis");
+        ui.radio_value(&mut self.dynamics, Dynamics::HeatBath, "Heat bath");
+
+        let measurement = self.get_history().last().unwrap();
+        ui.label(format!("Magnetization: {:.3}", measurement.magnetization));
+        ui.label(format!("Energy per spin: {:.3}", measurement.energy));
+    }
+}
+
+/// A space-time diagram of a Nagel–Schreckenberg road. Each row is the road at one step, with the newest step at the
+/// bottom. Faster vehicles are drawn brighter.
+pub struct TrafficSpaceTimeCanvas {
+    road: NagelSchreckenberg,
+    history: Vec<Vec<Option<u8>>>,
+    max_velocity: u8,
+}
+
+impl TrafficSpaceTimeCanvas {
+    pub fn new(road: NagelSchreckenberg, max_velocity: u8, n_rows: usize) -> TrafficSpaceTimeCanvas {
+        let empty_row = vec![None; road.get_length()];
+
+        TrafficSpaceTimeCanvas {
+            road,
+            history: vec![empty_row; n_rows],
+            max_velocity,
+        }
+    }
+}
+
+impl CanvasModel for TrafficSpaceTimeCanvas {
+    fn get_dimensions(&self) -> (usize, usize) {
+        (self.road.get_length(), self.history.len())
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        match self.history[y][x] {
+            None => Color32::BLACK,
+            Some(velocity) => {
+                let brightness = 0.3 + 0.7 * velocity as f32 / self.max_velocity.max(1) as f32;
+                egui::ecolor::Hsva::new(0.12, 0.8, brightness, 1.0).into()
+            },
+        }
+    }
+
+    fn advance(&mut self) {
+        self.road.advance();
+        self.history.remove(0);
+        self.history.push((0..self.road.get_length()).map(|position| self.road.get_cell(position)).collect());
+    }
+}
+
+/// The same rule run from the same pattern with and without memory, side by side with the run with memory on the left.
+pub struct MemoryComparisonCanvas {
+    with_memory: MemoryEnvironment<u8>,
+    without: Environment<u8>,
+}
+
+impl MemoryComparisonCanvas {
+    pub fn new(width: usize, height: usize, initial_cell_producer: impl Fn(usize, usize) -> u8, rule: impl Fn() -> Box<AdvanceCellF<u8>>, weighting: MemoryWeighting) -> MemoryComparisonCanvas {
+        MemoryComparisonCanvas {
+            with_memory: MemoryEnvironment::new(width, height, &initial_cell_producer, rule(), weighting),
+            without: Environment::new(width, height, &initial_cell_producer, rule()),
+        }
+    }
+}
+
+impl CanvasModel for MemoryComparisonCanvas {
+    fn get_dimensions(&self) -> (usize, usize) {
+        let (width, height) = self.without.get_dimensions();
+        (2 * width + 1, height)
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        let width = self.without.get_dimensions().0;
+        let state = match x.cmp(&width) {
+            Ordering::Less => self.with_memory.get_cell(x, y),
+            Ordering::Equal => return Color32::DARK_GRAY,
+            Ordering::Greater => self.without.get_cell(x - width - 1, y),
+        };
+
+        if state == 0 { Color32::BLACK } else { Color32::WHITE }
+    }
+
+    fn advance(&mut self) {
+        self.with_memory.advance();
+        self.without.advance();
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        let weighting = self.with_memory.get_weighting();
+        ui.label(format!("Generation: {}", self.with_memory.get_generation()));
+        ui.label(format!("Memory depth {}, decay {}", weighting.depth, weighting.decay));
+    }
+}
+
+/// How one layer of a layered environment is drawn. States past the end of the palette are drawn white, and
+/// transparent palette entries let the layers below show through.
+pub struct LayerStyle {
+    pub palette: Vec<Color32>,
+    pub visible: bool,
+    pub opacity: f32,
+}
+
+/// A layered environment drawn from the bottom layer up, blending each visible layer over the ones below it.
+pub struct LayeredCanvas {
+    pub env: LayeredEnvironment<u8>,
+    pub styles: Vec<LayerStyle>,
+}
+
+impl CanvasModel for LayeredCanvas {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.env.get_dimensions()
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        self.styles.iter().enumerate().filter(|(_, style)| style.visible).fold(Color32::BLACK, |below, (layer, style)| {
+            let color = style.palette.get(self.env.get_cell(layer, x, y) as usize).copied().unwrap_or(Color32::WHITE);
+            let alpha = style.opacity * color.a() as f32 / 255.0;
+            let blend = |under: u8, over: u8| (under as f32 + (over as f32 - under as f32) * alpha).round() as u8;
+            Color32::from_rgb(blend(below.r(), color.r()), blend(below.g(), color.g()), blend(below.b(), color.b()))
+        })
+    }
+
+    fn advance(&mut self) {
+        self.env.advance();
+    }
+
+    fn has_controls(&self) -> bool {
+        true
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        for (layer, style) in self.styles.iter_mut().enumerate() {
+            ui.checkbox(&mut style.visible, self.env.get_layer_name(layer));
+            ui.add(egui::Slider::new(&mut style.opacity, 0.0..=1.0).text("Opacity"));
+        }
+    }
+}
+
+/// Coupled boards drawn at their positions on one canvas, with a palette indexed by state. Anywhere not covered by a
+/// board is drawn dark gray.
+pub struct CoupledCanvas {
+    pub boards: CoupledEnvironments<u8>,
+    pub positions: Vec<(usize, usize)>,
+    pub palette: Vec<Color32>,
+}
+
+impl CanvasModel for CoupledCanvas {
+    fn get_dimensions(&self) -> (usize, usize) {
+        self.positions.iter().enumerate().fold((0, 0), |(width, height), (board, &(left, top))| {
+            let (board_width, board_height) = self.boards.get_dimensions(board);
+            (width.max(left + board_width), height.max(top + board_height))
+        })
+    }
+
+    fn cell_color(&self, x: usize, y: usize) -> Color32 {
+        self.positions.iter().enumerate().find_map(|(board, &(left, top))| {
+            let (width, height) = self.boards.get_dimensions(board);
+            let inside = (left..left + width).contains(&x) && (top..top + height).contains(&y);
+            inside.then(|| self.palette.get(self.boards.get_cell(board, x - left, y - top) as usize).copied().unwrap_or(Color32::WHITE))
+        }).unwrap_or(Color32::DARK_GRAY)
+    }
+
+    fn advance(&mut self) {
+        self.boards.advance();
+    }
+}
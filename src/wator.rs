@@ -0,0 +1,236 @@
+use rand::rngs::StdRng;
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::SeedableRng;
+use crate::grid::{Boundary, Grid, VON_NEUMANN_OFFSETS};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Species {
+    Fish,
+    Shark,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Creature {
+    pub species: Species,
+    /// Chronons since the creature was born or last bred.
+    pub breed_timer: u32,
+    /// Chronons since the shark last ate. Always 0 for fish.
+    pub starve_timer: u32,
+}
+
+impl Creature {
+    pub fn new(species: Species) -> Creature {
+        Creature {
+            species,
+            breed_timer: 0,
+            starve_timer: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct WatorParameters {
+    /// Chronons a fish must survive before it can breed.
+    pub fish_breed_time: u32,
+    /// Chronons a shark must survive before it can breed.
+    pub shark_breed_time: u32,
+    /// Chronons a shark can go without eating before it starves.
+    pub shark_starve_time: u32,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Population {
+    pub fish: usize,
+    pub sharks: usize,
+}
+
+/// Dewdney's Wa-Tor predator-prey model on a toroidal ocean. Every chronon, each creature acts once, in a random order.
+/// Fish swim to a random empty neighbor. Sharks eat a random neighboring fish if there is one, and otherwise swim like
+/// fish. A creature that moves after reaching its breeding age leaves a newborn behind, and sharks that go too long
+/// without eating die.
+pub struct Wator {
+    ocean: Grid<Option<Creature>>,
+    parameters: WatorParameters,
+    rng: StdRng,
+    history: Vec<Population>,
+}
+
+impl Wator {
+    pub fn new(width: usize, height: usize, parameters: WatorParameters, initial_cell_producer: impl Fn(usize, usize) -> Option<Species>, seed: u64) -> Wator {
+        let ocean = Grid::new(width, height, |x, y| initial_cell_producer(x, y).map(Creature::new));
+        let mut wator = Wator {
+            ocean,
+            parameters,
+            rng: StdRng::seed_from_u64(seed),
+            history: vec![],
+        };
+        wator.history.push(wator.count_population());
+
+        wator
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> Option<&Creature> {
+        self.ocean.get_cell_ref(x, y).as_ref()
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.ocean.get_width(), self.ocean.get_height())
+    }
+
+    pub fn count_population(&self) -> Population {
+        let (width, height) = self.get_dimensions();
+        let mut population = Population::default();
+
+        for y in 0..height {
+            for x in 0..width {
+                match self.get_cell(x, y).map(|creature| creature.species) {
+                    Some(Species::Fish) => population.fish += 1,
+                    Some(Species::Shark) => population.sharks += 1,
+                    None => {},
+                }
+            }
+        }
+
+        population
+    }
+
+    /// The population at the start, and after every chronon since.
+    pub fn get_history(&self) -> &[Population] {
+        &self.history
+    }
+
+    fn neighbors_where(&self, x: usize, y: usize, predicate: impl Fn(&Option<Creature>) -> bool) -> Vec<(usize, usize)> {
+        VON_NEUMANN_OFFSETS.iter()
+            .filter_map(|&offset| self.ocean.get_neighbor_coord(x, y, offset, Boundary::Wrapping))
+            .filter(|&(neighbor_x, neighbor_y)| predicate(self.ocean.get_cell_ref(neighbor_x, neighbor_y)))
+            .collect()
+    }
+
+    fn update_creature(&mut self, x: usize, y: usize, moved: &mut Grid<bool>) {
+        let Some(creature) = self.ocean.get_cell_mut(x, y).as_mut() else { return };
+        let species = creature.species;
+        creature.breed_timer += 1;
+        if species == Species::Shark {
+            creature.starve_timer += 1;
+        }
+
+        let fish_neighbors = if species == Species::Shark {
+            self.neighbors_where(x, y, |cell| cell.as_ref().is_some_and(|neighbor| neighbor.species == Species::Fish))
+        } else {
+            vec![]
+        };
+
+        let destination = if let Some(&prey) = fish_neighbors.choose(&mut self.rng) {
+            self.ocean.set_cell(prey.0, prey.1, None);
+            self.ocean.get_cell_mut(x, y).as_mut().unwrap().starve_timer = 0;
+            Some(prey)
+        } else {
+            self.neighbors_where(x, y, Option::is_none).choose(&mut self.rng).copied()
+        };
+
+        let creature = self.ocean.get_cell_ref(x, y).as_ref().unwrap();
+        if species == Species::Shark && creature.starve_timer >= self.parameters.shark_starve_time {
+            self.ocean.set_cell(x, y, None);
+            return;
+        }
+
+        let Some((to_x, to_y)) = destination else { return };
+        self.ocean.swap_cells((x, y), (to_x, to_y));
+        moved.set_cell(to_x, to_y, true);
+
+        let breed_time = match species {
+            Species::Fish => self.parameters.fish_breed_time,
+            Species::Shark => self.parameters.shark_breed_time,
+        };
+        let parent = self.ocean.get_cell_mut(to_x, to_y).as_mut().unwrap();
+        if parent.breed_timer >= breed_time {
+            parent.breed_timer = 0;
+            self.ocean.set_cell(x, y, Some(Creature::new(species)));
+            moved.set_cell(x, y, true);
+        }
+    }
+
+    pub fn advance(&mut self) {
+        let (width, height) = self.get_dimensions();
+        let mut moved = Grid::new_filled(width, height, false);
+
+        let mut occupied: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get_cell(x, y).is_some())
+            .collect();
+        occupied.shuffle(&mut self.rng);
+
+        for (x, y) in occupied {
+            if !moved.get_cell(x, y) {
+                self.update_creature(x, y, &mut moved);
+            }
+        }
+
+        self.history.push(self.count_population());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMETERS: WatorParameters = WatorParameters { fish_breed_time: 3, shark_breed_time: 10, shark_starve_time: 3 };
+
+    #[test]
+    fn test_fish_breed_after_breed_time() {
+        let mut wator = Wator::new(5, 5, PARAMETERS, |x, y| if (x, y) == (2, 2) { Some(Species::Fish) } else { None }, 35);
+
+        wator.advance();
+        wator.advance();
+        assert_eq!(wator.count_population().fish, 1);
+
+        wator.advance();
+        assert_eq!(wator.count_population().fish, 2);
+    }
+
+    #[test]
+    fn test_shark_starves_without_fish() {
+        let mut wator = Wator::new(5, 5, PARAMETERS, |x, y| if (x, y) == (2, 2) { Some(Species::Shark) } else { None }, 35);
+
+        wator.advance();
+        wator.advance();
+        assert_eq!(wator.count_population().sharks, 1);
+
+        wator.advance();
+        assert_eq!(wator.count_population().sharks, 0);
+    }
+
+    #[test]
+    fn test_shark_eats_neighboring_fish() {
+        let mut wator = Wator::new(3, 1, PARAMETERS, |x, _y| match x {
+            0 => Some(Species::Shark),
+            1 => Some(Species::Fish),
+            _ => None,
+        }, 35);
+        // Surround the fish so it can't escape before the shark acts, whatever the order.
+        wator.ocean.set_cell(2, 0, Some(Creature::new(Species::Shark)));
+
+        wator.advance();
+
+        assert_eq!(wator.count_population(), Population { fish: 0, sharks: 2 });
+        let shark = (0..3).filter_map(|x| wator.get_cell(x, 0)).find(|creature| creature.starve_timer == 0);
+        assert!(shark.is_some());
+    }
+
+    #[test]
+    fn test_history_records_every_chronon() {
+        let mut wator = Wator::new(30, 30, PARAMETERS, |x, y| match (x * 7 + y * 3) % 10 {
+            0 => Some(Species::Shark),
+            1..=4 => Some(Species::Fish),
+            _ => None,
+        }, 35);
+
+        for _ in 0..20 {
+            wator.advance();
+        }
+
+        assert_eq!(wator.get_history().len(), 21);
+        assert_eq!(*wator.get_history().last().unwrap(), wator.count_population());
+        assert!(wator.get_history().iter().all(|population| population.fish + population.sharks <= 900));
+    }
+}
@@ -16,8 +16,8 @@ impl Environment {
         let write_grid = Grid::new(width, height, initial_cell_producer);
 
         Environment {
-            read_grid: read_grid,
-            write_grid: write_grid,
+            read_grid,
+            write_grid,
         }
     }
 
@@ -25,7 +25,7 @@ impl Environment {
         Self::new(width, height, |_x, _y| CellType::Empty)
     }
 
-    fn swap_grids(&mut self) -> () {
+    fn swap_grids(&mut self) {
         swap(&mut self.read_grid, &mut self.write_grid);
     }
 
@@ -79,14 +79,27 @@ impl Environment {
         self.swap_grids();
     }
 
-    pub fn main_loop(&mut self, max_iters: usize) {
-        for _ in 0..max_iters {
-            println!("{self:?}");
+    /// Advances up to `max_iters` times, calling `should_continue` with the environment and the iteration before each
+    /// step, and stops early once it returns false. This is only for this standalone WireWorld; the generic
+    /// `crate::environment::Environment` running `cell_types::wireworld::wireworld_rule` has the observer API, which is
+    /// the supported way to watch a run.
+    pub fn run_while(&mut self, max_iters: usize, mut should_continue: impl FnMut(&Environment, usize) -> bool) {
+        for iteration in 0..max_iters {
+            if !should_continue(self, iteration) {
+                return;
+            }
 
             self.advance();
+        }
+    }
 
+    /// Prints every generation to the terminal, at about six generations a second.
+    pub fn main_loop(&mut self, max_iters: usize) {
+        self.run_while(max_iters, |env, _iteration| {
+            println!("{env:?}");
             sleep(Duration::from_millis(166));
-        }
+            true
+        });
     }
 }
 
@@ -218,4 +231,25 @@ mod tests {
         assert_eq!(env.read_grid.get_cell(1, 2), CellType::ElectronTail);
         assert_eq!(env.read_grid.get_cell(0, 2), CellType::ElectronHead);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_run_while_stops_when_it_returns_false() {
+        let mut env = Environment::new_empty(5, 1);
+        env.bulk_set_readable(vec![
+            (0, 0, CellType::ElectronHead),
+            (1, 0, CellType::Conductor),
+            (2, 0, CellType::Conductor),
+            (3, 0, CellType::Conductor),
+            (4, 0, CellType::Conductor),
+        ]);
+
+        let mut seen = vec![];
+        env.run_while(10, |env, iteration| {
+            seen.push(iteration);
+            env.get_cell(3, 0) != CellType::ElectronHead
+        });
+
+        assert_eq!(seen, vec![0, 1, 2, 3]);
+        assert_eq!(env.get_cell(3, 0), CellType::ElectronHead);
+    }
+}
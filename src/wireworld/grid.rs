@@ -68,10 +68,10 @@ impl TwoDimensionRangeIterator {
             current_x: min_x,
             current_y: min_y,
 
-            min_x: min_x,
-            max_x: max_x,
+            min_x,
+            max_x,
 
-            max_y: max_y,
+            max_y,
 
             done: false,
         }
@@ -98,7 +98,7 @@ impl Iterator for TwoDimensionRangeIterator {
             self.done = true;
         }
 
-        return Some(current);
+        Some(current)
     }
 }
 
@@ -108,12 +108,8 @@ fn get_moore_neighborhood_iterator(x: usize, y: usize, width: usize, height: usi
     let min_y = y.saturating_sub(1).clamp(0, height - 1);
     let max_y = y.saturating_add(1).clamp(0, height - 1);
 
-    TwoDimensionRangeIterator::new(min_x, max_x, min_y, max_y).filter_map(move |current| {
-        if current.0 == x && current.1 == y {
-            None
-        } else {
-            Some(current)
-        }
+    TwoDimensionRangeIterator::new(min_x, max_x, min_y, max_y).filter(move |current| {
+        current.0 != x || current.1 != y
     })
 }
 